@@ -0,0 +1,3 @@
+DROP TRIGGER IF EXISTS posts_set_updated_at ON posts;
+DROP TABLE IF EXISTS posts;
+DROP FUNCTION IF EXISTS polar_set_updated_at();
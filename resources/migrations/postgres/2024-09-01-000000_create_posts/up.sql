@@ -0,0 +1,32 @@
+-- Keep `updated_at` columns in sync with the last modification of their row
+
+CREATE OR REPLACE FUNCTION polar_set_updated_at() RETURNS trigger AS $$
+BEGIN
+    IF NEW IS DISTINCT FROM OLD AND NEW.updated_at IS NOT DISTINCT FROM OLD.updated_at THEN
+        NEW.updated_at := CURRENT_TIMESTAMP;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Posts
+
+CREATE TABLE posts (
+    id           SERIAL PRIMARY KEY,
+    slug         VARCHAR(255) NOT NULL UNIQUE,
+    title        VARCHAR(255) NOT NULL,
+    body         TEXT         NOT NULL DEFAULT '',
+    status       VARCHAR(16)  NOT NULL DEFAULT 'draft'
+                 CHECK (status IN ('draft', 'published')),
+    author       VARCHAR(64)  NOT NULL,
+    created_at   TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at   TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    published_at TIMESTAMPTZ
+);
+
+CREATE INDEX posts_status_published_at_idx ON posts (status, published_at DESC);
+CREATE INDEX posts_author_idx ON posts (author);
+
+CREATE TRIGGER posts_set_updated_at
+    BEFORE UPDATE ON posts
+    FOR EACH ROW EXECUTE PROCEDURE polar_set_updated_at();
@@ -0,0 +1,3 @@
+ALTER TABLE posts DROP CONSTRAINT IF EXISTS posts_author_fkey;
+DROP TRIGGER IF EXISTS users_set_updated_at ON users;
+DROP TABLE IF EXISTS users;
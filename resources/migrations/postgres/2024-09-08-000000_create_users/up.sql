@@ -0,0 +1,29 @@
+-- Users
+
+CREATE TABLE users (
+    id            SERIAL PRIMARY KEY,
+    username      VARCHAR(64)  NOT NULL UNIQUE,
+    email         VARCHAR(255) NOT NULL UNIQUE,
+    password_hash TEXT         NOT NULL,
+    display_name  VARCHAR(255) NOT NULL,
+    bio           TEXT         NOT NULL DEFAULT '',
+    disabled      BOOLEAN      NOT NULL DEFAULT FALSE,
+    created_at    TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at    TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER users_set_updated_at
+    BEFORE UPDATE ON users
+    FOR EACH ROW EXECUTE PROCEDURE polar_set_updated_at();
+
+-- Authors of already existing posts get a disabled account, with an unusable
+-- password, so that posts may reference their author
+
+INSERT INTO users (username, email, password_hash, display_name, disabled)
+SELECT DISTINCT author, author || '@localhost.invalid', '!', author, TRUE
+FROM posts
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE posts
+    ADD CONSTRAINT posts_author_fkey FOREIGN KEY (author)
+        REFERENCES users (username) ON UPDATE CASCADE;
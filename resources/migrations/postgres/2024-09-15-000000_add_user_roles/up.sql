@@ -0,0 +1,6 @@
+ALTER TABLE users
+    ADD COLUMN role VARCHAR(16) NOT NULL DEFAULT 'reader'
+        CHECK (role IN ('admin', 'editor', 'author', 'reader'));
+
+-- Every active account had full rights before roles existed, keep it that way
+UPDATE users SET role = 'admin' WHERE NOT disabled;
@@ -0,0 +1,2 @@
+ALTER TABLE posts DROP COLUMN body_html;
+ALTER TABLE posts RENAME COLUMN body_markdown TO body;
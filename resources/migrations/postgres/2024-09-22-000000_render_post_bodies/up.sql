@@ -0,0 +1,4 @@
+-- Post bodies are written in Markdown, their sanitized HTML rendering is
+-- cached alongside and filled in by `polar migrate` for existing posts
+ALTER TABLE posts RENAME COLUMN body TO body_markdown;
+ALTER TABLE posts ADD COLUMN body_html TEXT NOT NULL DEFAULT '';
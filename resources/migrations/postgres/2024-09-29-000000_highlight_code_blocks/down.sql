@@ -0,0 +1 @@
+UPDATE posts SET body_html = '';
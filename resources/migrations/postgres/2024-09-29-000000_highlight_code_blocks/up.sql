@@ -0,0 +1,2 @@
+-- Code blocks are now highlighted, have `polar migrate` render every post again
+UPDATE posts SET body_html = '';
@@ -0,0 +1,4 @@
+DROP TABLE post_categories;
+DROP TABLE post_tags;
+DROP TABLE categories;
+DROP TABLE tags;
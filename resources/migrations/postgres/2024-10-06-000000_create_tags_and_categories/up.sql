@@ -0,0 +1,46 @@
+-- Tags, loosely attached by authors
+
+CREATE TABLE tags (
+    id         SERIAL PRIMARY KEY,
+    slug       VARCHAR(255) NOT NULL UNIQUE,
+    name       VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER tags_set_updated_at
+    BEFORE UPDATE ON tags
+    FOR EACH ROW EXECUTE PROCEDURE polar_set_updated_at();
+
+-- Categories, curated by editors
+
+CREATE TABLE categories (
+    id          SERIAL PRIMARY KEY,
+    slug        VARCHAR(255) NOT NULL UNIQUE,
+    name        VARCHAR(255) NOT NULL,
+    description TEXT         NOT NULL DEFAULT '',
+    created_at  TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TRIGGER categories_set_updated_at
+    BEFORE UPDATE ON categories
+    FOR EACH ROW EXECUTE PROCEDURE polar_set_updated_at();
+
+-- Relations to posts
+
+CREATE TABLE post_tags (
+    post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    tag_id  INTEGER NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+    PRIMARY KEY (post_id, tag_id)
+);
+
+CREATE INDEX post_tags_tag_id_idx ON post_tags (tag_id);
+
+CREATE TABLE post_categories (
+    post_id     INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    category_id INTEGER NOT NULL REFERENCES categories (id) ON DELETE CASCADE,
+    PRIMARY KEY (post_id, category_id)
+);
+
+CREATE INDEX post_categories_category_id_idx ON post_categories (category_id);
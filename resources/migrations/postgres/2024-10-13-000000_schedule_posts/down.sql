@@ -0,0 +1,11 @@
+UPDATE posts SET status = 'draft' WHERE status = 'scheduled';
+UPDATE posts SET status = 'published' WHERE status = 'archived';
+
+DROP INDEX posts_scheduled_publish_at_idx;
+ALTER TABLE posts DROP CONSTRAINT posts_publish_at_check;
+ALTER TABLE posts DROP COLUMN publish_at;
+
+ALTER TABLE posts DROP CONSTRAINT posts_status_check;
+ALTER TABLE posts
+    ADD CONSTRAINT posts_status_check
+        CHECK (status IN ('draft', 'published'));
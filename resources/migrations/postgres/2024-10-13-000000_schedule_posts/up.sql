@@ -0,0 +1,13 @@
+-- Posts may be scheduled for a later publication, then archived
+
+ALTER TABLE posts DROP CONSTRAINT posts_status_check;
+ALTER TABLE posts
+    ADD CONSTRAINT posts_status_check
+        CHECK (status IN ('draft', 'scheduled', 'published', 'archived'));
+
+ALTER TABLE posts ADD COLUMN publish_at TIMESTAMPTZ;
+ALTER TABLE posts
+    ADD CONSTRAINT posts_publish_at_check
+        CHECK ((status = 'scheduled') = (publish_at IS NOT NULL));
+
+CREATE INDEX posts_scheduled_publish_at_idx ON posts (publish_at) WHERE status = 'scheduled';
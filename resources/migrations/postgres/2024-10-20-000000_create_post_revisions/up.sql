@@ -0,0 +1,14 @@
+-- Previous versions of posts, `author` being the user whose update replaced
+-- the version and `created_at` the date of that update
+
+CREATE TABLE post_revisions (
+    id            SERIAL PRIMARY KEY,
+    post_id       INTEGER      NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    slug          VARCHAR(255) NOT NULL,
+    title         VARCHAR(255) NOT NULL,
+    body_markdown TEXT         NOT NULL,
+    author        VARCHAR(64)  NOT NULL REFERENCES users (username) ON UPDATE CASCADE,
+    created_at    TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX post_revisions_post_id_idx ON post_revisions (post_id, id DESC);
@@ -0,0 +1,25 @@
+ALTER TABLE posts ADD COLUMN comments_enabled BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Comments, `author` being set for authenticated users only while anonymous
+-- ones give a name and optionally an email address
+
+CREATE TABLE comments (
+    id           SERIAL PRIMARY KEY,
+    post_id      INTEGER      NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    parent_id    INTEGER      REFERENCES comments (id) ON DELETE CASCADE,
+    author       VARCHAR(64)  REFERENCES users (username) ON UPDATE CASCADE,
+    author_name  VARCHAR(255) NOT NULL,
+    author_email VARCHAR(255),
+    body         TEXT         NOT NULL,
+    status       VARCHAR(16)  NOT NULL DEFAULT 'pending'
+                 CHECK (status IN ('pending', 'approved', 'spam', 'deleted')),
+    created_at   TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at   TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX comments_post_id_idx ON comments (post_id, created_at);
+CREATE INDEX comments_status_idx ON comments (status, created_at);
+
+CREATE TRIGGER comments_set_updated_at
+    BEFORE UPDATE ON comments
+    FOR EACH ROW EXECUTE PROCEDURE polar_set_updated_at();
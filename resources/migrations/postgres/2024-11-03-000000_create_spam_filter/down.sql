@@ -0,0 +1,7 @@
+ALTER TABLE comments
+    DROP COLUMN trained_as,
+    DROP COLUMN spam_score;
+
+DROP TABLE spam_corpus;
+
+DROP TABLE spam_tokens;
@@ -0,0 +1,21 @@
+-- Statistics of the naive Bayes comment spam filter, trained by moderators
+
+CREATE TABLE spam_tokens (
+    token VARCHAR(64) PRIMARY KEY,
+    spam  INTEGER     NOT NULL DEFAULT 0 CHECK (spam >= 0),
+    ham   INTEGER     NOT NULL DEFAULT 0 CHECK (ham >= 0)
+);
+
+-- Single row counting the comments the filter was trained with
+
+CREATE TABLE spam_corpus (
+    id   BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    spam INTEGER NOT NULL DEFAULT 0 CHECK (spam >= 0),
+    ham  INTEGER NOT NULL DEFAULT 0 CHECK (ham >= 0)
+);
+
+INSERT INTO spam_corpus DEFAULT VALUES;
+
+ALTER TABLE comments
+    ADD COLUMN spam_score REAL,
+    ADD COLUMN trained_as VARCHAR(8) CHECK (trained_as IN ('spam', 'ham'));
@@ -0,0 +1,9 @@
+DROP INDEX posts_search_vector_idx;
+
+DROP TRIGGER posts_set_search_vector ON posts;
+
+DROP FUNCTION polar_posts_search_vector();
+
+ALTER TABLE posts
+    DROP COLUMN search_vector,
+    DROP COLUMN language;
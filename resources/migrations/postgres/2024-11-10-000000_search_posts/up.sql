@@ -0,0 +1,29 @@
+-- Text search configuration posts are indexed with, such as 'english' or
+-- 'french', as listed by pg_ts_config
+
+ALTER TABLE posts
+    ADD COLUMN language      VARCHAR(64) NOT NULL DEFAULT 'english',
+    ADD COLUMN search_vector TSVECTOR;
+
+CREATE FUNCTION polar_posts_search_vector() RETURNS trigger AS $$
+BEGIN
+    NEW.search_vector :=
+        setweight(to_tsvector(NEW.language::regconfig, NEW.title), 'A') ||
+        setweight(to_tsvector(NEW.language::regconfig, NEW.body_markdown), 'B');
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER posts_set_search_vector
+    BEFORE INSERT OR UPDATE OF title, body_markdown, language ON posts
+    FOR EACH ROW EXECUTE PROCEDURE polar_posts_search_vector();
+
+-- Indexing existing posts is no modification of theirs
+
+ALTER TABLE posts DISABLE TRIGGER posts_set_updated_at;
+
+UPDATE posts SET language = language;
+
+ALTER TABLE posts ENABLE TRIGGER posts_set_updated_at;
+
+CREATE INDEX posts_search_vector_idx ON posts USING GIN (search_vector);
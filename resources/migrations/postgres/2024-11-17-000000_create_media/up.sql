@@ -0,0 +1,14 @@
+-- Uploaded files, stored once whoever uploads them again under the hex SHA-256
+-- hash of their content, `owner` being their first uploader
+
+CREATE TABLE media (
+    id         SERIAL PRIMARY KEY,
+    hash       VARCHAR(64)  NOT NULL UNIQUE,
+    owner      VARCHAR(64)  NOT NULL REFERENCES users (username) ON UPDATE CASCADE,
+    file_name  VARCHAR(255) NOT NULL,
+    mime_type  VARCHAR(255) NOT NULL,
+    size       BIGINT       NOT NULL CHECK (size >= 0),
+    created_at TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX media_owner_idx ON media (owner, created_at);
@@ -0,0 +1,5 @@
+ALTER TABLE media
+    DROP COLUMN color,
+    DROP COLUMN blurhash,
+    DROP COLUMN height,
+    DROP COLUMN width;
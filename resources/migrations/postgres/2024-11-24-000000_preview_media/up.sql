@@ -0,0 +1,8 @@
+-- Dimensions of uploaded images as displayed, along with what is shown of
+-- them while they load, left empty for other files
+
+ALTER TABLE media
+    ADD COLUMN width    INTEGER     CHECK (width > 0),
+    ADD COLUMN height   INTEGER     CHECK (height > 0),
+    ADD COLUMN blurhash VARCHAR(64),
+    ADD COLUMN color    VARCHAR(7);
@@ -0,0 +1,10 @@
+-- Former paths of posts, such as their URLs on the blog they were imported
+-- from, permanently redirected to the posts wherever these are now
+
+CREATE TABLE redirects (
+    path       VARCHAR(2048) PRIMARY KEY,
+    post_id    INTEGER       NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ   NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX redirects_post_id_idx ON redirects (post_id);
@@ -0,0 +1,354 @@
+//! `polar build` command
+//!
+//! Every published post, the home, tag and author pages along with their
+//! feeds, the sitemaps, `robots.txt` and the stylesheets are requested from a
+//! local instance of the blog, then written under the output directory along
+//! with the uploaded media these link to. Pages are written as the
+//! `index.html` of their own directory, for file servers to answer
+//! `/posts/<slug>` with `/posts/<slug>/index.html`.
+//!
+//! File servers leave query strings out, so further pages of listings are
+//! written under `page/<number>/` and their `?page=` links rewritten to point
+//! there. They also tell the type of a file from its extension, so media are
+//! written as `/media/<hash>.<extension>` and the links to them rewritten as
+//! well. Renditions are not exported, `srcset` candidates falling back to the
+//! original images. Files already in the output directory are overwritten,
+//! stale ones being left in place.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use rocket::http::{ContentType, Status};
+use rocket::local::asynchronous::Client;
+use rocket::tokio::fs;
+use rocket_db_pools::diesel::AsyncPgConnection;
+
+use crate::api::Pagination;
+use crate::app::core::database::models::{Media, Post, PostFilter, Tag, Visibility};
+use crate::app::core::feed::FeedFormat;
+use crate::app::core::sitemap;
+use crate::app::core::storage::Storage;
+use crate::app::core::theme::Theme;
+use crate::config::Config;
+use crate::result::{ExportError, Result};
+
+pub async fn run<'a>(
+    client: &Client,
+    conn: &mut AsyncPgConnection,
+    config: &Config,
+    out: &str,
+) -> Result<'a, ()> {
+    let out = Path::new(out);
+    let mut exported = 0;
+
+    // Files of the uploaded media by hash, those linked to being exported
+    let files: HashMap<String, String> = Media::list(conn)
+        .await?
+        .into_iter()
+        .map(|media| {
+            let file = media_file(&media.hash, &media.mime_type);
+            (media.hash, file)
+        })
+        .collect();
+    let mut linked = BTreeSet::new();
+
+    // Listings of the published posts, of those of each tag and of each author
+    let tags = Tag::last_modified(conn).await?;
+    let authors = Post::last_modified_by_author(conn).await?;
+    let mut listings = vec![(String::new(), PostFilter::default())];
+    listings.extend(tags.iter().map(|(slug, _)| {
+        let filter = PostFilter {
+            tag: Some(slug.as_str()),
+            ..PostFilter::default()
+        };
+        (format!("/tags/{}", slug), filter)
+    }));
+    listings.extend(authors.iter().map(|(username, _)| {
+        let filter = PostFilter {
+            author: Some(username.as_str()),
+            ..PostFilter::default()
+        };
+        (format!("/authors/{}", username), filter)
+    }));
+
+    let per_page = Pagination::default().per_page;
+    for (listing, filter) in listings {
+        if !is_exportable(&listing) {
+            eprintln!("{} cannot be written as a file, skipped", listing);
+            continue;
+        }
+        let filter = PostFilter {
+            visibility: Visibility::Public,
+            ..filter
+        };
+        let pages = (Post::count(conn, &filter).await? + per_page - 1) / per_page;
+        let path = match listing.as_str() {
+            "" => "/",
+            path => path,
+        };
+        for page in 1..=pages.max(1) {
+            let html = get(client, &format!("{}?page={}", path, page)).await?;
+            let html = link_pages(&String::from_utf8_lossy(&html), &listing);
+            let html = link_media(&html, &files, &mut linked);
+            write(&page_file(out, &page_path(&listing, page)), html.as_bytes()).await?;
+            exported += 1;
+        }
+        for format in FeedFormat::ALL {
+            let path = format!("{}/feed.{}", listing, format.extension());
+            let feed = get(client, &path).await?;
+            let feed = link_media(&String::from_utf8_lossy(&feed), &files, &mut linked);
+            write(&file(out, &path), feed.as_bytes()).await?;
+            exported += 1;
+        }
+    }
+
+    let posts = Post::last_modified(conn).await?;
+    for (slug, _) in &posts {
+        let path = format!("/posts/{}", slug);
+        let html = get(client, &path).await?;
+        let html = link_media(&String::from_utf8_lossy(&html), &files, &mut linked);
+        write(&page_file(out, &path), html.as_bytes()).await?;
+        exported += 1;
+    }
+
+    let sitemaps = sitemap::pages(&sitemap::urls(&config.site, &posts, &tags, &authors));
+    let mut paths = vec!["/sitemap.xml".to_string(), "/robots.txt".to_string()];
+    if sitemaps > 1 {
+        paths.extend((1..=sitemaps).map(|n| format!("/sitemaps/{}.xml", n)));
+    }
+    paths.extend(["/static/polar.css", "/static/highlight.css"].map(String::from));
+    for path in &paths {
+        write(&file(out, path), &get(client, path).await?).await?;
+        exported += 1;
+    }
+    if let Some(directory) = Theme::static_dir(&config.theme) {
+        exported += copy_dir(Path::new(&directory), &out.join("static")).await?;
+    }
+
+    let storage = client
+        .rocket()
+        .state::<Box<dyn Storage>>()
+        .expect("storage is managed");
+    for hash in linked {
+        let Some(data) = storage.get(hash).await? else {
+            eprintln!("Media {} is missing from the storage, skipped", hash);
+            continue;
+        };
+        write(&file(out, &format!("/media/{}", files[hash])), &data).await?;
+        exported += 1;
+    }
+
+    println!("Exported {} files to {}", exported, out.display());
+    Ok(())
+}
+
+/// Body of a page, which must be found
+async fn get<'a>(client: &Client, path: &str) -> Result<'a, Vec<u8>> {
+    let response = client.get(path).dispatch().await;
+    let status = response.status();
+    if status != Status::Ok {
+        return Err(ExportError::UnexpectedStatus(path.to_string(), status.code).into());
+    }
+    Ok(response.into_bytes().await.unwrap_or_default())
+}
+
+async fn write<'a>(file: &Path, data: &[u8]) -> Result<'a, ()> {
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)
+            .await
+            .map_err(ExportError::from)?;
+    }
+    fs::write(file, data).await.map_err(ExportError::from)?;
+    Ok(())
+}
+
+/// Copy the files of a directory and its subdirectories, returning their number
+async fn copy_dir<'a>(from: &Path, to: &Path) -> Result<'a, usize> {
+    let mut copied = 0;
+    let mut directories = vec![PathBuf::new()];
+    while let Some(directory) = directories.pop() {
+        fs::create_dir_all(to.join(&directory))
+            .await
+            .map_err(ExportError::from)?;
+        let mut entries = fs::read_dir(from.join(&directory))
+            .await
+            .map_err(ExportError::from)?;
+        while let Some(entry) = entries.next_entry().await.map_err(ExportError::from)? {
+            let path = directory.join(entry.file_name());
+            match entry.file_type().await.map_err(ExportError::from)?.is_dir() {
+                true => directories.push(path),
+                false => {
+                    fs::copy(from.join(&path), to.join(&path))
+                        .await
+                        .map_err(ExportError::from)?;
+                    copied += 1;
+                }
+            }
+        }
+    }
+    Ok(copied)
+}
+
+/* ------------------------------------------- Paths ------------------------------------------- */
+
+/// Whether none of the segments of a path would point elsewhere as a file path,
+/// usernames possibly being dots
+fn is_exportable(path: &str) -> bool {
+    path.split('/')
+        .all(|segment| segment != "." && segment != "..")
+}
+
+/// File of the given path
+fn file(out: &Path, path: &str) -> PathBuf {
+    out.join(path.trim_start_matches('/'))
+}
+
+/// File of the page at the given path, the `index.html` of its directory
+fn page_file(out: &Path, path: &str) -> PathBuf {
+    out.join(path.trim_matches('/')).join("index.html")
+}
+
+/// Path of the given page of a listing
+fn page_path(listing: &str, page: i64) -> String {
+    match page {
+        1 => format!("{}/", listing),
+        _ => format!("{}/page/{}/", listing, page),
+    }
+}
+
+/// Point the `?page=` links of a page of a listing to the exported pages
+fn link_pages(html: &str, listing: &str) -> String {
+    const LINK: &str = "href=\"?page=";
+
+    let mut linked = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find(LINK) {
+        linked.push_str(&rest[..start]);
+        let after = &rest[start + LINK.len()..];
+        let digits = after
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(after.len());
+        match after[..digits].parse() {
+            Ok(page) if after[digits..].starts_with('"') => {
+                linked.push_str(&format!("href=\"{}", page_path(listing, page)));
+            }
+            _ => linked.push_str(&rest[start..start + LINK.len() + digits]),
+        }
+        rest = &after[digits..];
+    }
+    linked.push_str(rest);
+    linked
+}
+
+/// File of a media, named after its hash with the extension of its type if
+/// known
+fn media_file(hash: &str, mime_type: &str) -> String {
+    let extension = ContentType::parse_flexible(mime_type)
+        .and_then(|content_type| content_type.extension().map(|e| e.to_string()));
+    match extension {
+        Some(extension) => format!("{}.{}", hash, extension),
+        None => hash.to_string(),
+    }
+}
+
+/// Point the links of a page to media to their exported files, adding the
+/// hashes of these to `linked`
+fn link_media<'f>(
+    html: &str,
+    files: &'f HashMap<String, String>,
+    linked: &mut BTreeSet<&'f str>,
+) -> String {
+    const LINK: &str = "/media/";
+
+    let mut linked_html = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find(LINK) {
+        linked_html.push_str(&rest[..start + LINK.len()]);
+        let after = &rest[start + LINK.len()..];
+        let length = after
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(after.len());
+        match files.get_key_value(&after[..length]) {
+            Some((hash, file)) => {
+                linked_html.push_str(file);
+                linked.insert(hash);
+            }
+            None => linked_html.push_str(&after[..length]),
+        }
+        rest = &after[length..];
+    }
+    linked_html.push_str(rest);
+    linked_html
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use std::collections::{BTreeSet, HashMap};
+
+    use super::{file, is_exportable, link_media, link_pages, media_file, page_file};
+
+    #[test]
+    fn files() {
+        let out = Path::new("site");
+
+        assert_eq!(page_file(out, "/"), Path::new("site/index.html"));
+        assert_eq!(
+            page_file(out, "/posts/hello"),
+            Path::new("site/posts/hello/index.html")
+        );
+        assert_eq!(
+            page_file(out, "/tags/rust/page/2/"),
+            Path::new("site/tags/rust/page/2/index.html")
+        );
+        assert_eq!(
+            file(out, "/tags/rust/feed.atom"),
+            Path::new("site/tags/rust/feed.atom")
+        );
+        assert!(is_exportable("/authors/j.doe"));
+        assert!(!is_exportable("/authors/.."));
+    }
+
+    #[test]
+    fn page_links() {
+        let html =
+            r#"<a rel="prev" href="?page=1">Newer</a> <a rel="next" href="?page=3">Older</a>"#;
+
+        assert_eq!(
+            link_pages(html, ""),
+            r#"<a rel="prev" href="/">Newer</a> <a rel="next" href="/page/3/">Older</a>"#
+        );
+        assert_eq!(
+            link_pages(html, "/tags/rust"),
+            r#"<a rel="prev" href="/tags/rust/">Newer</a> <a rel="next" href="/tags/rust/page/3/">Older</a>"#
+        );
+        assert_eq!(
+            link_pages(r#"<a href="?page=x">"#, ""),
+            r#"<a href="?page=x">"#
+        );
+    }
+
+    #[test]
+    fn media_links() {
+        let files = HashMap::from([
+            ("3f2a".to_string(), media_file("3f2a", "image/png")),
+            (
+                "b7c1".to_string(),
+                media_file("b7c1", "application/x-unknown"),
+            ),
+        ]);
+        let mut linked = BTreeSet::new();
+        let html =
+            r#"<img src="/media/3f2a" srcset="/media/3f2a?w=320 320w"> <a href="/media/9d0e">"#;
+
+        assert_eq!(
+            link_media(html, &files, &mut linked),
+            r#"<img src="/media/3f2a.png" srcset="/media/3f2a.png?w=320 320w"> <a href="/media/9d0e">"#
+        );
+        assert_eq!(linked, BTreeSet::from(["3f2a"]));
+        assert_eq!(files["b7c1"], "b7c1");
+    }
+}
@@ -0,0 +1,268 @@
+//! `polar import` subcommands
+//!
+//! The plan of an import is printed before anything is created, and nothing is
+//! as long as it lists problems. Attachments are only fetched once the plan is
+//! known to be carried out, those which cannot be being left out with a warning.
+
+use std::mem;
+use std::panic;
+use std::path::Path;
+use std::time::Duration;
+
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Client;
+use rocket::data::{ByteUnit, Limits};
+use rocket::http::{ContentType, RawStr};
+use rocket::tokio::task;
+use rocket_db_pools::diesel::AsyncPgConnection;
+
+use crate::app::core::import::{markdown, wordpress, FetchedMedia, Plan, PlannedAttachment};
+use crate::app::core::metadata;
+use crate::app::core::rendition::ImageFormat;
+use crate::app::core::storage::{self, Storage};
+use crate::cli::{ImportAction, ImportMarkdown, ImportWordpress};
+use crate::config::Config;
+use crate::result::{ImportError, Result};
+
+/// Time an attachment is given to be fetched
+const FETCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub async fn run<'a>(
+    conn: &mut AsyncPgConnection,
+    config: &Config,
+    limits: &Limits,
+    action: &ImportAction,
+) -> Result<'a, ()> {
+    match action {
+        ImportAction::Markdown(args) => self::markdown(conn, config, limits, args).await,
+        ImportAction::Wordpress(args) => self::wordpress(conn, config, limits, args).await,
+    }
+}
+
+async fn markdown<'a>(
+    conn: &mut AsyncPgConnection,
+    config: &Config,
+    limits: &Limits,
+    args: &ImportMarkdown,
+) -> Result<'a, ()> {
+    let content = markdown::read(Path::new(&args.directory), args.permalink.as_deref())
+        .map_err(ImportError::from)?;
+    let mut plan = Plan::new(conn, content, &args.author, &config.search.language).await?;
+    carry_out(conn, config, limits, &mut plan, args.dry_run).await
+}
+
+async fn wordpress<'a>(
+    conn: &mut AsyncPgConnection,
+    config: &Config,
+    limits: &Limits,
+    args: &ImportWordpress,
+) -> Result<'a, ()> {
+    let mut content = wordpress::read(Path::new(&args.file))?;
+    if !args.fetch_media && !content.attachments.is_empty() {
+        println!(
+            "{} attachments left on the former blog, fetch them with --fetch-media",
+            content.attachments.len()
+        );
+        content.attachments.clear();
+    }
+    let mut plan = Plan::new(conn, content, &args.author, &config.search.language).await?;
+    carry_out(conn, config, limits, &mut plan, args.dry_run).await
+}
+
+/// Print the plan, then fetch its attachments and carry it out unless it is a
+/// dry run
+async fn carry_out<'a>(
+    conn: &mut AsyncPgConnection,
+    config: &Config,
+    limits: &Limits,
+    plan: &mut Plan,
+    dry_run: bool,
+) -> Result<'a, ()> {
+    print!("{}", plan);
+    if !plan.problems.is_empty() {
+        return Err(ImportError::Problems(plan.problems.len()).into());
+    }
+    if dry_run {
+        println!("Dry run, nothing imported");
+        return Ok(());
+    }
+
+    if !plan.attachments.is_empty() {
+        let storage = storage::from_config(&config.media.storage)?;
+        let client = Client::new();
+        for attachment in mem::take(&mut plan.attachments) {
+            match fetch(&client, config, limits, storage.as_ref(), &attachment).await {
+                Ok(media) => plan.add_media(&attachment.url, media),
+                Err(reason) => eprintln!("{} not fetched: {}", attachment.url, reason),
+            }
+        }
+        println!("Fetched {} attachments", plan.media.len());
+    }
+    plan.commit(conn).await?;
+    println!("Imported {} posts", plan.posts.len());
+    if !plan.users.is_empty() {
+        println!("Imported users may log in once given a password with `polar user passwd`");
+    }
+    Ok(())
+}
+
+/// Largest file of the given MIME type which may be uploaded, after the
+/// `file/<extension>` limits or else the `file` one
+fn file_limit(limits: &Limits, mime_type: &str) -> ByteUnit {
+    let extension = ContentType::parse_flexible(mime_type)
+        .and_then(|content_type| content_type.extension().map(|e| e.to_string()));
+    match extension {
+        Some(extension) => limits.find(["file", extension.as_str()]),
+        None => limits.get("file"),
+    }
+    .unwrap_or(Limits::FILE)
+}
+
+/// Fetch an attachment into the storage, stripped of its metadata if it is an
+/// image, or the reason why it cannot be. Attachments are bounded by the limits
+/// of uploaded files, whatever their announced length.
+async fn fetch(
+    client: &Client,
+    config: &Config,
+    limits: &Limits,
+    storage: &dyn Storage,
+    attachment: &PlannedAttachment,
+) -> std::result::Result<FetchedMedia, String> {
+    let mut response = client
+        .get(&attachment.url)
+        .timeout(FETCH_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    let mime_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_lowercase())
+        .unwrap_or_default();
+    if !config.media.types.contains(&mime_type) {
+        return Err(format!("{} is not an accepted media type", mime_type));
+    }
+    let limit = file_limit(limits, &mime_type);
+    let too_large = || format!("larger than the {} limit of {} files", limit, mime_type);
+    if response
+        .content_length()
+        .is_some_and(|length| limit < length)
+    {
+        return Err(too_large());
+    }
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        if limit < data.len() + chunk.len() {
+            return Err(too_large());
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    let mut preview = None;
+    if ImageFormat::of_original(&mime_type).is_some() {
+        let (mime, keep) = (mime_type.clone(), config.media.keep_metadata.clone());
+        let processed = task::spawn_blocking(move || metadata::process(&data, &mime, &keep))
+            .await
+            .unwrap_or_else(|e| panic::resume_unwind(e.into_panic()));
+        let (stripped, image) = processed.map_err(|_| "not a valid image".to_string())?;
+        (data, preview) = (stripped, Some(image));
+    }
+    let hash = storage::hash(&data);
+    storage
+        .put(&hash, &mime_type, &data)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    // Name of the file without its directories, kept for the record only
+    let path = attachment.url.split(['?', '#']).next().unwrap_or_default();
+    let name = path.rsplit('/').next().unwrap_or_default();
+    let name = RawStr::new(name).percent_decode_lossy();
+    let file_name = match name.trim() {
+        "" => hash.clone(),
+        name => name.chars().take(255).collect(),
+    };
+    Ok(FetchedMedia {
+        hash,
+        owner: attachment.owner.clone(),
+        file_name,
+        mime_type,
+        size: data.len() as i64,
+        preview,
+    })
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use reqwest::Client;
+    use rocket::data::{Limits, ToByteUnit};
+
+    use super::{fetch, file_limit};
+    use crate::app::core::import::PlannedAttachment;
+    use crate::app::core::storage::LocalStorage;
+    use crate::config::Config;
+
+    #[test]
+    fn file_limits() {
+        let limits = Limits::default()
+            .limit("file", 5.mebibytes())
+            .limit("file/png", 1.mebibytes());
+
+        assert_eq!(file_limit(&limits, "image/png"), 1.mebibytes());
+        assert_eq!(file_limit(&limits, "image/jpeg"), 5.mebibytes());
+        assert_eq!(
+            file_limit(&Limits::default(), "image/x-unknown"),
+            Limits::FILE
+        );
+    }
+
+    /// URL of a server answering once with a PNG of the given size, its
+    /// length announced or not
+    fn serve(size: usize, announced: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/bear.png", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.read(&mut [0; 1024]).unwrap();
+            let length = match announced {
+                true => format!("Content-Length: {}\r\n", size),
+                false => String::new(),
+            };
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\n{}\r\n",
+                length
+            );
+            stream.write_all(head.as_bytes()).unwrap();
+            let _ = stream.write_all(&vec![0; size]);
+        });
+        url
+    }
+
+    #[rocket::async_test]
+    async fn oversized_attachments() {
+        let (client, config) = (Client::new(), Config::default());
+        let limits = Limits::default().limit("file", 1.kibibytes());
+        let storage = LocalStorage::new("unused");
+        for announced in [true, false] {
+            let attachment = PlannedAttachment {
+                url: serve(64 * 1024, announced),
+                owner: "polar".to_string(),
+            };
+
+            assert_eq!(
+                fetch(&client, &config, &limits, &storage, &attachment)
+                    .await
+                    .unwrap_err(),
+                "larger than the 1KiB limit of image/png files"
+            );
+        }
+    }
+}
@@ -0,0 +1,9 @@
+//! # Commands
+//!
+//! Implementation of the administration subcommands, run outside of the
+//! webserver.
+
+pub mod build;
+pub mod import;
+pub mod theme;
+pub mod user;
@@ -0,0 +1,24 @@
+//! `polar theme` subcommands
+
+use crate::app::core::markdown;
+use crate::cli::{ThemeAction, ThemeCss};
+use crate::result::Result;
+
+pub fn run<'a>(action: &ThemeAction) -> Result<'a, ()> {
+    match action {
+        ThemeAction::Css(css) => self::css(css),
+        ThemeAction::List => self::list(),
+    }
+}
+
+fn css<'a>(css: &ThemeCss) -> Result<'a, ()> {
+    print!("{}", markdown::theme_css(&css.name)?);
+    Ok(())
+}
+
+fn list<'a>() -> Result<'a, ()> {
+    for name in markdown::themes() {
+        println!("{}", name);
+    }
+    Ok(())
+}
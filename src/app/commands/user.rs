@@ -0,0 +1,108 @@
+//! `polar user` subcommands
+
+use std::io::stdin;
+
+use rocket_db_pools::diesel::AsyncPgConnection;
+
+use crate::app::core::database::models::{NewUser, User, UserChanges};
+use crate::app::core::validation::check_user;
+use crate::cli::{UserAction, UserCreate, UserDisable, UserPasswd};
+use crate::result::{ensure_valid, Result};
+use crate::security::hash_password;
+
+pub async fn run<'a>(conn: &mut AsyncPgConnection, action: &UserAction) -> Result<'a, ()> {
+    match action {
+        UserAction::Create(create) => self::create(conn, create).await,
+        UserAction::List => self::list(conn).await,
+        UserAction::Passwd(passwd) => self::passwd(conn, passwd).await,
+        UserAction::Disable(disable) => self::disable(conn, disable).await,
+    }
+}
+
+/// Read a new password, either from standard input or by prompting twice for it
+fn read_password<'a>(from_stdin: bool) -> Result<'a, String> {
+    if from_stdin {
+        let mut line = String::new();
+        stdin().read_line(&mut line)?;
+        return Ok(line.trim_end_matches(['\r', '\n']).to_string());
+    }
+
+    loop {
+        let password = rpassword::prompt_password("Password: ")?;
+        if password.is_empty() {
+            eprintln!("The password may not be empty, try again.");
+            continue;
+        }
+        if password == rpassword::prompt_password("Confirm password: ")? {
+            return Ok(password);
+        }
+        eprintln!("Passwords do not match, try again.");
+    }
+}
+
+async fn create<'a>(conn: &mut AsyncPgConnection, args: &UserCreate) -> Result<'a, ()> {
+    let email = args.email.to_lowercase();
+    ensure_valid(check_user(Some(&args.username), Some(&email), None))?;
+    let password = read_password(args.password_stdin)?;
+    ensure_valid(check_user(None, None, Some(&password)))?;
+    let password_hash = hash_password(&password)?;
+    let user = User::create(
+        conn,
+        &NewUser {
+            username: &args.username,
+            email: &email,
+            password_hash: &password_hash,
+            display_name: args.display_name.as_deref().unwrap_or(&args.username),
+            bio: args.bio.as_deref().unwrap_or_default(),
+            role: args.role,
+        },
+    )
+    .await?;
+    println!("User {} created", user.username);
+    Ok(())
+}
+
+async fn list<'a>(conn: &mut AsyncPgConnection) -> Result<'a, ()> {
+    let users = User::list(conn).await?;
+    println!(
+        "{:<6} {:<24} {:<32} {:<24} {:<8} STATUS",
+        "ID", "USERNAME", "EMAIL", "DISPLAY NAME", "ROLE"
+    );
+    for user in users {
+        println!(
+            "{:<6} {:<24} {:<32} {:<24} {:<8} {}",
+            user.id,
+            user.username,
+            user.email,
+            user.display_name,
+            user.role,
+            if user.disabled { "disabled" } else { "active" }
+        );
+    }
+    Ok(())
+}
+
+async fn passwd<'a>(conn: &mut AsyncPgConnection, args: &UserPasswd) -> Result<'a, ()> {
+    // Fail early on unknown users, before prompting for anything
+    User::find_by_username(conn, &args.username).await?;
+    let password = read_password(args.password_stdin)?;
+    ensure_valid(check_user(None, None, Some(&password)))?;
+    let password_hash = hash_password(&password)?;
+    let changes = UserChanges {
+        password_hash: Some(&password_hash),
+        ..Default::default()
+    };
+    User::update(conn, &args.username, &changes).await?;
+    println!("Password of user {} changed", args.username);
+    Ok(())
+}
+
+async fn disable<'a>(conn: &mut AsyncPgConnection, args: &UserDisable) -> Result<'a, ()> {
+    let changes = UserChanges {
+        disabled: Some(true),
+        ..Default::default()
+    };
+    User::update(conn, &args.username, &changes).await?;
+    println!("User {} disabled", args.username);
+    Ok(())
+}
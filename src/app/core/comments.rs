@@ -0,0 +1,139 @@
+//! # Comments
+//!
+//! Readers comment published posts, replying to each other in threads. Unless
+//! moderation is disabled, comments wait in a queue for an administrator to
+//! approve them, those of editors and administrators being trusted. Comments
+//! the [spam](crate::app::core::spam) filter is confident about are set aside
+//! as spam right away.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::app::core::database::models::{Comment, CommentStatus, Post, PostStatus};
+use crate::config::CommentsConfig;
+use crate::security::Role;
+
+/// Comment along with its replies
+#[derive(Debug, Serialize)]
+pub struct Thread {
+    #[serde(flatten)]
+    pub comment: Comment,
+    pub replies: Vec<Thread>,
+}
+
+/// Whether readers may comment the given post
+pub fn accepts_comments(config: &CommentsConfig, post: &Post) -> bool {
+    config.enabled && post.comments_enabled && post.status == PostStatus::Published
+}
+
+/// Status of a new comment written by a user of the given role, if any, given
+/// its spam probability
+pub fn initial_status(
+    config: &CommentsConfig,
+    role: Option<Role>,
+    spam_score: Option<f32>,
+) -> CommentStatus {
+    match role {
+        Some(role) if role >= Role::Editor => CommentStatus::Approved,
+        _ if spam_score.is_some_and(|score| score > config.spam_threshold) => CommentStatus::Spam,
+        _ if config.moderated => CommentStatus::Pending,
+        _ => CommentStatus::Approved,
+    }
+}
+
+/// Nest comments under their parent, keeping their order. Replies to a
+/// comment missing from `comments` are dropped along with their own replies.
+pub fn thread(comments: Vec<Comment>) -> Vec<Thread> {
+    let mut children: HashMap<Option<i32>, Vec<Comment>> = HashMap::new();
+    for comment in comments {
+        children.entry(comment.parent_id).or_default().push(comment);
+    }
+    replies(&mut children, None)
+}
+
+fn replies(children: &mut HashMap<Option<i32>, Vec<Comment>>, parent: Option<i32>) -> Vec<Thread> {
+    children
+        .remove(&parent)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|comment| {
+            let replies = replies(children, Some(comment.id));
+            Thread { comment, replies }
+        })
+        .collect()
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::{initial_status, thread};
+    use crate::app::core::database::models::{Comment, CommentStatus};
+    use crate::config::CommentsConfig;
+    use crate::security::Role;
+
+    fn comment(id: i32, parent_id: Option<i32>) -> Comment {
+        Comment {
+            id,
+            post_id: 1,
+            parent_id,
+            author: None,
+            author_name: "Jane".to_string(),
+            author_email: None,
+            body: format!("Comment {}", id),
+            status: CommentStatus::Approved,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            spam_score: None,
+            trained_as: None,
+        }
+    }
+
+    #[test]
+    fn threading() {
+        let threads = thread(vec![
+            comment(1, None),
+            comment(2, Some(1)),
+            comment(3, None),
+            comment(4, Some(2)),
+            comment(5, Some(1)),
+            comment(6, Some(42)),
+        ]);
+
+        let ids: Vec<i32> = threads.iter().map(|t| t.comment.id).collect();
+        assert_eq!(ids, [1, 3]);
+        let replies: Vec<i32> = threads[0].replies.iter().map(|t| t.comment.id).collect();
+        assert_eq!(replies, [2, 5]);
+        assert_eq!(threads[0].replies[0].replies[0].comment.id, 4);
+        assert!(threads[1].replies.is_empty());
+    }
+
+    #[test]
+    fn moderation() {
+        let mut config = CommentsConfig::default();
+        assert_eq!(initial_status(&config, None, None), CommentStatus::Pending);
+        assert_eq!(
+            initial_status(&config, Some(Role::Author), Some(0.5)),
+            CommentStatus::Pending
+        );
+        assert_eq!(
+            initial_status(&config, Some(Role::Editor), Some(0.99)),
+            CommentStatus::Approved
+        );
+        assert_eq!(
+            initial_status(&config, None, Some(0.95)),
+            CommentStatus::Spam
+        );
+
+        config.moderated = false;
+        assert_eq!(initial_status(&config, None, None), CommentStatus::Approved);
+        config.spam_threshold = 1.0;
+        assert_eq!(
+            initial_status(&config, None, Some(1.0)),
+            CommentStatus::Approved
+        );
+    }
+}
@@ -1 +1,1446 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
 
+use chrono::{DateTime, Utc};
+use diesel::deserialize::FromSqlRow;
+use diesel::expression::AsExpression;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Float4, Int4, Int8, Text};
+use rocket_db_pools::diesel::scoped_futures::ScopedFutureExt;
+use rocket_db_pools::diesel::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+
+use super::schema::{
+    categories, comments, media, post_categories, post_revisions, post_tags, posts, redirects,
+    spam_corpus, spam_tokens, tags, users,
+};
+use crate::api::Pagination;
+use crate::database::text_enum_sql;
+use crate::security::Role;
+
+/* ---------------------------------------- Post Status ---------------------------------------- */
+
+/// Publication state of a [Post], stored as lowercase text in the database.
+/// Only published posts are ever shown to the public.
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, Hash, AsExpression, FromSqlRow, Serialize, Deserialize,
+)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "lowercase")]
+pub enum PostStatus {
+    Draft,
+    /// Waiting for its `publish_at` date to be published
+    Scheduled,
+    Published,
+    /// Withdrawn from publication, kept for the record
+    Archived,
+}
+
+impl PostStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PostStatus::Draft => "draft",
+            PostStatus::Scheduled => "scheduled",
+            PostStatus::Published => "published",
+            PostStatus::Archived => "archived",
+        }
+    }
+
+    /// Whether a post may go from this status to the `next` one
+    pub fn can_become(&self, next: PostStatus) -> bool {
+        use PostStatus::*;
+        matches!(
+            (self, next),
+            (Draft, Scheduled | Published)
+                | (Scheduled, Draft | Scheduled | Published)
+                | (Published, Draft | Archived)
+                | (Archived, Draft | Published)
+        ) || *self == next
+    }
+}
+
+impl Display for PostStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PostStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "draft" => Ok(PostStatus::Draft),
+            "scheduled" => Ok(PostStatus::Scheduled),
+            "published" => Ok(PostStatus::Published),
+            "archived" => Ok(PostStatus::Archived),
+            other => Err(format!("Unknown post status {}", other)),
+        }
+    }
+}
+
+text_enum_sql!(PostStatus);
+
+/* -------------------------------------------- Post ------------------------------------------- */
+
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, Serialize)]
+#[diesel(table_name = posts)]
+#[diesel(check_for_backend(Pg))]
+pub struct Post {
+    pub id: i32,
+    pub slug: String,
+    pub title: String,
+    pub body_markdown: String,
+    pub status: PostStatus,
+    pub author: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub published_at: Option<DateTime<Utc>>,
+    /// Sanitized rendering of `body_markdown`, kept in sync on every write
+    pub body_html: String,
+    /// Publication date of a scheduled post
+    pub publish_at: Option<DateTime<Utc>>,
+    /// Whether readers may comment the post, provided comments are enabled
+    pub comments_enabled: bool,
+    /// Text search configuration the post is indexed with
+    pub language: String,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = posts)]
+pub struct NewPost<'a> {
+    pub slug: &'a str,
+    pub title: &'a str,
+    pub body_markdown: &'a str,
+    pub body_html: &'a str,
+    pub status: PostStatus,
+    pub author: &'a str,
+    pub published_at: Option<DateTime<Utc>>,
+    pub publish_at: Option<DateTime<Utc>>,
+    pub comments_enabled: bool,
+    pub language: &'a str,
+    /// Original dates of imported posts, now when left out
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, AsChangeset)]
+#[diesel(table_name = posts)]
+pub struct PostChanges<'a> {
+    pub slug: Option<&'a str>,
+    pub title: Option<&'a str>,
+    pub body_markdown: Option<&'a str>,
+    pub body_html: Option<&'a str>,
+    pub status: Option<PostStatus>,
+    pub published_at: Option<Option<DateTime<Utc>>>,
+    pub publish_at: Option<Option<DateTime<Utc>>>,
+    pub comments_enabled: Option<bool>,
+    pub language: Option<&'a str>,
+}
+
+/// Posts a [PostFilter] may ever return, whatever its other criteria
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility<'a> {
+    /// Published posts only
+    #[default]
+    Public,
+    /// Published posts and any post of the given author
+    Own(&'a str),
+    /// Every post
+    All,
+}
+
+/// Optional criteria narrowing down a [Post] listing
+#[derive(Debug, Default, Clone)]
+pub struct PostFilter<'a> {
+    pub visibility: Visibility<'a>,
+    pub status: Option<PostStatus>,
+    pub author: Option<&'a str>,
+    /// Slug of a tag
+    pub tag: Option<&'a str>,
+    /// Slug of a category
+    pub category: Option<&'a str>,
+}
+
+impl Post {
+    pub async fn find(conn: &mut AsyncPgConnection, id: i32) -> QueryResult<Post> {
+        posts::table
+            .find(id)
+            .select(Post::as_select())
+            .first(conn)
+            .await
+    }
+
+    pub async fn find_all(conn: &mut AsyncPgConnection, ids: &[i32]) -> QueryResult<Vec<Post>> {
+        posts::table
+            .filter(posts::id.eq_any(ids))
+            .select(Post::as_select())
+            .load(conn)
+            .await
+    }
+
+    pub async fn find_by_slug(conn: &mut AsyncPgConnection, slug: &str) -> QueryResult<Post> {
+        posts::table
+            .filter(posts::slug.eq(slug))
+            .select(Post::as_select())
+            .first(conn)
+            .await
+    }
+
+    /// Posts matching the filter, whichever their order
+    fn filtered<'a>(filter: &PostFilter<'a>) -> posts::BoxedQuery<'a, Pg> {
+        let mut query = posts::table.into_boxed();
+        match filter.visibility {
+            Visibility::Public => query = query.filter(posts::status.eq(PostStatus::Published)),
+            Visibility::Own(author) => {
+                query = query.filter(
+                    posts::status
+                        .eq(PostStatus::Published)
+                        .or(posts::author.eq(author)),
+                )
+            }
+            Visibility::All => {}
+        }
+        if let Some(status) = filter.status {
+            query = query.filter(posts::status.eq(status));
+        }
+        if let Some(author) = filter.author {
+            query = query.filter(posts::author.eq(author));
+        }
+        if let Some(tag) = filter.tag {
+            let tagged = post_tags::table
+                .inner_join(tags::table)
+                .filter(tags::slug.eq(tag))
+                .select(post_tags::post_id);
+            query = query.filter(posts::id.eq_any(tagged));
+        }
+        if let Some(category) = filter.category {
+            let categorized = post_categories::table
+                .inner_join(categories::table)
+                .filter(categories::slug.eq(category))
+                .select(post_categories::post_id);
+            query = query.filter(posts::id.eq_any(categorized));
+        }
+        query
+    }
+
+    pub async fn list(
+        conn: &mut AsyncPgConnection,
+        filter: &PostFilter<'_>,
+        pagination: Pagination,
+    ) -> QueryResult<Vec<Post>> {
+        Post::filtered(filter)
+            .select(Post::as_select())
+            .order((posts::created_at.desc(), posts::id.desc()))
+            .limit(pagination.limit())
+            .offset(pagination.offset())
+            .load(conn)
+            .await
+    }
+
+    pub async fn count(conn: &mut AsyncPgConnection, filter: &PostFilter<'_>) -> QueryResult<i64> {
+        Post::filtered(filter).count().get_result(conn).await
+    }
+
+    /// Slugs of the published posts along with their last update
+    pub async fn last_modified(
+        conn: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<(String, DateTime<Utc>)>> {
+        posts::table
+            .filter(posts::status.eq(PostStatus::Published))
+            .select((posts::slug, posts::updated_at))
+            .order(posts::id.asc())
+            .load(conn)
+            .await
+    }
+
+    /// Authors of published posts along with the last update of these
+    pub async fn last_modified_by_author(
+        conn: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<(String, Option<DateTime<Utc>>)>> {
+        posts::table
+            .filter(posts::status.eq(PostStatus::Published))
+            .group_by(posts::author)
+            .select((posts::author, diesel::dsl::max(posts::updated_at)))
+            .order(posts::author.asc())
+            .load(conn)
+            .await
+    }
+
+    /// Slugs among the given ones already taken by a post
+    pub async fn existing_slugs(
+        conn: &mut AsyncPgConnection,
+        slugs: &[&str],
+    ) -> QueryResult<Vec<String>> {
+        posts::table
+            .filter(posts::slug.eq_any(slugs))
+            .select(posts::slug)
+            .load(conn)
+            .await
+    }
+
+    /// Whether PostgreSQL knows the text search configuration of the given name
+    pub async fn language_exists(
+        conn: &mut AsyncPgConnection,
+        language: &str,
+    ) -> QueryResult<bool> {
+        diesel::select(
+            diesel::dsl::sql::<Bool>("EXISTS (SELECT 1 FROM pg_ts_config WHERE cfgname = ")
+                .bind::<Text, _>(language)
+                .sql(")"),
+        )
+        .get_result(conn)
+        .await
+    }
+
+    pub async fn create(conn: &mut AsyncPgConnection, post: &NewPost<'_>) -> QueryResult<Post> {
+        diesel::insert_into(posts::table)
+            .values(post)
+            .returning(Post::as_returning())
+            .get_result(conn)
+            .await
+    }
+
+    pub async fn update(
+        conn: &mut AsyncPgConnection,
+        id: i32,
+        changes: &PostChanges<'_>,
+    ) -> QueryResult<Post> {
+        diesel::update(posts::table.find(id))
+            .set(changes)
+            .returning(Post::as_returning())
+            .get_result(conn)
+            .await
+    }
+
+    pub async fn delete(conn: &mut AsyncPgConnection, id: i32) -> QueryResult<usize> {
+        diesel::delete(posts::table.find(id)).execute(conn).await
+    }
+
+    /// Publish the scheduled posts whose time has come, returning how many were
+    pub async fn publish_scheduled(
+        conn: &mut AsyncPgConnection,
+        now: DateTime<Utc>,
+    ) -> QueryResult<usize> {
+        diesel::update(
+            posts::table
+                .filter(posts::status.eq(PostStatus::Scheduled))
+                .filter(posts::publish_at.le(now)),
+        )
+        .set((
+            posts::status.eq(PostStatus::Published),
+            posts::published_at.eq(posts::publish_at),
+            posts::publish_at.eq(None::<DateTime<Utc>>),
+        ))
+        .execute(conn)
+        .await
+    }
+
+    /// Render the body of every post lacking its HTML, returning how many were
+    pub async fn render_missing(
+        conn: &mut AsyncPgConnection,
+        render: impl Fn(&str) -> String,
+    ) -> QueryResult<usize> {
+        let pending: Vec<(i32, String)> = posts::table
+            .filter(posts::body_html.eq(""))
+            .filter(posts::body_markdown.ne(""))
+            .select((posts::id, posts::body_markdown))
+            .load(conn)
+            .await?;
+
+        for (id, markdown) in &pending {
+            diesel::update(posts::table.find(id))
+                .set(posts::body_html.eq(render(markdown)))
+                .execute(conn)
+                .await?;
+        }
+        Ok(pending.len())
+    }
+}
+
+/// A [Post] along with its tags and categories, as served to clients
+#[derive(Debug, Clone, Serialize)]
+pub struct PostDetails {
+    #[serde(flatten)]
+    pub post: Post,
+    pub tags: Vec<Tag>,
+    pub categories: Vec<Category>,
+}
+
+impl PostDetails {
+    /// Fetch the tags and categories of many posts at once
+    pub async fn load(
+        conn: &mut AsyncPgConnection,
+        posts: Vec<Post>,
+    ) -> QueryResult<Vec<PostDetails>> {
+        let ids: Vec<i32> = posts.iter().map(|post| post.id).collect();
+        let tags = Tag::for_posts(conn, &ids).await?;
+        let categories = Category::for_posts(conn, &ids).await?;
+
+        Ok(posts
+            .into_iter()
+            .map(|post| PostDetails {
+                tags: of_post(&tags, post.id),
+                categories: of_post(&categories, post.id),
+                post,
+            })
+            .collect())
+    }
+
+    pub async fn load_one(conn: &mut AsyncPgConnection, post: Post) -> QueryResult<PostDetails> {
+        let mut details = PostDetails::load(conn, vec![post]).await?;
+        Ok(details.remove(0))
+    }
+}
+
+fn of_post<T: Clone>(pairs: &[(i32, T)], post_id: i32) -> Vec<T> {
+    pairs
+        .iter()
+        .filter(|(id, _)| *id == post_id)
+        .map(|(_, item)| item.clone())
+        .collect()
+}
+
+/* ------------------------------------------- Search ------------------------------------------ */
+
+/// Published [Post] matching a full-text search
+#[derive(Debug, QueryableByName)]
+pub struct SearchHit {
+    #[diesel(sql_type = Int4)]
+    pub id: i32,
+    #[diesel(sql_type = Float4)]
+    pub rank: f32,
+    /// Excerpts of the body as given by `ts_headline`
+    #[diesel(sql_type = Text)]
+    pub snippet: String,
+}
+
+impl SearchHit {
+    /// Published posts matching the `tsquery`, most relevant first. The query
+    /// is parsed once per language posts are written in, so that matching
+    /// posts are found through the index.
+    pub async fn search(
+        conn: &mut AsyncPgConnection,
+        query: &str,
+        headline_options: &str,
+        pagination: Pagination,
+    ) -> QueryResult<Vec<SearchHit>> {
+        diesel::sql_query(
+            "WITH queries AS ( \
+                 SELECT language, to_tsquery(language::regconfig, $1) AS query \
+                 FROM (SELECT DISTINCT language FROM posts WHERE status = 'published') languages \
+             ) \
+             SELECT p.id, \
+                    ts_rank(p.search_vector, q.query) AS rank, \
+                    ts_headline(p.language::regconfig, p.body_markdown, q.query, $2) AS snippet \
+             FROM posts p JOIN queries q ON q.language = p.language \
+             WHERE p.status = 'published' AND p.search_vector @@ q.query \
+             ORDER BY rank DESC, p.published_at DESC, p.id DESC \
+             LIMIT $3 OFFSET $4",
+        )
+        .bind::<Text, _>(query)
+        .bind::<Text, _>(headline_options)
+        .bind::<Int8, _>(pagination.limit())
+        .bind::<Int8, _>(pagination.offset())
+        .load(conn)
+        .await
+    }
+}
+
+/* --------------------------------------- Post Revision --------------------------------------- */
+
+/// Former version of a [Post]
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, Serialize)]
+#[diesel(table_name = post_revisions)]
+#[diesel(check_for_backend(Pg))]
+pub struct PostRevision {
+    pub id: i32,
+    pub post_id: i32,
+    pub slug: String,
+    pub title: String,
+    pub body_markdown: String,
+    /// User whose update replaced this version
+    pub author: String,
+    /// Date at which this version was replaced
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = post_revisions)]
+pub struct NewPostRevision<'a> {
+    pub post_id: i32,
+    pub slug: &'a str,
+    pub title: &'a str,
+    pub body_markdown: &'a str,
+    pub author: &'a str,
+}
+
+impl PostRevision {
+    /// Save the current version of a post, about to be replaced by `author`
+    pub async fn snapshot(
+        conn: &mut AsyncPgConnection,
+        post: &Post,
+        author: &str,
+    ) -> QueryResult<PostRevision> {
+        diesel::insert_into(post_revisions::table)
+            .values(&NewPostRevision {
+                post_id: post.id,
+                slug: &post.slug,
+                title: &post.title,
+                body_markdown: &post.body_markdown,
+                author,
+            })
+            .returning(PostRevision::as_returning())
+            .get_result(conn)
+            .await
+    }
+
+    pub async fn find(
+        conn: &mut AsyncPgConnection,
+        post_id: i32,
+        id: i32,
+    ) -> QueryResult<PostRevision> {
+        post_revisions::table
+            .find(id)
+            .filter(post_revisions::post_id.eq(post_id))
+            .select(PostRevision::as_select())
+            .first(conn)
+            .await
+    }
+
+    /// Revisions of a post, latest first
+    pub async fn list(
+        conn: &mut AsyncPgConnection,
+        post_id: i32,
+    ) -> QueryResult<Vec<PostRevision>> {
+        post_revisions::table
+            .filter(post_revisions::post_id.eq(post_id))
+            .select(PostRevision::as_select())
+            .order(post_revisions::id.desc())
+            .load(conn)
+            .await
+    }
+}
+
+/* ------------------------------------------ Redirect ----------------------------------------- */
+
+/// Former path of a post, such as its URL on the blog it was imported from
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, Serialize)]
+#[diesel(table_name = redirects)]
+#[diesel(primary_key(path))]
+#[diesel(check_for_backend(Pg))]
+pub struct Redirect {
+    pub path: String,
+    pub post_id: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = redirects)]
+pub struct NewRedirect<'a> {
+    pub path: &'a str,
+    pub post_id: i32,
+}
+
+impl Redirect {
+    /// Slug of the published post the given former path leads to
+    pub async fn target(conn: &mut AsyncPgConnection, path: &str) -> QueryResult<String> {
+        redirects::table
+            .inner_join(posts::table)
+            .filter(redirects::path.eq(path))
+            .filter(posts::status.eq(PostStatus::Published))
+            .select(posts::slug)
+            .first(conn)
+            .await
+    }
+
+    /// Paths among the given ones already leading to a post
+    pub async fn existing_paths(
+        conn: &mut AsyncPgConnection,
+        paths: &[&str],
+    ) -> QueryResult<Vec<String>> {
+        redirects::table
+            .filter(redirects::path.eq_any(paths))
+            .select(redirects::path)
+            .load(conn)
+            .await
+    }
+
+    pub async fn create_all(
+        conn: &mut AsyncPgConnection,
+        redirects: &[NewRedirect<'_>],
+    ) -> QueryResult<usize> {
+        if redirects.is_empty() {
+            return Ok(0);
+        }
+        diesel::insert_into(redirects::table)
+            .values(redirects)
+            .execute(conn)
+            .await
+    }
+}
+
+/* --------------------------------------- Comment Status -------------------------------------- */
+
+/// Moderation state of a [Comment], only approved ones being shown
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, Hash, AsExpression, FromSqlRow, Serialize, Deserialize,
+)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentStatus {
+    /// Waiting in the moderation queue
+    Pending,
+    Approved,
+    Spam,
+    /// Removed by a moderator, replies included
+    Deleted,
+}
+
+impl CommentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommentStatus::Pending => "pending",
+            CommentStatus::Approved => "approved",
+            CommentStatus::Spam => "spam",
+            CommentStatus::Deleted => "deleted",
+        }
+    }
+}
+
+impl Display for CommentStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CommentStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(CommentStatus::Pending),
+            "approved" => Ok(CommentStatus::Approved),
+            "spam" => Ok(CommentStatus::Spam),
+            "deleted" => Ok(CommentStatus::Deleted),
+            other => Err(format!("Unknown comment status {}", other)),
+        }
+    }
+}
+
+text_enum_sql!(CommentStatus);
+
+/* ------------------------------------------ Comment ------------------------------------------ */
+
+/// Comment on a [Post], replying to another comment of the same post if it
+/// has a `parent_id`
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, Serialize)]
+#[diesel(table_name = comments)]
+#[diesel(check_for_backend(Pg))]
+pub struct Comment {
+    pub id: i32,
+    pub post_id: i32,
+    pub parent_id: Option<i32>,
+    /// Username of the author, unless the comment is anonymous
+    pub author: Option<String>,
+    pub author_name: String,
+    #[serde(skip_serializing)]
+    pub author_email: Option<String>,
+    pub body: String,
+    pub status: CommentStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Spam probability given by the filter when the comment was written
+    #[serde(skip_serializing)]
+    pub spam_score: Option<f32>,
+    /// Label the spam filter learned from the comment, if any
+    #[serde(skip_serializing)]
+    pub trained_as: Option<SpamLabel>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = comments)]
+pub struct NewComment<'a> {
+    pub post_id: i32,
+    pub parent_id: Option<i32>,
+    pub author: Option<&'a str>,
+    pub author_name: &'a str,
+    pub author_email: Option<&'a str>,
+    pub body: &'a str,
+    pub status: CommentStatus,
+    pub spam_score: Option<f32>,
+    /// Original dates of imported comments, now when left out
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Comment {
+    pub async fn find(conn: &mut AsyncPgConnection, id: i32) -> QueryResult<Comment> {
+        comments::table
+            .find(id)
+            .select(Comment::as_select())
+            .first(conn)
+            .await
+    }
+
+    pub async fn find_all(conn: &mut AsyncPgConnection, ids: &[i32]) -> QueryResult<Vec<Comment>> {
+        comments::table
+            .filter(comments::id.eq_any(ids))
+            .select(Comment::as_select())
+            .load(conn)
+            .await
+    }
+
+    /// Comments of a post in the given status, oldest first
+    pub async fn for_post(
+        conn: &mut AsyncPgConnection,
+        post_id: i32,
+        status: CommentStatus,
+    ) -> QueryResult<Vec<Comment>> {
+        comments::table
+            .filter(comments::post_id.eq(post_id))
+            .filter(comments::status.eq(status))
+            .select(Comment::as_select())
+            .order((comments::created_at.asc(), comments::id.asc()))
+            .load(conn)
+            .await
+    }
+
+    /// Comments of all posts in the given status, oldest first as a queue
+    pub async fn list(
+        conn: &mut AsyncPgConnection,
+        status: CommentStatus,
+        pagination: Pagination,
+    ) -> QueryResult<Vec<Comment>> {
+        comments::table
+            .filter(comments::status.eq(status))
+            .select(Comment::as_select())
+            .order((comments::created_at.asc(), comments::id.asc()))
+            .limit(pagination.limit())
+            .offset(pagination.offset())
+            .load(conn)
+            .await
+    }
+
+    pub async fn create(
+        conn: &mut AsyncPgConnection,
+        comment: &NewComment<'_>,
+    ) -> QueryResult<Comment> {
+        diesel::insert_into(comments::table)
+            .values(comment)
+            .returning(Comment::as_returning())
+            .get_result(conn)
+            .await
+    }
+
+    /// Move the given comments to a new status, returning the updated ones
+    pub async fn set_status(
+        conn: &mut AsyncPgConnection,
+        ids: &[i32],
+        status: CommentStatus,
+    ) -> QueryResult<Vec<Comment>> {
+        diesel::update(comments::table.filter(comments::id.eq_any(ids)))
+            .set(comments::status.eq(status))
+            .returning(Comment::as_returning())
+            .get_results(conn)
+            .await
+    }
+
+    pub async fn set_trained_as(
+        conn: &mut AsyncPgConnection,
+        id: i32,
+        label: Option<SpamLabel>,
+    ) -> QueryResult<usize> {
+        diesel::update(comments::table.find(id))
+            .set(comments::trained_as.eq(label))
+            .execute(conn)
+            .await
+    }
+}
+
+/* ---------------------------------------- Spam Filter ---------------------------------------- */
+
+/// Class of a comment the spam filter learns from
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, Hash, AsExpression, FromSqlRow, Serialize, Deserialize,
+)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "lowercase")]
+pub enum SpamLabel {
+    Spam,
+    Ham,
+}
+
+impl SpamLabel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SpamLabel::Spam => "spam",
+            SpamLabel::Ham => "ham",
+        }
+    }
+}
+
+impl Display for SpamLabel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SpamLabel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "spam" => Ok(SpamLabel::Spam),
+            "ham" => Ok(SpamLabel::Ham),
+            other => Err(format!("Unknown spam label {}", other)),
+        }
+    }
+}
+
+text_enum_sql!(SpamLabel);
+
+/// Number of spam and ham comments a token was found in
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = spam_tokens)]
+#[diesel(check_for_backend(Pg))]
+pub struct SpamToken {
+    pub token: String,
+    pub spam: i32,
+    pub ham: i32,
+}
+
+/// Number of spam and ham comments the filter learned from
+#[derive(Debug, Clone, Copy, Default, Queryable, Selectable)]
+#[diesel(table_name = spam_corpus)]
+#[diesel(check_for_backend(Pg))]
+pub struct SpamCorpus {
+    pub spam: i32,
+    pub ham: i32,
+}
+
+impl SpamToken {
+    pub async fn find_all(
+        conn: &mut AsyncPgConnection,
+        tokens: &[String],
+    ) -> QueryResult<Vec<SpamToken>> {
+        spam_tokens::table
+            .filter(spam_tokens::token.eq_any(tokens))
+            .select(SpamToken::as_select())
+            .load(conn)
+            .await
+    }
+
+    /// Count the tokens of a comment of the given label
+    pub async fn learn(
+        conn: &mut AsyncPgConnection,
+        tokens: &[String],
+        label: SpamLabel,
+    ) -> QueryResult<usize> {
+        use diesel::upsert::excluded;
+
+        if tokens.is_empty() {
+            return Ok(0);
+        }
+        let (spam, ham) = match label {
+            SpamLabel::Spam => (1, 0),
+            SpamLabel::Ham => (0, 1),
+        };
+        let rows: Vec<_> = tokens
+            .iter()
+            .map(|token| {
+                (
+                    spam_tokens::token.eq(token),
+                    spam_tokens::spam.eq(spam),
+                    spam_tokens::ham.eq(ham),
+                )
+            })
+            .collect();
+        diesel::insert_into(spam_tokens::table)
+            .values(rows)
+            .on_conflict(spam_tokens::token)
+            .do_update()
+            .set((
+                spam_tokens::spam.eq(spam_tokens::spam + excluded(spam_tokens::spam)),
+                spam_tokens::ham.eq(spam_tokens::ham + excluded(spam_tokens::ham)),
+            ))
+            .execute(conn)
+            .await
+    }
+
+    /// Uncount the tokens of a comment formerly learned with the given label
+    pub async fn forget(
+        conn: &mut AsyncPgConnection,
+        tokens: &[String],
+        label: SpamLabel,
+    ) -> QueryResult<usize> {
+        let learned = spam_tokens::table.filter(spam_tokens::token.eq_any(tokens));
+        match label {
+            SpamLabel::Spam => {
+                diesel::update(learned.filter(spam_tokens::spam.gt(0)))
+                    .set(spam_tokens::spam.eq(spam_tokens::spam - 1))
+                    .execute(conn)
+                    .await
+            }
+            SpamLabel::Ham => {
+                diesel::update(learned.filter(spam_tokens::ham.gt(0)))
+                    .set(spam_tokens::ham.eq(spam_tokens::ham - 1))
+                    .execute(conn)
+                    .await
+            }
+        }
+    }
+}
+
+impl SpamCorpus {
+    pub async fn get(conn: &mut AsyncPgConnection) -> QueryResult<SpamCorpus> {
+        spam_corpus::table
+            .select(SpamCorpus::as_select())
+            .first(conn)
+            .await
+    }
+
+    /// Count a comment of the given label
+    pub async fn learn(conn: &mut AsyncPgConnection, label: SpamLabel) -> QueryResult<usize> {
+        let update = diesel::update(spam_corpus::table);
+        match label {
+            SpamLabel::Spam => {
+                update
+                    .set(spam_corpus::spam.eq(spam_corpus::spam + 1))
+                    .execute(conn)
+                    .await
+            }
+            SpamLabel::Ham => {
+                update
+                    .set(spam_corpus::ham.eq(spam_corpus::ham + 1))
+                    .execute(conn)
+                    .await
+            }
+        }
+    }
+
+    /// Uncount a comment formerly learned with the given label
+    pub async fn forget(conn: &mut AsyncPgConnection, label: SpamLabel) -> QueryResult<usize> {
+        match label {
+            SpamLabel::Spam => {
+                diesel::update(spam_corpus::table.filter(spam_corpus::spam.gt(0)))
+                    .set(spam_corpus::spam.eq(spam_corpus::spam - 1))
+                    .execute(conn)
+                    .await
+            }
+            SpamLabel::Ham => {
+                diesel::update(spam_corpus::table.filter(spam_corpus::ham.gt(0)))
+                    .set(spam_corpus::ham.eq(spam_corpus::ham - 1))
+                    .execute(conn)
+                    .await
+            }
+        }
+    }
+}
+
+/* ------------------------------------------- Media ------------------------------------------- */
+
+/// Uploaded file, stored under its [hash](Media::hash)
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, Serialize)]
+#[diesel(table_name = media)]
+#[diesel(check_for_backend(Pg))]
+pub struct Media {
+    pub id: i32,
+    /// Hex SHA-256 hash of the content, also its storage key
+    pub hash: String,
+    /// Username of the first uploader
+    pub owner: String,
+    pub file_name: String,
+    pub mime_type: String,
+    /// Size in bytes
+    pub size: i64,
+    pub created_at: DateTime<Utc>,
+    /// Width in pixels of an image, as displayed
+    pub width: Option<i32>,
+    /// Height in pixels of an image, as displayed
+    pub height: Option<i32>,
+    /// [BlurHash](https://blurha.sh) of an image
+    pub blurhash: Option<String>,
+    /// Average color of an image, as `#rrggbb`
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = media)]
+pub struct NewMedia<'a> {
+    pub hash: &'a str,
+    pub owner: &'a str,
+    pub file_name: &'a str,
+    pub mime_type: &'a str,
+    pub size: i64,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub blurhash: Option<&'a str>,
+    pub color: Option<&'a str>,
+}
+
+impl Media {
+    pub async fn find_by_hash(conn: &mut AsyncPgConnection, hash: &str) -> QueryResult<Media> {
+        media::table
+            .filter(media::hash.eq(hash))
+            .select(Media::as_select())
+            .first(conn)
+            .await
+    }
+
+    pub async fn list(conn: &mut AsyncPgConnection) -> QueryResult<Vec<Media>> {
+        media::table
+            .select(Media::as_select())
+            .order(media::id.asc())
+            .load(conn)
+            .await
+    }
+
+    pub async fn create(conn: &mut AsyncPgConnection, media: &NewMedia<'_>) -> QueryResult<Media> {
+        diesel::insert_into(media::table)
+            .values(media)
+            .returning(Media::as_returning())
+            .get_result(conn)
+            .await
+    }
+}
+
+/* -------------------------------------------- Tag -------------------------------------------- */
+
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, Serialize)]
+#[diesel(table_name = tags)]
+#[diesel(check_for_backend(Pg))]
+pub struct Tag {
+    pub id: i32,
+    pub slug: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = tags)]
+pub struct NewTag<'a> {
+    pub slug: &'a str,
+    pub name: &'a str,
+}
+
+#[derive(Debug, Default, AsChangeset)]
+#[diesel(table_name = tags)]
+pub struct TagChanges<'a> {
+    pub slug: Option<&'a str>,
+    pub name: Option<&'a str>,
+}
+
+impl Tag {
+    pub async fn find_by_slug(conn: &mut AsyncPgConnection, slug: &str) -> QueryResult<Tag> {
+        tags::table
+            .filter(tags::slug.eq(slug))
+            .select(Tag::as_select())
+            .first(conn)
+            .await
+    }
+
+    pub async fn list(conn: &mut AsyncPgConnection) -> QueryResult<Vec<Tag>> {
+        tags::table
+            .select(Tag::as_select())
+            .order(tags::name.asc())
+            .load(conn)
+            .await
+    }
+
+    /// Slugs of the tags of published posts along with the last update of these
+    pub async fn last_modified(
+        conn: &mut AsyncPgConnection,
+    ) -> QueryResult<Vec<(String, Option<DateTime<Utc>>)>> {
+        tags::table
+            .inner_join(post_tags::table.inner_join(posts::table))
+            .filter(posts::status.eq(PostStatus::Published))
+            .group_by(tags::slug)
+            .select((tags::slug, diesel::dsl::max(posts::updated_at)))
+            .order(tags::slug.asc())
+            .load(conn)
+            .await
+    }
+
+    pub async fn create(conn: &mut AsyncPgConnection, tag: &NewTag<'_>) -> QueryResult<Tag> {
+        diesel::insert_into(tags::table)
+            .values(tag)
+            .returning(Tag::as_returning())
+            .get_result(conn)
+            .await
+    }
+
+    /// Create the tags which do not exist yet, returning all of them
+    pub async fn ensure(
+        conn: &mut AsyncPgConnection,
+        tags: &[NewTag<'_>],
+    ) -> QueryResult<Vec<Tag>> {
+        if tags.is_empty() {
+            return Ok(Vec::new());
+        }
+        diesel::insert_into(tags::table)
+            .values(tags)
+            .on_conflict(tags::slug)
+            .do_nothing()
+            .execute(conn)
+            .await?;
+
+        let slugs: Vec<&str> = tags.iter().map(|tag| tag.slug).collect();
+        tags::table
+            .filter(tags::slug.eq_any(slugs))
+            .select(Tag::as_select())
+            .order(tags::name.asc())
+            .load(conn)
+            .await
+    }
+
+    pub async fn update(
+        conn: &mut AsyncPgConnection,
+        slug: &str,
+        changes: &TagChanges<'_>,
+    ) -> QueryResult<Tag> {
+        diesel::update(tags::table.filter(tags::slug.eq(slug)))
+            .set(changes)
+            .returning(Tag::as_returning())
+            .get_result(conn)
+            .await
+    }
+
+    pub async fn delete(conn: &mut AsyncPgConnection, slug: &str) -> QueryResult<usize> {
+        diesel::delete(tags::table.filter(tags::slug.eq(slug)))
+            .execute(conn)
+            .await
+    }
+
+    /// Re-point every post of the `source` tag to the `target` one and drop the
+    /// source tag, in a single transaction
+    pub async fn merge(
+        conn: &mut AsyncPgConnection,
+        source: &str,
+        target: &str,
+    ) -> QueryResult<Tag> {
+        conn.transaction(|conn| {
+            async move {
+                let from = Tag::find_by_slug(conn, source).await?;
+                let into = Tag::find_by_slug(conn, target).await?;
+
+                let tagged = post_tags::table
+                    .filter(post_tags::tag_id.eq(from.id))
+                    .select((post_tags::post_id, into.id.into_sql::<Int4>()));
+                diesel::insert_into(post_tags::table)
+                    .values(tagged)
+                    .into_columns((post_tags::post_id, post_tags::tag_id))
+                    .on_conflict_do_nothing()
+                    .execute(conn)
+                    .await?;
+                diesel::delete(tags::table.find(from.id))
+                    .execute(conn)
+                    .await?;
+                Ok(into)
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    /// Tags of each of the given posts, as `(post id, tag)` pairs
+    pub async fn for_posts(
+        conn: &mut AsyncPgConnection,
+        post_ids: &[i32],
+    ) -> QueryResult<Vec<(i32, Tag)>> {
+        post_tags::table
+            .inner_join(tags::table)
+            .filter(post_tags::post_id.eq_any(post_ids))
+            .select((post_tags::post_id, Tag::as_select()))
+            .order(tags::name.asc())
+            .load(conn)
+            .await
+    }
+
+    /// Replace the tags of a post
+    pub async fn set_for_post(
+        conn: &mut AsyncPgConnection,
+        post_id: i32,
+        tags: &[Tag],
+    ) -> QueryResult<()> {
+        diesel::delete(post_tags::table.filter(post_tags::post_id.eq(post_id)))
+            .execute(conn)
+            .await?;
+        if tags.is_empty() {
+            return Ok(());
+        }
+
+        let rows: Vec<_> = tags
+            .iter()
+            .map(|tag| (post_tags::post_id.eq(post_id), post_tags::tag_id.eq(tag.id)))
+            .collect();
+        diesel::insert_into(post_tags::table)
+            .values(&rows)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+}
+
+/* ----------------------------------------- Category ------------------------------------------ */
+
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, Serialize)]
+#[diesel(table_name = categories)]
+#[diesel(check_for_backend(Pg))]
+pub struct Category {
+    pub id: i32,
+    pub slug: String,
+    pub name: String,
+    pub description: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = categories)]
+pub struct NewCategory<'a> {
+    pub slug: &'a str,
+    pub name: &'a str,
+    pub description: &'a str,
+}
+
+#[derive(Debug, Default, AsChangeset)]
+#[diesel(table_name = categories)]
+pub struct CategoryChanges<'a> {
+    pub slug: Option<&'a str>,
+    pub name: Option<&'a str>,
+    pub description: Option<&'a str>,
+}
+
+impl Category {
+    pub async fn find_by_slug(conn: &mut AsyncPgConnection, slug: &str) -> QueryResult<Category> {
+        categories::table
+            .filter(categories::slug.eq(slug))
+            .select(Category::as_select())
+            .first(conn)
+            .await
+    }
+
+    pub async fn find_all_by_slug(
+        conn: &mut AsyncPgConnection,
+        slugs: &[String],
+    ) -> QueryResult<Vec<Category>> {
+        categories::table
+            .filter(categories::slug.eq_any(slugs))
+            .select(Category::as_select())
+            .order(categories::name.asc())
+            .load(conn)
+            .await
+    }
+
+    pub async fn list(conn: &mut AsyncPgConnection) -> QueryResult<Vec<Category>> {
+        categories::table
+            .select(Category::as_select())
+            .order(categories::name.asc())
+            .load(conn)
+            .await
+    }
+
+    pub async fn create(
+        conn: &mut AsyncPgConnection,
+        category: &NewCategory<'_>,
+    ) -> QueryResult<Category> {
+        diesel::insert_into(categories::table)
+            .values(category)
+            .returning(Category::as_returning())
+            .get_result(conn)
+            .await
+    }
+
+    /// Create the categories which do not exist yet, returning all of them
+    pub async fn ensure(
+        conn: &mut AsyncPgConnection,
+        categories: &[NewCategory<'_>],
+    ) -> QueryResult<Vec<Category>> {
+        if categories.is_empty() {
+            return Ok(Vec::new());
+        }
+        diesel::insert_into(categories::table)
+            .values(categories)
+            .on_conflict(categories::slug)
+            .do_nothing()
+            .execute(conn)
+            .await?;
+
+        let slugs: Vec<&str> = categories.iter().map(|category| category.slug).collect();
+        categories::table
+            .filter(categories::slug.eq_any(slugs))
+            .select(Category::as_select())
+            .order(categories::name.asc())
+            .load(conn)
+            .await
+    }
+
+    pub async fn update(
+        conn: &mut AsyncPgConnection,
+        slug: &str,
+        changes: &CategoryChanges<'_>,
+    ) -> QueryResult<Category> {
+        diesel::update(categories::table.filter(categories::slug.eq(slug)))
+            .set(changes)
+            .returning(Category::as_returning())
+            .get_result(conn)
+            .await
+    }
+
+    pub async fn delete(conn: &mut AsyncPgConnection, slug: &str) -> QueryResult<usize> {
+        diesel::delete(categories::table.filter(categories::slug.eq(slug)))
+            .execute(conn)
+            .await
+    }
+
+    /// Categories of each of the given posts, as `(post id, category)` pairs
+    pub async fn for_posts(
+        conn: &mut AsyncPgConnection,
+        post_ids: &[i32],
+    ) -> QueryResult<Vec<(i32, Category)>> {
+        post_categories::table
+            .inner_join(categories::table)
+            .filter(post_categories::post_id.eq_any(post_ids))
+            .select((post_categories::post_id, Category::as_select()))
+            .order(categories::name.asc())
+            .load(conn)
+            .await
+    }
+
+    /// Replace the categories of a post
+    pub async fn set_for_post(
+        conn: &mut AsyncPgConnection,
+        post_id: i32,
+        categories: &[Category],
+    ) -> QueryResult<()> {
+        diesel::delete(post_categories::table.filter(post_categories::post_id.eq(post_id)))
+            .execute(conn)
+            .await?;
+        if categories.is_empty() {
+            return Ok(());
+        }
+
+        let rows: Vec<_> = categories
+            .iter()
+            .map(|category| {
+                (
+                    post_categories::post_id.eq(post_id),
+                    post_categories::category_id.eq(category.id),
+                )
+            })
+            .collect();
+        diesel::insert_into(post_categories::table)
+            .values(&rows)
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+}
+
+/* -------------------------------------------- User ------------------------------------------- */
+
+#[derive(Debug, Clone, Queryable, Selectable, Identifiable, Serialize)]
+#[diesel(table_name = users)]
+#[diesel(check_for_backend(Pg))]
+pub struct User {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+    #[serde(skip_serializing)]
+    pub password_hash: String,
+    pub display_name: String,
+    pub bio: String,
+    pub disabled: bool,
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = users)]
+pub struct NewUser<'a> {
+    pub username: &'a str,
+    pub email: &'a str,
+    pub password_hash: &'a str,
+    pub display_name: &'a str,
+    pub bio: &'a str,
+    pub role: Role,
+}
+
+#[derive(Debug, Default, AsChangeset)]
+#[diesel(table_name = users)]
+pub struct UserChanges<'a> {
+    pub email: Option<&'a str>,
+    pub password_hash: Option<&'a str>,
+    pub display_name: Option<&'a str>,
+    pub bio: Option<&'a str>,
+    pub disabled: Option<bool>,
+    pub role: Option<Role>,
+}
+
+impl User {
+    pub async fn find_by_username(
+        conn: &mut AsyncPgConnection,
+        username: &str,
+    ) -> QueryResult<User> {
+        users::table
+            .filter(users::username.eq(username))
+            .select(User::as_select())
+            .first(conn)
+            .await
+    }
+
+    pub async fn find_all_by_username(
+        conn: &mut AsyncPgConnection,
+        usernames: &[&str],
+    ) -> QueryResult<Vec<User>> {
+        users::table
+            .filter(users::username.eq_any(usernames))
+            .select(User::as_select())
+            .load(conn)
+            .await
+    }
+
+    /// Email addresses among the given ones already taken by a user
+    pub async fn existing_emails(
+        conn: &mut AsyncPgConnection,
+        emails: &[&str],
+    ) -> QueryResult<Vec<String>> {
+        users::table
+            .filter(users::email.eq_any(emails))
+            .select(users::email)
+            .load(conn)
+            .await
+    }
+
+    pub async fn list(conn: &mut AsyncPgConnection) -> QueryResult<Vec<User>> {
+        users::table
+            .select(User::as_select())
+            .order(users::username.asc())
+            .load(conn)
+            .await
+    }
+
+    pub async fn create(conn: &mut AsyncPgConnection, user: &NewUser<'_>) -> QueryResult<User> {
+        diesel::insert_into(users::table)
+            .values(user)
+            .returning(User::as_returning())
+            .get_result(conn)
+            .await
+    }
+
+    pub async fn update(
+        conn: &mut AsyncPgConnection,
+        username: &str,
+        changes: &UserChanges<'_>,
+    ) -> QueryResult<User> {
+        diesel::update(users::table.filter(users::username.eq(username)))
+            .set(changes)
+            .returning(User::as_returning())
+            .get_result(conn)
+            .await
+    }
+}
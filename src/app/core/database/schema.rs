@@ -1 +1,190 @@
+// @generated automatically by Diesel CLI.
 
+diesel::table! {
+    categories (id) {
+        id -> Int4,
+        #[max_length = 255]
+        slug -> Varchar,
+        #[max_length = 255]
+        name -> Varchar,
+        description -> Text,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    comments (id) {
+        id -> Int4,
+        post_id -> Int4,
+        parent_id -> Nullable<Int4>,
+        #[max_length = 64]
+        author -> Nullable<Varchar>,
+        #[max_length = 255]
+        author_name -> Varchar,
+        #[max_length = 255]
+        author_email -> Nullable<Varchar>,
+        body -> Text,
+        #[max_length = 16]
+        status -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        spam_score -> Nullable<Float4>,
+        #[max_length = 8]
+        trained_as -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    media (id) {
+        id -> Int4,
+        #[max_length = 64]
+        hash -> Varchar,
+        #[max_length = 64]
+        owner -> Varchar,
+        #[max_length = 255]
+        file_name -> Varchar,
+        #[max_length = 255]
+        mime_type -> Varchar,
+        size -> Int8,
+        created_at -> Timestamptz,
+        width -> Nullable<Int4>,
+        height -> Nullable<Int4>,
+        #[max_length = 64]
+        blurhash -> Nullable<Varchar>,
+        #[max_length = 7]
+        color -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    post_categories (post_id, category_id) {
+        post_id -> Int4,
+        category_id -> Int4,
+    }
+}
+
+diesel::table! {
+    post_revisions (id) {
+        id -> Int4,
+        post_id -> Int4,
+        #[max_length = 255]
+        slug -> Varchar,
+        #[max_length = 255]
+        title -> Varchar,
+        body_markdown -> Text,
+        #[max_length = 64]
+        author -> Varchar,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    post_tags (post_id, tag_id) {
+        post_id -> Int4,
+        tag_id -> Int4,
+    }
+}
+
+diesel::table! {
+    posts (id) {
+        id -> Int4,
+        #[max_length = 255]
+        slug -> Varchar,
+        #[max_length = 255]
+        title -> Varchar,
+        body_markdown -> Text,
+        #[max_length = 16]
+        status -> Varchar,
+        #[max_length = 64]
+        author -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        published_at -> Nullable<Timestamptz>,
+        body_html -> Text,
+        publish_at -> Nullable<Timestamptz>,
+        comments_enabled -> Bool,
+        #[max_length = 64]
+        language -> Varchar,
+    }
+}
+
+diesel::table! {
+    redirects (path) {
+        #[max_length = 2048]
+        path -> Varchar,
+        post_id -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    spam_corpus (id) {
+        id -> Bool,
+        spam -> Int4,
+        ham -> Int4,
+    }
+}
+
+diesel::table! {
+    spam_tokens (token) {
+        #[max_length = 64]
+        token -> Varchar,
+        spam -> Int4,
+        ham -> Int4,
+    }
+}
+
+diesel::table! {
+    tags (id) {
+        id -> Int4,
+        #[max_length = 255]
+        slug -> Varchar,
+        #[max_length = 255]
+        name -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    users (id) {
+        id -> Int4,
+        #[max_length = 64]
+        username -> Varchar,
+        #[max_length = 255]
+        email -> Varchar,
+        password_hash -> Text,
+        #[max_length = 255]
+        display_name -> Varchar,
+        bio -> Text,
+        disabled -> Bool,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        #[max_length = 16]
+        role -> Varchar,
+    }
+}
+
+diesel::joinable!(comments -> posts (post_id));
+diesel::joinable!(post_categories -> categories (category_id));
+diesel::joinable!(post_categories -> posts (post_id));
+diesel::joinable!(post_revisions -> posts (post_id));
+diesel::joinable!(post_tags -> posts (post_id));
+diesel::joinable!(post_tags -> tags (tag_id));
+diesel::joinable!(redirects -> posts (post_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    categories,
+    comments,
+    media,
+    post_categories,
+    post_revisions,
+    post_tags,
+    posts,
+    redirects,
+    spam_corpus,
+    spam_tokens,
+    tags,
+    users,
+);
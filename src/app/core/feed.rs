@@ -0,0 +1,479 @@
+//! # Feed
+//!
+//! Syndication of the latest published posts, for feed readers, as Atom, RSS
+//! 2.0 or JSON Feed documents. Entries carry either the rendered body of their
+//! post or a plain text summary, as configured.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+
+use crate::app::core::database::models::{PostDetails, User};
+use crate::app::core::markdown;
+use crate::config::{FeedContent, SiteConfig};
+use crate::result::SerdeError;
+
+/// Length of the summaries of the posts, in characters
+pub const SUMMARY_LENGTH: usize = 280;
+
+const XML_DECLARATION: &str = r#"<?xml version="1.0" encoding="utf-8"?>"#;
+const ATOM_NAMESPACE: &str = "http://www.w3.org/2005/Atom";
+const JSON_FEED_VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+/* ------------------------------------------- Format ------------------------------------------ */
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+    Json,
+}
+
+impl FeedFormat {
+    pub const ALL: [FeedFormat; 3] = [FeedFormat::Atom, FeedFormat::Rss, FeedFormat::Json];
+
+    /// Extension of the feed file name, `feed.<extension>`
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FeedFormat::Atom => "atom",
+            FeedFormat::Rss => "rss",
+            FeedFormat::Json => "json",
+        }
+    }
+}
+
+impl Display for FeedFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str(self.extension())
+    }
+}
+
+impl FromStr for FeedFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "atom" => Ok(FeedFormat::Atom),
+            "rss" => Ok(FeedFormat::Rss),
+            "json" => Ok(FeedFormat::Json),
+            other => Err(format!("Unknown feed format {}", other)),
+        }
+    }
+}
+
+/* -------------------------------------------- Feed ------------------------------------------- */
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Content {
+    Html(String),
+    Summary(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub url: String,
+    pub title: String,
+    pub author_name: String,
+    pub author_url: String,
+    pub published: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+    /// Names of the tags of the post
+    pub tags: Vec<String>,
+    pub content: Content,
+}
+
+impl Entry {
+    pub fn new(
+        site: &SiteConfig,
+        content: FeedContent,
+        details: &PostDetails,
+        author: &User,
+    ) -> Self {
+        let post = &details.post;
+        Entry {
+            url: site.url_of(&format!("/posts/{}", post.slug)),
+            title: post.title.clone(),
+            author_name: author.display_name.clone(),
+            author_url: site.url_of(&format!("/authors/{}", author.username)),
+            published: post.published_at.unwrap_or(post.created_at),
+            updated: post.updated_at,
+            tags: details.tags.iter().map(|tag| tag.name.clone()).collect(),
+            content: match content {
+                FeedContent::Full => Content::Html(post.body_html.clone()),
+                FeedContent::Summary => {
+                    Content::Summary(markdown::summary(&post.body_markdown, SUMMARY_LENGTH))
+                }
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Feed {
+    pub title: String,
+    pub description: String,
+    /// Page listing the posts of the feed
+    pub home_url: String,
+    /// URL the feed itself is served at
+    pub feed_url: String,
+    pub entries: Vec<Entry>,
+}
+
+impl Feed {
+    /// Date of the latest change to an entry of the feed
+    pub fn updated(&self) -> Option<DateTime<Utc>> {
+        self.entries.iter().map(|entry| entry.updated).max()
+    }
+
+    pub fn render(&self, format: FeedFormat) -> Result<String, SerdeError> {
+        match format {
+            FeedFormat::Atom => self.atom(),
+            FeedFormat::Rss => self.rss(),
+            FeedFormat::Json => self.json(),
+        }
+    }
+
+    fn atom(&self) -> Result<String, SerdeError> {
+        let document = AtomFeed {
+            xmlns: ATOM_NAMESPACE,
+            id: &self.feed_url,
+            title: &self.title,
+            subtitle: Some(self.description.as_str()).filter(|s| !s.is_empty()),
+            updated: rfc3339(self.updated().unwrap_or(DateTime::UNIX_EPOCH)),
+            link: vec![
+                AtomLink {
+                    rel: Some("self"),
+                    href: &self.feed_url,
+                },
+                AtomLink {
+                    rel: None,
+                    href: &self.home_url,
+                },
+            ],
+            entry: self
+                .entries
+                .iter()
+                .map(|entry| AtomEntry {
+                    id: &entry.url,
+                    title: &entry.title,
+                    link: AtomLink {
+                        rel: None,
+                        href: &entry.url,
+                    },
+                    published: rfc3339(entry.published),
+                    updated: rfc3339(entry.updated),
+                    author: AtomPerson {
+                        name: &entry.author_name,
+                        uri: &entry.author_url,
+                    },
+                    category: entry
+                        .tags
+                        .iter()
+                        .map(|term| AtomCategory { term })
+                        .collect(),
+                    content: match &entry.content {
+                        Content::Html(html) => Some(AtomText {
+                            kind: "html",
+                            value: html,
+                        }),
+                        Content::Summary(_) => None,
+                    },
+                    summary: match &entry.content {
+                        Content::Summary(text) => Some(AtomText {
+                            kind: "text",
+                            value: text,
+                        }),
+                        Content::Html(_) => None,
+                    },
+                })
+                .collect(),
+        };
+        Ok(format!(
+            "{}{}",
+            XML_DECLARATION,
+            quick_xml::se::to_string(&document)?
+        ))
+    }
+
+    fn rss(&self) -> Result<String, SerdeError> {
+        let document = RssDocument {
+            version: "2.0",
+            xmlns_atom: ATOM_NAMESPACE,
+            channel: RssChannel {
+                title: &self.title,
+                link: &self.home_url,
+                description: &self.description,
+                last_build_date: self.updated().map(|date| date.to_rfc2822()),
+                atom_link: AtomLink {
+                    rel: Some("self"),
+                    href: &self.feed_url,
+                },
+                item: self
+                    .entries
+                    .iter()
+                    .map(|entry| RssItem {
+                        title: &entry.title,
+                        link: &entry.url,
+                        guid: &entry.url,
+                        pub_date: entry.published.to_rfc2822(),
+                        category: entry.tags.iter().map(String::as_str).collect(),
+                        description: match &entry.content {
+                            Content::Html(text) | Content::Summary(text) => text,
+                        },
+                    })
+                    .collect(),
+            },
+        };
+        Ok(format!(
+            "{}{}",
+            XML_DECLARATION,
+            quick_xml::se::to_string(&document)?
+        ))
+    }
+
+    fn json(&self) -> Result<String, SerdeError> {
+        let document = JsonFeed {
+            version: JSON_FEED_VERSION,
+            title: &self.title,
+            description: Some(self.description.as_str()).filter(|s| !s.is_empty()),
+            home_page_url: &self.home_url,
+            feed_url: &self.feed_url,
+            items: self
+                .entries
+                .iter()
+                .map(|entry| JsonItem {
+                    id: &entry.url,
+                    url: &entry.url,
+                    title: &entry.title,
+                    content_html: match &entry.content {
+                        Content::Html(html) => Some(html),
+                        Content::Summary(_) => None,
+                    },
+                    summary: match &entry.content {
+                        Content::Summary(text) => Some(text),
+                        Content::Html(_) => None,
+                    },
+                    date_published: rfc3339(entry.published),
+                    date_modified: rfc3339(entry.updated),
+                    authors: vec![JsonAuthor {
+                        name: &entry.author_name,
+                        url: &entry.author_url,
+                    }],
+                    tags: entry.tags.iter().map(String::as_str).collect(),
+                })
+                .collect(),
+        };
+        Ok(serde_json::to_string(&document)?)
+    }
+}
+
+fn rfc3339(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/* ------------------------------------------- Atom -------------------------------------------- */
+
+#[derive(Serialize)]
+#[serde(rename = "feed")]
+struct AtomFeed<'a> {
+    #[serde(rename = "@xmlns")]
+    xmlns: &'a str,
+    id: &'a str,
+    title: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    subtitle: Option<&'a str>,
+    updated: String,
+    link: Vec<AtomLink<'a>>,
+    entry: Vec<AtomEntry<'a>>,
+}
+
+#[derive(Serialize)]
+struct AtomLink<'a> {
+    #[serde(rename = "@rel", skip_serializing_if = "Option::is_none")]
+    rel: Option<&'a str>,
+    #[serde(rename = "@href")]
+    href: &'a str,
+}
+
+#[derive(Serialize)]
+struct AtomEntry<'a> {
+    id: &'a str,
+    title: &'a str,
+    link: AtomLink<'a>,
+    published: String,
+    updated: String,
+    author: AtomPerson<'a>,
+    category: Vec<AtomCategory<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<AtomText<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<AtomText<'a>>,
+}
+
+#[derive(Serialize)]
+struct AtomPerson<'a> {
+    name: &'a str,
+    uri: &'a str,
+}
+
+#[derive(Serialize)]
+struct AtomCategory<'a> {
+    #[serde(rename = "@term")]
+    term: &'a str,
+}
+
+#[derive(Serialize)]
+struct AtomText<'a> {
+    #[serde(rename = "@type")]
+    kind: &'a str,
+    #[serde(rename = "$text")]
+    value: &'a str,
+}
+
+/* -------------------------------------------- RSS -------------------------------------------- */
+
+#[derive(Serialize)]
+#[serde(rename = "rss")]
+struct RssDocument<'a> {
+    #[serde(rename = "@version")]
+    version: &'a str,
+    #[serde(rename = "@xmlns:atom")]
+    xmlns_atom: &'a str,
+    channel: RssChannel<'a>,
+}
+
+#[derive(Serialize)]
+struct RssChannel<'a> {
+    title: &'a str,
+    link: &'a str,
+    description: &'a str,
+    #[serde(rename = "lastBuildDate", skip_serializing_if = "Option::is_none")]
+    last_build_date: Option<String>,
+    #[serde(rename = "atom:link")]
+    atom_link: AtomLink<'a>,
+    item: Vec<RssItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct RssItem<'a> {
+    title: &'a str,
+    link: &'a str,
+    guid: &'a str,
+    #[serde(rename = "pubDate")]
+    pub_date: String,
+    category: Vec<&'a str>,
+    description: &'a str,
+}
+
+/* ------------------------------------------- JSON -------------------------------------------- */
+
+#[derive(Serialize)]
+struct JsonFeed<'a> {
+    version: &'a str,
+    title: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    home_page_url: &'a str,
+    feed_url: &'a str,
+    items: Vec<JsonItem<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonItem<'a> {
+    id: &'a str,
+    url: &'a str,
+    title: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_html: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    summary: Option<&'a str>,
+    date_published: String,
+    date_modified: String,
+    authors: Vec<JsonAuthor<'a>>,
+    tags: Vec<&'a str>,
+}
+
+#[derive(Serialize)]
+struct JsonAuthor<'a> {
+    name: &'a str,
+    url: &'a str,
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+    use serde_json::Value;
+
+    use super::{Content, Entry, Feed, FeedFormat};
+
+    fn date(value: &str) -> DateTime<Utc> {
+        value.parse().unwrap()
+    }
+
+    fn feed(content: Content) -> Feed {
+        Feed {
+            title: "Polar".to_string(),
+            description: String::new(),
+            home_url: "https://blog.example/".to_string(),
+            feed_url: "https://blog.example/feed.atom".to_string(),
+            entries: vec![Entry {
+                url: "https://blog.example/posts/hello".to_string(),
+                title: "Hello & welcome".to_string(),
+                author_name: "Jane".to_string(),
+                author_url: "https://blog.example/authors/jane".to_string(),
+                published: date("2024-11-01T10:00:00Z"),
+                updated: date("2024-11-02T10:00:00Z"),
+                tags: vec!["Rust".to_string()],
+                content,
+            }],
+        }
+    }
+
+    #[test]
+    fn atom() {
+        let xml = feed(Content::Html("<p>Hi</p>".to_string()))
+            .render(FeedFormat::Atom)
+            .unwrap();
+
+        assert!(xml.starts_with(
+            r#"<?xml version="1.0" encoding="utf-8"?><feed xmlns="http://www.w3.org/2005/Atom">"#
+        ));
+        assert!(xml.contains("<updated>2024-11-02T10:00:00Z</updated>"));
+        assert!(xml.contains(r#"<link rel="self" href="https://blog.example/feed.atom"/>"#));
+        assert!(xml.contains("<title>Hello &amp; welcome</title>"));
+        assert!(xml.contains(r#"<category term="Rust"/>"#));
+        assert!(xml.contains(r#"<content type="html">&lt;p&gt;Hi&lt;/p&gt;</content>"#));
+        assert!(!xml.contains("<summary"));
+    }
+
+    #[test]
+    fn rss() {
+        let xml = feed(Content::Summary("Hi".to_string()))
+            .render(FeedFormat::Rss)
+            .unwrap();
+
+        assert!(xml.contains(r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">"#));
+        assert!(xml.contains("<lastBuildDate>Sat, 2 Nov 2024 10:00:00 +0000</lastBuildDate>"));
+        assert!(xml.contains("<pubDate>Fri, 1 Nov 2024 10:00:00 +0000</pubDate>"));
+        assert!(xml.contains("<category>Rust</category><description>Hi</description>"));
+    }
+
+    #[test]
+    fn json() {
+        let json = feed(Content::Summary("Hi".to_string()))
+            .render(FeedFormat::Json)
+            .unwrap();
+        let json: Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(json["version"], "https://jsonfeed.org/version/1.1");
+        assert!(json.get("description").is_none());
+        assert_eq!(json["items"][0]["summary"], "Hi");
+        assert!(json["items"][0].get("content_html").is_none());
+        assert_eq!(json["items"][0]["authors"][0]["name"], "Jane");
+        assert_eq!(json["items"][0]["date_published"], "2024-11-01T10:00:00Z");
+    }
+}
@@ -0,0 +1,402 @@
+//! Markdown files with front matter, as written for Jekyll, Hugo and most
+//! static site generators
+//!
+//! Every `.md` or `.markdown` file of a directory and its subdirectories is a
+//! post, save for hidden files and the `_index.md` pages of Hugo sections. Its
+//! front matter is YAML between `---` lines or TOML between `+++` lines, giving
+//! its `title`, `date`, `slug`, `tags`, `categories`, `author` and whether it
+//! is a `draft`, or not `published` as Jekyll has it. Former URLs are read from
+//! `permalink`, `url` and `aliases`. Posts dated in the future are scheduled.
+//!
+//! Posts without a date in their front matter are dated by their Jekyll file
+//! name, `2024-01-31-hello.md`, and files of a `_drafts` directory are drafts.
+//! Posts without a slug are named after their file, or their directory for the
+//! `index.md` of Hugo page bundles.
+
+use std::fs;
+use std::io::{ErrorKind, Result as IOResult};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+use serde::Deserialize;
+
+use super::{Content, Problem, Source};
+use crate::app::core::database::models::PostStatus;
+
+/// Formats of the dates of front matter lacking an offset, taken as UTC ones
+const NAIVE_DATE_FORMATS: [&str; 3] = [
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Formats of the dates of front matter with an offset, besides RFC 3339
+const DATE_FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M:%S%.f %z", "%Y-%m-%d %H:%M %z"];
+
+/// Read the posts of a directory, the former URLs of those giving none
+/// following the given permalink pattern
+pub fn read(directory: &Path, permalink: Option<&str>) -> IOResult<Content> {
+    let mut content = Content::default();
+    for file in files(directory)? {
+        let path = file.strip_prefix(directory).unwrap_or(&file);
+        let origin = path.display().to_string();
+        let text = match fs::read_to_string(&file) {
+            Err(e) if e.kind() == ErrorKind::InvalidData => {
+                content
+                    .problems
+                    .push(Problem::new(origin, "is not valid UTF-8"));
+                continue;
+            }
+            text => text?,
+        };
+        match parse(path, &text, permalink) {
+            Ok(source) => content.posts.push(source),
+            Err(message) => content.problems.push(Problem::new(origin, message)),
+        }
+    }
+    Ok(content)
+}
+
+/// Markdown files of a directory and its subdirectories, in order
+fn files(directory: &Path) -> IOResult<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut directories = vec![directory.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        for entry in fs::read_dir(&directory)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') || name.starts_with("_index.") {
+                continue;
+            }
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                directories.push(path);
+            } else if matches!(
+                path.extension().and_then(|extension| extension.to_str()),
+                Some("md" | "markdown")
+            ) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Post of the given content, read from the given path of the directory
+fn parse(path: &Path, content: &str, permalink: Option<&str>) -> Result<Source, String> {
+    let (front_matter, body) = split(content).ok_or("has no front matter")?;
+    let front_matter = front_matter.map_err(|e| format!("has invalid front matter: {}", e))?;
+    let (file_date, file_slug) = file_name(path);
+
+    let title = front_matter.title.ok_or("has no title")?;
+    let date = match front_matter.date {
+        Some(date) => parse_date(&date).ok_or_else(|| format!("has an invalid date {}", date))?,
+        None => file_date
+            .map(|date| date.and_time(Default::default()).and_utc().fixed_offset())
+            .ok_or("has no date, in its front matter or file name")?,
+    };
+    let slug = slug::slugify(front_matter.slug.as_deref().unwrap_or(&file_slug));
+    let status = if front_matter.draft
+        || front_matter.published == Some(false)
+        || path.components().any(|c| c.as_os_str() == "_drafts")
+    {
+        PostStatus::Draft
+    } else if date > Utc::now() {
+        PostStatus::Scheduled
+    } else {
+        PostStatus::Published
+    };
+
+    let mut redirects = match (front_matter.permalink.or(front_matter.url), permalink) {
+        (Some(url), _) => vec![url],
+        (None, Some(pattern)) => vec![expand(pattern, &date, &slug)],
+        (None, None) => Vec::new(),
+    };
+    redirects.extend(front_matter.aliases.into_vec());
+    // Jekyll separates tags and categories given as a string by spaces
+    let terms = |terms| match terms {
+        OneOrMany::One(terms) => terms.split_whitespace().map(String::from).collect(),
+        OneOrMany::Many(terms) => terms,
+    };
+
+    Ok(Source {
+        origin: path.display().to_string(),
+        title,
+        slug,
+        author: front_matter.author.into_vec().into_iter().next(),
+        date: date.with_timezone(&Utc),
+        status,
+        tags: terms(front_matter.tags),
+        categories: terms(front_matter.categories),
+        body_markdown: body.trim_start_matches(['\r', '\n']).trim_end().to_string(),
+        comments_enabled: true,
+        comments: Vec::new(),
+        redirects,
+    })
+}
+
+/* ---------------------------------------- Front Matter --------------------------------------- */
+
+/// Fields of the front matter of a post, others being ignored
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct FrontMatter {
+    title: Option<String>,
+    date: Option<String>,
+    slug: Option<String>,
+    tags: OneOrMany,
+    categories: OneOrMany,
+    #[serde(alias = "authors")]
+    author: OneOrMany,
+    draft: bool,
+    published: Option<bool>,
+    permalink: Option<String>,
+    url: Option<String>,
+    aliases: OneOrMany,
+}
+
+/// Field given either as a single string or a list of them
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Default for OneOrMany {
+    fn default() -> Self {
+        OneOrMany::Many(Vec::new())
+    }
+}
+
+impl OneOrMany {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            OneOrMany::One(value) => vec![value],
+            OneOrMany::Many(values) => values,
+        }
+    }
+}
+
+/// Front matter and body of the content of a file, unless it has no front
+/// matter
+fn split(content: &str) -> Option<(Result<FrontMatter, String>, &str)> {
+    let content = content.trim_start_matches('\u{feff}');
+    let mut lines = content.split_inclusive('\n');
+    let first = lines.next()?;
+    let delimiter = first.trim_end();
+    if delimiter != "---" && delimiter != "+++" {
+        return None;
+    }
+
+    let mut end = first.len();
+    for line in lines {
+        // YAML documents may also end with `...`
+        let line_end = line.trim_end();
+        if line_end == delimiter || (delimiter == "---" && line_end == "...") {
+            let text = &content[first.len()..end];
+            let front_matter = match (delimiter, text.trim().is_empty()) {
+                (_, true) => Ok(FrontMatter::default()),
+                ("---", false) => serde_yaml::from_str(text).map_err(|e| e.to_string()),
+                _ => toml_front_matter(text),
+            };
+            return Some((front_matter, &content[end + line.len()..]));
+        }
+        end += line.len();
+    }
+    None
+}
+
+fn toml_front_matter(text: &str) -> Result<FrontMatter, String> {
+    let mut value = toml::Value::Table(text.parse().map_err(|e: toml::de::Error| e.to_string())?);
+    stringify_dates(&mut value);
+    value.try_into().map_err(|e: toml::de::Error| e.to_string())
+}
+
+/// Turn the dates of a TOML value into strings, as they are in YAML
+fn stringify_dates(value: &mut toml::Value) {
+    match value {
+        toml::Value::Datetime(datetime) => *value = toml::Value::String(datetime.to_string()),
+        toml::Value::Array(values) => values.iter_mut().for_each(stringify_dates),
+        toml::Value::Table(table) => table
+            .iter_mut()
+            .for_each(|(_, value)| stringify_dates(value)),
+        _ => {}
+    }
+}
+
+/* ------------------------------------------- Dates ------------------------------------------- */
+
+/// Date of a post as written in its front matter, dates alone being midnight
+/// UTC
+fn parse_date(date: &str) -> Option<DateTime<FixedOffset>> {
+    let date = date.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(date);
+    }
+    if let Some(date) = DATE_FORMATS
+        .iter()
+        .find_map(|format| DateTime::parse_from_str(date, format).ok())
+    {
+        return Some(date);
+    }
+    NAIVE_DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .ok()
+                .map(|date| date.and_time(Default::default()))
+        })
+        .map(|date| date.and_utc().fixed_offset())
+}
+
+/// Date a Jekyll file name starts with, if any, and the slug the file name
+/// gives, the one of the directory of a page bundle's `index.md`
+fn file_name(path: &Path) -> (Option<NaiveDate>, String) {
+    let stem = |path: &Path| {
+        path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    let name = match (stem(path), path.parent()) {
+        (name, Some(parent)) if name == "index" && parent.file_name().is_some() => stem(parent),
+        (name, _) => name,
+    };
+    let date = name
+        .get(..10)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+    match (date, name.get(10..11)) {
+        (Some(date), Some("-")) => (Some(date), name[11..].to_string()),
+        _ => (None, name),
+    }
+}
+
+/// Former URL of a post following a permalink pattern, replacing its `:year`,
+/// `:month`, `:day` and `:slug` or `:title`
+fn expand(pattern: &str, date: &DateTime<FixedOffset>, slug: &str) -> String {
+    pattern
+        .replace(":year", &format!("{:04}", date.year()))
+        .replace(":month", &format!("{:02}", date.month()))
+        .replace(":day", &format!("{:02}", date.day()))
+        .replace(":slug", slug)
+        .replace(":title", slug)
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use chrono::{DateTime, NaiveDate};
+
+    use super::{file_name, parse, parse_date};
+    use crate::app::core::database::models::PostStatus;
+
+    fn date(rfc3339: &str) -> Option<DateTime<chrono::FixedOffset>> {
+        Some(DateTime::parse_from_rfc3339(rfc3339).unwrap())
+    }
+
+    #[test]
+    fn yaml_front_matter() {
+        let content = "---\ntitle: Hello, world\ndate: 2024-01-31 10:00:00 +0100\ntags: rust web\n\
+                       categories: notes\npublished: false\npermalink: /hello.html\naliases: [/old/hello/]\n---\n\n\
+                       # Hello\n";
+        let source = parse(Path::new("_posts/hello.md"), content, Some("/:slug/")).unwrap();
+
+        assert_eq!(source.title, "Hello, world");
+        assert_eq!(source.slug, "hello");
+        assert_eq!(source.date.to_rfc3339(), "2024-01-31T09:00:00+00:00");
+        assert_eq!(source.tags, ["rust", "web"]);
+        assert_eq!(source.categories, ["notes"]);
+        assert_eq!(source.status, PostStatus::Draft);
+        assert_eq!(source.redirects, ["/hello.html", "/old/hello/"]);
+        assert_eq!(source.body_markdown, "# Hello");
+        assert_eq!(source.author, None);
+    }
+
+    #[test]
+    fn toml_front_matter() {
+        let content =
+            "+++\ntitle = \"Bundled\"\ndate = 2024-01-31T10:00:00Z\nslug = \"Renamed Post\"\n\
+                       tags = [\"Rust\", \"Web APIs\"]\nauthors = [\"Jane Doe\"]\n+++\nBody\n";
+        let source = parse(
+            Path::new("posts/2023-05-01-bundle/index.md"),
+            content,
+            Some("/:year/:month/:day/:title/"),
+        )
+        .unwrap();
+
+        assert_eq!(source.slug, "renamed-post");
+        assert_eq!(source.date.to_rfc3339(), "2024-01-31T10:00:00+00:00");
+        assert_eq!(source.tags, ["Rust", "Web APIs"]);
+        assert_eq!(source.author.as_deref(), Some("Jane Doe"));
+        assert_eq!(source.status, PostStatus::Published);
+        assert_eq!(source.redirects, ["/2024/01/31/renamed-post/"]);
+        assert_eq!(source.body_markdown, "Body");
+    }
+
+    #[test]
+    fn file_names() {
+        let content = "---\ntitle: Dated by name\n---\n";
+        let source = parse(Path::new("_drafts/2024-01-31-dated.md"), content, None).unwrap();
+
+        assert_eq!(source.slug, "dated");
+        assert_eq!(source.date.to_rfc3339(), "2024-01-31T00:00:00+00:00");
+        assert_eq!(source.status, PostStatus::Draft);
+        assert_eq!(
+            file_name(Path::new("2024-01-31-hello.markdown")),
+            (NaiveDate::from_ymd_opt(2024, 1, 31), "hello".to_string())
+        );
+        assert_eq!(
+            file_name(Path::new("index.md")),
+            (None, "index".to_string())
+        );
+
+        assert!(parse(Path::new("undated.md"), content, None).is_err());
+        assert!(parse(Path::new("plain.md"), "# No front matter", None).is_err());
+        assert!(parse(
+            Path::new("untitled.md"),
+            "---\ndate: 2024-01-31\n---\n",
+            None
+        )
+        .is_err());
+        assert!(parse(Path::new("invalid.md"), "---\ntitle: [\n---\n", None).is_err());
+    }
+
+    #[test]
+    fn scheduled_posts() {
+        let content = "---\ntitle: Coming soon\ndate: 2999-01-31\n---\n";
+        let source = parse(Path::new("soon.md"), content, None).unwrap();
+        assert_eq!(source.status, PostStatus::Scheduled);
+
+        let content = "---\ntitle: Coming soon\ndate: 2999-01-31\ndraft: true\n---\n";
+        let source = parse(Path::new("soon.md"), content, None).unwrap();
+        assert_eq!(source.status, PostStatus::Draft);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(
+            parse_date("2024-01-31T10:00:00+01:00"),
+            date("2024-01-31T10:00:00+01:00")
+        );
+        assert_eq!(
+            parse_date("2024-01-31 10:00:00 +0100"),
+            date("2024-01-31T10:00:00+01:00")
+        );
+        assert_eq!(
+            parse_date("2024-01-31 10:00 -05:00"),
+            date("2024-01-31T10:00:00-05:00")
+        );
+        assert_eq!(
+            parse_date("2024-01-31T10:00:00.5"),
+            date("2024-01-31T10:00:00.5Z")
+        );
+        assert_eq!(parse_date("2024-01-31"), date("2024-01-31T00:00:00Z"));
+        assert_eq!(parse_date("31/01/2024"), None);
+    }
+}
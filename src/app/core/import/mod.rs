@@ -0,0 +1,783 @@
+//! # Imports
+//!
+//! Content written for another blog engine is read by the importer of its
+//! format, then checked as a whole into a [Plan] of the users, tags,
+//! categories, posts, comments and redirects the import would create. A plan
+//! is only carried out when it lists no problem, in a single transaction, so
+//! that an import either succeeds as a whole or leaves the blog untouched.
+//!
+//! Former URLs of the posts are kept as [redirects](Redirect), for links to the
+//! previous blog to lead to the imported posts. Files the posts link to may be
+//! fetched into the media storage, the posts then linking to the media.
+
+pub mod markdown;
+pub mod wordpress;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use chrono::{DateTime, Utc};
+use diesel::{OptionalExtension, QueryResult};
+use rocket::http::RawStr;
+use rocket_db_pools::diesel::scoped_futures::ScopedFutureExt;
+use rocket_db_pools::diesel::{AsyncConnection, AsyncPgConnection};
+
+use super::database::models::{
+    Category, Comment, CommentStatus, Media, NewCategory, NewComment, NewMedia, NewPost,
+    NewRedirect, NewTag, NewUser, Post, PostStatus, Redirect, Tag, User,
+};
+use super::metadata::Preview;
+use super::validation::{check_comment, check_post, check_terms, is_email, is_username};
+use crate::security::Role;
+
+/// Password hash no password matches, imported users being unable to log in
+/// until given a password with `polar user passwd`
+const LOCKED_PASSWORD: &str = "!";
+
+/// Reserved domain of the email addresses of imported users whose own is
+/// unknown or taken
+const EMAIL_DOMAIN: &str = "users.invalid";
+
+const MAX_PATH_LENGTH: usize = 2048;
+const MAX_DISPLAY_NAME_LENGTH: usize = 255;
+
+/// Content read from another blog
+#[derive(Debug, Default)]
+pub struct Content {
+    pub posts: Vec<Source>,
+    /// Authors the source tells more of than their name
+    pub authors: Vec<SourceAuthor>,
+    pub attachments: Vec<Attachment>,
+    /// Problems met while reading the content
+    pub problems: Vec<Problem>,
+}
+
+/// Post as read from another blog
+#[derive(Debug, Clone)]
+pub struct Source {
+    /// Where the post was read from, for problems to point at
+    pub origin: String,
+    pub title: String,
+    pub slug: String,
+    /// Name of the author, posts naming none being attributed to the default one
+    pub author: Option<String>,
+    /// Publication date, or the one a scheduled post is to be published at
+    pub date: DateTime<Utc>,
+    /// Draft, published or scheduled
+    pub status: PostStatus,
+    /// Names of the tags of the post
+    pub tags: Vec<String>,
+    /// Names of the categories of the post
+    pub categories: Vec<String>,
+    pub body_markdown: String,
+    pub comments_enabled: bool,
+    /// Comments of the post, parents before their replies
+    pub comments: Vec<SourceComment>,
+    /// Former URLs or paths of the post
+    pub redirects: Vec<String>,
+}
+
+/// Comment on a post as read from another blog
+#[derive(Debug, Clone)]
+pub struct SourceComment {
+    /// Identifier of the comment in the source, replies naming their parent by it
+    pub id: String,
+    pub parent: Option<String>,
+    /// Name of the author, unless the comment is anonymous
+    pub author: Option<String>,
+    pub author_name: String,
+    pub author_email: Option<String>,
+    /// Plain text of the comment
+    pub body: String,
+    pub status: CommentStatus,
+    pub date: DateTime<Utc>,
+}
+
+/// Author of posts or comments, as posts and comments name them
+#[derive(Debug, Clone)]
+pub struct SourceAuthor {
+    pub name: String,
+    pub display_name: String,
+    pub email: Option<String>,
+}
+
+/// File uploaded to another blog, which its posts may link to
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub origin: String,
+    pub url: String,
+    /// Name of the uploader, the default author when missing
+    pub owner: Option<String>,
+}
+
+/// Reason for which an import cannot be carried out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub origin: String,
+    pub message: String,
+}
+
+impl Problem {
+    pub fn new<O: Into<String>, M: Into<String>>(origin: O, message: M) -> Self {
+        Problem {
+            origin: origin.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}: {}", self.origin, self.message)
+    }
+}
+
+/// Path a former URL of a post is requested at, without its scheme, host,
+/// query, trailing slash or `index.html`, unless it is the root
+pub fn redirect_path(url: &str) -> Option<String> {
+    let path = match url.split_once("://") {
+        Some((_, rest)) => &rest[rest.find('/').unwrap_or(rest.len())..],
+        None => url,
+    };
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let path = RawStr::new(path).percent_decode_lossy();
+    let path = path.trim_matches('/');
+    let path = match path.strip_suffix("index.html") {
+        Some(parent) if parent.is_empty() || parent.ends_with('/') => parent.trim_end_matches('/'),
+        _ => path,
+    };
+    (!path.is_empty()).then(|| format!("/{}", path))
+}
+
+/// Point the links of a Markdown body to a file of another blog, whatever its
+/// host, to the given path instead. Links to the copies WordPress resizes
+/// images to, `<name>-<width>x<height>.<extension>`, are pointed there as well.
+pub fn relink(markdown: &str, url: &str, target: &str) -> String {
+    let path = match url.split_once("://") {
+        Some((_, rest)) => &rest[rest.find('/').unwrap_or(rest.len())..],
+        None => url,
+    };
+    let (stem, extension) = match path.rsplit_once('.') {
+        Some((stem, extension)) if !extension.contains('/') => (stem, extension),
+        _ => (path, ""),
+    };
+    if stem.is_empty() {
+        return markdown.to_string();
+    }
+    let is_delimiter = |c: char| c.is_whitespace() || "()[]<>\"'=".contains(c);
+
+    let mut relinked = String::with_capacity(markdown.len());
+    let mut rest = markdown;
+    while let Some(found) = rest.find(stem) {
+        let (before, after) = (&rest[..found], &rest[found + stem.len()..]);
+        let resized = resized_suffix(after);
+        let end = match extension {
+            "" => Some(resized),
+            _ => after[resized..]
+                .strip_prefix('.')
+                .and_then(|tail| tail.strip_prefix(extension))
+                .map(|_| resized + 1 + extension.len()),
+        };
+        let start = before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| is_delimiter(*c))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        // Only the host may come before the path, and nothing more of a URL after it
+        let host = &before[start..];
+        let is_link = host.is_empty()
+            || host
+                .split_once("//")
+                .is_some_and(|(_, host)| !host.contains('/'));
+        match end {
+            Some(end)
+                if is_link
+                    && !after[end..]
+                        .starts_with(|c: char| c.is_alphanumeric() || "-_./".contains(c)) =>
+            {
+                relinked.push_str(&before[..start]);
+                relinked.push_str(target);
+                rest = &after[end..];
+            }
+            _ => {
+                relinked.push_str(before);
+                relinked.push_str(stem);
+                rest = after;
+            }
+        }
+    }
+    relinked.push_str(rest);
+    relinked
+}
+
+/// Length of the `-<width>x<height>` suffix the given text starts with, if any
+fn resized_suffix(text: &str) -> usize {
+    let digits = |text: &str| {
+        text.find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len())
+    };
+    let Some(rest) = text.strip_prefix('-') else {
+        return 0;
+    };
+    let width = digits(rest);
+    let Some(rest) = rest[width..].strip_prefix('x') else {
+        return 0;
+    };
+    match (width, digits(rest)) {
+        (0, _) | (_, 0) => 0,
+        (width, height) => 1 + width + 1 + height,
+    }
+}
+
+/// Username of an author, as is when it can be one
+fn username_of(author: &str) -> String {
+    match is_username(author) {
+        true => author.to_string(),
+        false => slug::slugify(author),
+    }
+}
+
+/* -------------------------------------------- Plan ------------------------------------------- */
+
+/// User created for the posts of an author without an account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewAuthor {
+    pub username: String,
+    pub display_name: String,
+    pub email: String,
+}
+
+/// Post to import, attributed to the user of the given username
+#[derive(Debug, Clone)]
+pub struct PlannedPost {
+    pub source: Source,
+    pub author: String,
+}
+
+/// Attachment to fetch, owned by the user of the given username
+#[derive(Debug, Clone)]
+pub struct PlannedAttachment {
+    pub url: String,
+    pub owner: String,
+}
+
+/// Attachment fetched into the media storage, recorded along with the posts
+#[derive(Debug)]
+pub struct FetchedMedia {
+    pub hash: String,
+    pub owner: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub size: i64,
+    pub preview: Option<Preview>,
+}
+
+/// Everything an import creates, or the problems preventing it
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub users: Vec<NewAuthor>,
+    /// Names of the tags which do not exist yet
+    pub tags: Vec<String>,
+    /// Names of the categories which do not exist yet
+    pub categories: Vec<String>,
+    pub posts: Vec<PlannedPost>,
+    pub attachments: Vec<PlannedAttachment>,
+    pub media: Vec<FetchedMedia>,
+    pub problems: Vec<Problem>,
+    /// Text search configuration imported posts are indexed with
+    language: String,
+}
+
+impl Plan {
+    /// Check the given content against itself and the content of the blog
+    pub async fn new(
+        conn: &mut AsyncPgConnection,
+        content: Content,
+        default_author: &str,
+        language: &str,
+    ) -> QueryResult<Plan> {
+        let mut plan = Plan {
+            problems: content.problems,
+            language: language.to_string(),
+            ..Plan::default()
+        };
+        if !Post::language_exists(conn, language).await? {
+            plan.problems.push(Problem::new(
+                "search.language",
+                format!("has no text search configuration {}", language),
+            ));
+        }
+
+        // Usernames of the authors, along with their display name and email
+        let known: HashMap<String, &SourceAuthor> = content
+            .authors
+            .iter()
+            .map(|author| (username_of(&author.name), author))
+            .collect();
+        let mut authors = BTreeMap::new();
+        let mut author = |name: &str, origin: &str, problems: &mut Vec<Problem>| {
+            let username = username_of(name);
+            if !is_username(&username) {
+                problems.push(Problem::new(
+                    origin,
+                    format!("author {} cannot be given a username", name),
+                ));
+            }
+            authors.entry(username.clone()).or_insert_with(|| {
+                let display_name = known.get(&username).map_or(name, |a| &a.display_name);
+                let email = known.get(&username).and_then(|a| a.email.clone());
+                let display_name = display_name.chars().take(MAX_DISPLAY_NAME_LENGTH).collect();
+                (display_name, email.filter(|email| is_email(email)))
+            });
+            username
+        };
+
+        let mut slugs = HashSet::new();
+        let mut paths = HashSet::new();
+        let mut tags = BTreeMap::new();
+        let mut categories = BTreeMap::new();
+        for mut source in content.posts {
+            let origin = source.origin.as_str();
+            let mut errors = check_post(Some(&source.slug), Some(&source.title));
+            errors.extend(check_terms("tags", &source.tags));
+            errors.extend(check_terms("categories", &source.categories));
+            let mut problems: Vec<Problem> = errors
+                .iter()
+                .map(|error| Problem::new(origin, error.to_string()))
+                .collect();
+            if !slugs.insert(source.slug.clone()) {
+                problems.push(Problem::new(
+                    origin,
+                    format!("slug {} is the one of another imported post", source.slug),
+                ));
+            }
+
+            let username = author(
+                source.author.as_deref().unwrap_or(default_author),
+                origin,
+                &mut problems,
+            );
+            for comment in &source.comments {
+                let origin = format!("{}, comment {}", origin, comment.id);
+                if let Some(name) = &comment.author {
+                    author(name, &origin, &mut problems);
+                }
+                let errors = check_comment(
+                    Some(&comment.author_name),
+                    comment.author_email.as_deref(),
+                    &comment.body,
+                );
+                problems.extend(
+                    errors
+                        .iter()
+                        .map(|error| Problem::new(&origin, error.to_string())),
+                );
+            }
+            for name in &source.tags {
+                tags.entry(slug::slugify(name))
+                    .or_insert_with(|| name.trim().to_string());
+            }
+            for name in &source.categories {
+                categories
+                    .entry(slug::slugify(name))
+                    .or_insert_with(|| name.trim().to_string());
+            }
+
+            // Former paths of the post, unless these are its current one
+            let current = format!("/posts/{}", source.slug);
+            let mut redirects: Vec<String> = Vec::new();
+            for path in source.redirects.iter().filter_map(|url| redirect_path(url)) {
+                if path == current || redirects.contains(&path) {
+                    continue;
+                }
+                if path.chars().count() > MAX_PATH_LENGTH {
+                    problems.push(Problem::new(origin, format!("URL {} is too long", path)));
+                } else if !paths.insert(path.clone()) {
+                    problems.push(Problem::new(
+                        origin,
+                        format!("URL {} is the one of another imported post", path),
+                    ));
+                }
+                redirects.push(path);
+            }
+            source.redirects = redirects;
+
+            plan.problems.extend(problems);
+            plan.posts.push(PlannedPost {
+                source,
+                author: username,
+            });
+        }
+        for attachment in content.attachments {
+            let name = attachment.owner.as_deref().unwrap_or(default_author);
+            let owner = author(name, &attachment.origin, &mut plan.problems);
+            plan.attachments.push(PlannedAttachment {
+                url: attachment.url,
+                owner,
+            });
+        }
+
+        let slugs: Vec<&str> = plan
+            .posts
+            .iter()
+            .map(|post| post.source.slug.as_str())
+            .collect();
+        let taken = Post::existing_slugs(conn, &slugs).await?;
+        let paths: Vec<&str> = plan
+            .posts
+            .iter()
+            .flat_map(|post| post.source.redirects.iter().map(String::as_str))
+            .collect();
+        let redirected = Redirect::existing_paths(conn, &paths).await?;
+        let mut problems = Vec::new();
+        for post in &plan.posts {
+            let origin = post.source.origin.as_str();
+            if taken.contains(&post.source.slug) {
+                problems.push(Problem::new(
+                    origin,
+                    format!("slug {} is taken by a post of the blog", post.source.slug),
+                ));
+            }
+            for path in post.source.redirects.iter() {
+                if redirected.contains(path) {
+                    problems.push(Problem::new(
+                        origin,
+                        format!("URL {} already leads to a post of the blog", path),
+                    ));
+                }
+            }
+        }
+        plan.problems.extend(problems);
+
+        // Authors without an account, keeping their email unless it is taken
+        let usernames: Vec<&str> = authors.keys().map(String::as_str).collect();
+        let existing: Vec<String> = User::find_all_by_username(conn, &usernames)
+            .await?
+            .into_iter()
+            .map(|user| user.username)
+            .collect();
+        authors.retain(|username, _| !existing.contains(username));
+        let emails: Vec<&str> = authors
+            .values()
+            .filter_map(|(_, email)| email.as_deref())
+            .collect();
+        let mut taken: HashSet<String> = User::existing_emails(conn, &emails)
+            .await?
+            .into_iter()
+            .map(|email| email.to_lowercase())
+            .collect();
+        plan.users = authors
+            .into_iter()
+            .map(|(username, (display_name, email))| {
+                let email = email
+                    .map(|email| email.to_lowercase())
+                    .filter(|email| taken.insert(email.clone()))
+                    .unwrap_or_else(|| format!("{}@{}", username.to_lowercase(), EMAIL_DOMAIN));
+                NewAuthor {
+                    username,
+                    display_name,
+                    email,
+                }
+            })
+            .collect();
+
+        let existing: Vec<String> = Tag::list(conn)
+            .await?
+            .into_iter()
+            .map(|tag| tag.slug)
+            .collect();
+        plan.tags = tags
+            .into_iter()
+            .filter(|(slug, _)| !existing.contains(slug))
+            .map(|(_, name)| name)
+            .collect();
+        let existing: Vec<String> = Category::list(conn)
+            .await?
+            .into_iter()
+            .map(|category| category.slug)
+            .collect();
+        plan.categories = categories
+            .into_iter()
+            .filter(|(slug, _)| !existing.contains(slug))
+            .map(|(_, name)| name)
+            .collect();
+        Ok(plan)
+    }
+
+    pub fn comments(&self) -> usize {
+        self.posts
+            .iter()
+            .map(|post| post.source.comments.len())
+            .sum()
+    }
+
+    pub fn redirects(&self) -> usize {
+        self.posts
+            .iter()
+            .map(|post| post.source.redirects.len())
+            .sum()
+    }
+
+    /// Record a fetched attachment, the posts linking to its URL now linking
+    /// to the media
+    pub fn add_media(&mut self, url: &str, media: FetchedMedia) {
+        let target = format!("/media/{}", media.hash);
+        for post in &mut self.posts {
+            post.source.body_markdown = relink(&post.source.body_markdown, url, &target);
+        }
+        self.media.push(media);
+    }
+
+    /// Create everything the plan lists, in a single transaction
+    pub async fn commit(&self, conn: &mut AsyncPgConnection) -> QueryResult<()> {
+        conn.transaction(|conn| {
+            async move {
+                for user in &self.users {
+                    let new_user = NewUser {
+                        username: &user.username,
+                        email: &user.email,
+                        password_hash: LOCKED_PASSWORD,
+                        display_name: &user.display_name,
+                        bio: "",
+                        role: Role::Author,
+                    };
+                    User::create(conn, &new_user).await?;
+                }
+                for media in &self.media {
+                    if Media::find_by_hash(conn, &media.hash)
+                        .await
+                        .optional()?
+                        .is_some()
+                    {
+                        continue;
+                    }
+                    let preview = media.preview.as_ref();
+                    let new_media = NewMedia {
+                        hash: &media.hash,
+                        owner: &media.owner,
+                        file_name: &media.file_name,
+                        mime_type: &media.mime_type,
+                        size: media.size,
+                        width: preview.map(|preview| preview.width as i32),
+                        height: preview.map(|preview| preview.height as i32),
+                        blurhash: preview.map(|preview| preview.blurhash.as_str()),
+                        color: preview.map(|preview| preview.color.as_str()),
+                    };
+                    Media::create(conn, &new_media).await?;
+                }
+                for post in &self.posts {
+                    self.create_post(conn, post).await?;
+                }
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    async fn create_post(
+        &self,
+        conn: &mut AsyncPgConnection,
+        post: &PlannedPost,
+    ) -> QueryResult<()> {
+        let source = &post.source;
+        let body_html = super::markdown::render(&source.body_markdown);
+        let new_post = NewPost {
+            slug: &source.slug,
+            title: &source.title,
+            body_markdown: &source.body_markdown,
+            body_html: &body_html,
+            status: source.status,
+            author: &post.author,
+            published_at: (source.status == PostStatus::Published).then_some(source.date),
+            publish_at: (source.status == PostStatus::Scheduled).then_some(source.date),
+            comments_enabled: source.comments_enabled,
+            language: &self.language,
+            created_at: Some(source.date),
+            updated_at: Some(source.date),
+        };
+        let created = Post::create(conn, &new_post).await?;
+
+        let slugs: Vec<String> = source.tags.iter().map(slug::slugify).collect();
+        let new_tags: Vec<NewTag> = slugs
+            .iter()
+            .zip(&source.tags)
+            .map(|(slug, name)| NewTag {
+                slug,
+                name: name.trim(),
+            })
+            .collect();
+        let tags = Tag::ensure(conn, &new_tags).await?;
+        Tag::set_for_post(conn, created.id, &tags).await?;
+
+        let slugs: Vec<String> = source.categories.iter().map(slug::slugify).collect();
+        let new_categories: Vec<NewCategory> = slugs
+            .iter()
+            .zip(&source.categories)
+            .map(|(slug, name)| NewCategory {
+                slug,
+                name: name.trim(),
+                description: "",
+            })
+            .collect();
+        let categories = Category::ensure(conn, &new_categories).await?;
+        Category::set_for_post(conn, created.id, &categories).await?;
+
+        // Replies are created after their parent, whose new identifier they take
+        let mut ids = HashMap::new();
+        for comment in &source.comments {
+            let author = comment.author.as_deref().map(username_of);
+            let new_comment = NewComment {
+                post_id: created.id,
+                parent_id: comment
+                    .parent
+                    .as_ref()
+                    .and_then(|parent| ids.get(parent).copied()),
+                author: author.as_deref(),
+                author_name: &comment.author_name,
+                author_email: comment.author_email.as_deref(),
+                body: &comment.body,
+                status: comment.status,
+                spam_score: None,
+                created_at: Some(comment.date),
+                updated_at: Some(comment.date),
+            };
+            let created = Comment::create(conn, &new_comment).await?;
+            ids.insert(comment.id.clone(), created.id);
+        }
+
+        let redirects: Vec<NewRedirect> = source
+            .redirects
+            .iter()
+            .map(|path| NewRedirect {
+                path,
+                post_id: created.id,
+            })
+            .collect();
+        Redirect::create_all(conn, &redirects).await?;
+        Ok(())
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        if !self.users.is_empty() {
+            writeln!(f, "Users to create:")?;
+            for user in &self.users {
+                writeln!(f, "  {:<32} {}", user.username, user.display_name)?;
+            }
+        }
+        for (title, names) in [
+            ("Tags to create:", &self.tags),
+            ("Categories to create:", &self.categories),
+        ] {
+            if !names.is_empty() {
+                writeln!(f, "{}", title)?;
+                for name in names {
+                    writeln!(f, "  {}", name)?;
+                }
+            }
+        }
+        if !self.posts.is_empty() {
+            writeln!(f, "Posts to import:")?;
+        }
+        for post in &self.posts {
+            let source = &post.source;
+            write!(
+                f,
+                "  {:<32} {} {:<9} by {}",
+                source.slug,
+                source.date.format("%Y-%m-%d"),
+                source.status.as_str(),
+                post.author
+            )?;
+            match source.comments.len() {
+                0 => writeln!(f)?,
+                comments => writeln!(f, ", {} comments", comments)?,
+            }
+            for path in &source.redirects {
+                writeln!(f, "    from {}", path)?;
+            }
+        }
+        if !self.attachments.is_empty() {
+            writeln!(f, "Attachments to fetch:")?;
+            for attachment in &self.attachments {
+                writeln!(f, "  {}", attachment.url)?;
+            }
+        }
+        if !self.problems.is_empty() {
+            writeln!(f, "Problems:")?;
+            for problem in &self.problems {
+                writeln!(f, "  {}", problem)?;
+            }
+        }
+        writeln!(
+            f,
+            "{} posts, {} comments, {} users, {} tags, {} categories and {} redirects to create, \
+             {} attachments to fetch, {} problems",
+            self.posts.len(),
+            self.comments(),
+            self.users.len(),
+            self.tags.len(),
+            self.categories.len(),
+            self.redirects(),
+            self.attachments.len(),
+            self.problems.len()
+        )
+    }
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::{redirect_path, relink, username_of};
+
+    #[test]
+    fn redirect_paths() {
+        assert_eq!(
+            redirect_path("https://old.example.com/2024/01/31/hello/"),
+            Some("/2024/01/31/hello".to_string())
+        );
+        assert_eq!(
+            redirect_path("/2024/01/31/hello.html?utm_source=feed#top"),
+            Some("/2024/01/31/hello.html".to_string())
+        );
+        assert_eq!(
+            redirect_path("blog/caf%C3%A9/index.html"),
+            Some("/blog/café".to_string())
+        );
+        assert_eq!(
+            redirect_path("/blog/myindex.html"),
+            Some("/blog/myindex.html".to_string())
+        );
+        assert_eq!(redirect_path("https://old.example.com"), None);
+        assert_eq!(redirect_path("/"), None);
+    }
+
+    #[test]
+    fn relinks() {
+        let url = "http://old.example.com/wp-content/uploads/2024/01/bear.jpg";
+        let markdown = "![Bear](https://old.example.com/wp-content/uploads/2024/01/bear-300x200.jpg) \
+                        [full size](/wp-content/uploads/2024/01/bear.jpg \"Bear\") \
+                        ![Cub](https://old.example.com/wp-content/uploads/2024/01/bear-cub.jpg) \
+                        [elsewhere](https://cdn.example.com/mirror/wp-content/uploads/2024/01/bear.jpg)";
+
+        assert_eq!(
+            relink(markdown, url, "/media/3f2a"),
+            "![Bear](/media/3f2a) [full size](/media/3f2a \"Bear\") \
+             ![Cub](https://old.example.com/wp-content/uploads/2024/01/bear-cub.jpg) \
+             [elsewhere](https://cdn.example.com/mirror/wp-content/uploads/2024/01/bear.jpg)"
+        );
+        assert_eq!(
+            relink("no links", "https://old.example.com", "/media/3f2a"),
+            "no links"
+        );
+    }
+
+    #[test]
+    fn usernames() {
+        assert_eq!(username_of("j.doe"), "j.doe");
+        assert_eq!(username_of("Jane Doe"), "jane-doe");
+    }
+}
@@ -0,0 +1,621 @@
+//! WordPress eXtended RSS exports, as written by Tools > Export
+//!
+//! Posts and pages are both imported as posts, along with their tags,
+//! categories and comments, and attributed to the authors the export lists.
+//! Trashed items, automatic drafts and comments marked as spam are left out,
+//! as are the categories named `uncategorized`. Former URLs are read from the
+//! `link` of each item.
+//!
+//! Bodies written with the classic editor lack their paragraphs, added the way
+//! WordPress does when showing them. Bodies are then converted to Markdown
+//! unless they hold elements Markdown cannot express, those being kept as HTML
+//! for the renderer to sanitize. Attachments are listed for their files to be
+//! fetched, the posts linking to them being pointed to the media.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use ammonia::Builder;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rocket::http::RawStr;
+use serde::Deserialize;
+
+use super::{Attachment, Content, Problem, Source, SourceAuthor, SourceComment};
+use crate::app::core::database::models::{CommentStatus, PostStatus};
+use crate::app::core::validation::is_email;
+use crate::result::ImportError;
+
+/// Format of the dates of the export, in UTC or the timezone of the blog
+const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Elements the conversion to Markdown keeps the meaning of
+const CONVERTIBLE: [&str; 43] = [
+    "div",
+    "section",
+    "header",
+    "footer",
+    "p",
+    "br",
+    "hr",
+    "q",
+    "cite",
+    "blockquote",
+    "b",
+    "i",
+    "s",
+    "strong",
+    "em",
+    "del",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "pre",
+    "code",
+    "img",
+    "a",
+    "ol",
+    "ul",
+    "li",
+    "figure",
+    "figcaption",
+    "span",
+    "html",
+    "head",
+    "body",
+    "table",
+    "thead",
+    "tbody",
+    "tr",
+    "th",
+    "td",
+    "sub",
+    "sup",
+];
+
+/// Elements the paragraphs of the classic editor are never added around
+const BLOCKS: [&str; 19] = [
+    "address",
+    "blockquote",
+    "div",
+    "dl",
+    "figure",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+
+/// Read the posts, authors and attachments of an export
+pub fn read(file: &Path) -> Result<Content, ImportError> {
+    let xml = fs::read_to_string(file)?;
+    let rss: Rss =
+        quick_xml::de::from_str(&xml).map_err(|e| ImportError::InvalidExport(e.to_string()))?;
+    Ok(content_of(rss.channel))
+}
+
+fn content_of(channel: Channel) -> Content {
+    let mut content = Content::default();
+    let logins: HashMap<&str, &str> = channel
+        .authors
+        .iter()
+        .map(|author| (author.id.as_str(), author.login.as_str()))
+        .collect();
+
+    for item in &channel.items {
+        let origin = match item.post_type.as_str() {
+            "attachment" => format!("attachment {}", item.id),
+            post_type => format!("{} {}", post_type, item.id),
+        };
+        match (item.post_type.as_str(), item.status.as_str()) {
+            (_, "trash" | "auto-draft") => continue,
+            ("attachment", _) => {
+                if let Some(url) = item.attachment_url.as_ref().filter(|url| !url.is_empty()) {
+                    content.attachments.push(Attachment {
+                        origin,
+                        url: url.clone(),
+                        owner: non_empty(&item.creator),
+                    });
+                }
+                continue;
+            }
+            ("post" | "page", _) => (),
+            _ => continue,
+        }
+        match source_of(item, origin.clone(), &logins) {
+            Ok(source) => content.posts.push(source),
+            Err(message) => content.problems.push(Problem::new(origin, message)),
+        }
+    }
+
+    content.authors = channel
+        .authors
+        .into_iter()
+        .map(|author| SourceAuthor {
+            display_name: non_empty(&author.display_name).unwrap_or_else(|| author.login.clone()),
+            name: author.login,
+            email: non_empty(&author.email),
+        })
+        .collect();
+    content
+}
+
+/// Post of an item, the authors of comments being named by the logins of
+/// their account
+fn source_of(item: &Item, origin: String, logins: &HashMap<&str, &str>) -> Result<Source, String> {
+    let date = date_of(&item.post_date_gmt, &item.post_date)
+        .or_else(|| {
+            DateTime::parse_from_rfc2822(item.pub_date.trim())
+                .ok()
+                .map(|date| date.with_timezone(&Utc))
+        })
+        .ok_or("has no date")?;
+    let status = match item.status.as_str() {
+        _ if !item.password.is_empty() => PostStatus::Draft,
+        "publish" => PostStatus::Published,
+        "future" => PostStatus::Scheduled,
+        _ => PostStatus::Draft,
+    };
+    let title = item.title.trim().to_string();
+    let name = RawStr::new(&item.post_name).percent_decode_lossy();
+    let slug = match slug::slugify(&name) {
+        slug if slug.is_empty() => slug::slugify(&title),
+        slug => slug,
+    };
+
+    let mut tags = Vec::new();
+    let mut categories = Vec::new();
+    for term in &item.terms {
+        let name = term.name.trim().to_string();
+        match term.domain.as_str() {
+            "post_tag" => tags.push(name),
+            "category" if term.nicename != "uncategorized" => categories.push(name),
+            _ => (),
+        }
+    }
+
+    let mut comments: Vec<&WxrComment> = item
+        .comments
+        .iter()
+        .filter(|comment| matches!(comment.comment_type.as_str(), "" | "comment"))
+        .filter(|comment| matches!(comment.approved.as_str(), "1" | "0"))
+        .collect();
+    comments.sort_by_key(|comment| comment.id.parse::<u64>().unwrap_or(u64::MAX));
+    let comments = comments
+        .into_iter()
+        .map(|comment| comment_of(comment, logins))
+        .collect::<Result<_, _>>()?;
+
+    Ok(Source {
+        origin,
+        title,
+        slug,
+        author: non_empty(&item.creator),
+        date,
+        status,
+        tags,
+        categories,
+        body_markdown: html_to_markdown(item.encoded.first().map_or("", String::as_str)),
+        comments_enabled: item.comment_status == "open",
+        comments,
+        redirects: non_empty(&item.link).into_iter().collect(),
+    })
+}
+
+fn comment_of(comment: &WxrComment, logins: &HashMap<&str, &str>) -> Result<SourceComment, String> {
+    let date = date_of(&comment.date_gmt, &comment.date)
+        .ok_or_else(|| format!("has a comment {} without a date", comment.id))?;
+    let author_name = match comment.author.trim() {
+        "" => "Anonymous".to_string(),
+        name => text_of(name),
+    };
+    Ok(SourceComment {
+        id: comment.id.clone(),
+        parent: Some(comment.parent.clone()).filter(|parent| !matches!(parent.as_str(), "" | "0")),
+        author: logins
+            .get(comment.user_id.as_str())
+            .map(|login| login.to_string()),
+        author_name,
+        author_email: non_empty(&comment.author_email).filter(|email| is_email(email)),
+        body: text_of(&comment.content),
+        status: match comment.approved.as_str() {
+            "1" => CommentStatus::Approved,
+            _ => CommentStatus::Pending,
+        },
+        date,
+    })
+}
+
+/// Date given in UTC, or in the timezone of the blog when WordPress left it out
+fn date_of(gmt: &str, local: &str) -> Option<DateTime<Utc>> {
+    [gmt, local]
+        .into_iter()
+        .filter(|date| !date.starts_with("0000"))
+        .find_map(|date| NaiveDateTime::parse_from_str(date.trim(), DATE_FORMAT).ok())
+        .map(|date| date.and_utc())
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    Some(value.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+/* ------------------------------------------- Bodies ------------------------------------------ */
+
+/// Markdown of a body, or the body itself when it holds elements Markdown
+/// cannot express
+fn html_to_markdown(html: &str) -> String {
+    let html = unwrap_captions(html);
+    let html = match tags(&html).any(|tag| tag == "p") || html.contains("<!-- wp:") {
+        true => html,
+        false => autop(&html),
+    };
+    let convertible = tags(&html).all(|tag| CONVERTIBLE.contains(&tag.as_str()));
+    match convertible {
+        true => html2md::parse_html(&html).trim().to_string(),
+        false => html.trim().to_string(),
+    }
+}
+
+/// Lowercase names of the elements of some HTML, comments left out
+fn tags(html: &str) -> impl Iterator<Item = String> + '_ {
+    html.split('<').skip(1).filter_map(|tag| {
+        let name = tag.strip_prefix('/').unwrap_or(tag);
+        let end = name
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(name.len());
+        match &name[..end] {
+            "" => None,
+            name => Some(name.to_ascii_lowercase()),
+        }
+    })
+}
+
+/// Body without the `[caption]` shortcodes WordPress wraps captioned images in
+fn unwrap_captions(html: &str) -> String {
+    let mut unwrapped = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find("[caption") {
+        unwrapped.push_str(&rest[..start]);
+        rest = match rest[start..].find(']') {
+            Some(end) => &rest[start + end + 1..],
+            None => "",
+        };
+    }
+    unwrapped.push_str(rest);
+    unwrapped.replace("[/caption]", "")
+}
+
+/// Paragraphs of a body written with the classic editor, separated by blank
+/// lines, their lines being broken where the body breaks them
+fn autop(html: &str) -> String {
+    let html = html.replace("\r\n", "\n");
+    let mut blocks: Vec<String> = Vec::new();
+    for block in html.split("\n\n") {
+        match blocks.last_mut() {
+            // Preformatted text keeps its blank lines
+            Some(last) if last.matches("<pre").count() > last.matches("</pre>").count() => {
+                last.push_str("\n\n");
+                last.push_str(block);
+            }
+            _ => blocks.push(block.to_string()),
+        }
+    }
+    blocks
+        .iter()
+        .map(|block| block.trim())
+        .filter(|block| !block.is_empty())
+        .map(|block| match block.starts_with("<!--") || is_block(block) {
+            true => block.to_string(),
+            false => format!("<p>{}</p>", block.replace('\n', "<br />\n")),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether some HTML starts with an element paragraphs are not added around
+fn is_block(html: &str) -> bool {
+    tags(html)
+        .next()
+        .filter(|_| html.starts_with('<'))
+        .is_some_and(|tag| BLOCKS.contains(&tag.as_str()))
+}
+
+/// Plain text of a comment, its paragraphs and line breaks kept
+fn text_of(html: &str) -> String {
+    let html = html
+        .replace("\r\n", "\n")
+        .replace("<br />", "\n")
+        .replace("<br/>", "\n")
+        .replace("<br>", "\n")
+        .replace("</p>", "\n\n");
+    let text = Builder::empty().clean(&html).to_string();
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+/* ------------------------------------------- Export ------------------------------------------ */
+
+/// Fields of the export, others being ignored. Elements are named without
+/// their namespace, as the deserializer matches them.
+#[derive(Debug, Deserialize)]
+struct Rss {
+    channel: Channel,
+}
+
+#[derive(Debug, Deserialize)]
+struct Channel {
+    #[serde(rename = "author", default)]
+    authors: Vec<WxrAuthor>,
+    #[serde(rename = "item", default)]
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct WxrAuthor {
+    #[serde(rename = "author_id")]
+    id: String,
+    #[serde(rename = "author_login")]
+    login: String,
+    #[serde(rename = "author_email")]
+    email: String,
+    #[serde(rename = "author_display_name")]
+    display_name: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Item {
+    title: String,
+    link: String,
+    #[serde(rename = "pubDate")]
+    pub_date: String,
+    creator: String,
+    /// Content then excerpt, the namespaces of elements being left out
+    encoded: Vec<String>,
+    #[serde(rename = "post_id")]
+    id: String,
+    post_date: String,
+    post_date_gmt: String,
+    comment_status: String,
+    post_name: String,
+    status: String,
+    post_type: String,
+    #[serde(rename = "post_password")]
+    password: String,
+    attachment_url: Option<String>,
+    #[serde(rename = "category")]
+    terms: Vec<Term>,
+    #[serde(rename = "comment")]
+    comments: Vec<WxrComment>,
+}
+
+/// Tag or category of an item
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Term {
+    #[serde(rename = "@domain")]
+    domain: String,
+    #[serde(rename = "@nicename")]
+    nicename: String,
+    #[serde(rename = "$text")]
+    name: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct WxrComment {
+    #[serde(rename = "comment_id")]
+    id: String,
+    #[serde(rename = "comment_author")]
+    author: String,
+    #[serde(rename = "comment_author_email")]
+    author_email: String,
+    #[serde(rename = "comment_date")]
+    date: String,
+    #[serde(rename = "comment_date_gmt")]
+    date_gmt: String,
+    #[serde(rename = "comment_content")]
+    content: String,
+    #[serde(rename = "comment_approved")]
+    approved: String,
+    comment_type: String,
+    #[serde(rename = "comment_parent")]
+    parent: String,
+    #[serde(rename = "comment_user_id")]
+    user_id: String,
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::{autop, content_of, html_to_markdown, text_of, Rss};
+    use crate::app::core::database::models::{CommentStatus, PostStatus};
+
+    const EXPORT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:content="http://purl.org/rss/1.0/modules/content/"
+     xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:wp="http://wordpress.org/export/1.2/">
+<channel>
+  <title>Old blog</title>
+  <wp:author>
+    <wp:author_id>1</wp:author_id>
+    <wp:author_login><![CDATA[jane]]></wp:author_login>
+    <wp:author_email><![CDATA[jane@example.com]]></wp:author_email>
+    <wp:author_display_name><![CDATA[Jane Doe]]></wp:author_display_name>
+  </wp:author>
+  <item>
+    <title><![CDATA[Hello world]]></title>
+    <link>https://old.example.com/2024/01/31/hello-world/</link>
+    <pubDate>Wed, 31 Jan 2024 09:00:00 +0000</pubDate>
+    <dc:creator><![CDATA[jane]]></dc:creator>
+    <content:encoded><![CDATA[First <strong>post</strong>.
+
+Second paragraph]]></content:encoded>
+    <wp:post_id>7</wp:post_id>
+    <wp:post_date><![CDATA[2024-01-31 10:00:00]]></wp:post_date>
+    <wp:post_date_gmt><![CDATA[2024-01-31 09:00:00]]></wp:post_date_gmt>
+    <wp:comment_status><![CDATA[open]]></wp:comment_status>
+    <wp:post_name><![CDATA[hello-world]]></wp:post_name>
+    <wp:status><![CDATA[publish]]></wp:status>
+    <wp:post_type><![CDATA[post]]></wp:post_type>
+    <category domain="category" nicename="uncategorized"><![CDATA[Uncategorized]]></category>
+    <category domain="category" nicename="notes"><![CDATA[Notes]]></category>
+    <category domain="post_tag" nicename="rust"><![CDATA[Rust]]></category>
+    <wp:comment>
+      <wp:comment_id>12</wp:comment_id>
+      <wp:comment_author><![CDATA[Jane Doe]]></wp:comment_author>
+      <wp:comment_author_email><![CDATA[jane@example.com]]></wp:comment_author_email>
+      <wp:comment_date><![CDATA[2024-02-01 11:00:00]]></wp:comment_date>
+      <wp:comment_date_gmt><![CDATA[2024-02-01 10:00:00]]></wp:comment_date_gmt>
+      <wp:comment_content><![CDATA[Thanks!]]></wp:comment_content>
+      <wp:comment_approved><![CDATA[1]]></wp:comment_approved>
+      <wp:comment_type><![CDATA[comment]]></wp:comment_type>
+      <wp:comment_parent>11</wp:comment_parent>
+      <wp:comment_user_id>1</wp:comment_user_id>
+    </wp:comment>
+    <wp:comment>
+      <wp:comment_id>11</wp:comment_id>
+      <wp:comment_author><![CDATA[Reader]]></wp:comment_author>
+      <wp:comment_author_email><![CDATA[not an email]]></wp:comment_author_email>
+      <wp:comment_date><![CDATA[2024-02-01 10:00:00]]></wp:comment_date>
+      <wp:comment_date_gmt><![CDATA[2024-02-01 09:00:00]]></wp:comment_date_gmt>
+      <wp:comment_content><![CDATA[Nice <em>post</em><br />Really]]></wp:comment_content>
+      <wp:comment_approved><![CDATA[0]]></wp:comment_approved>
+      <wp:comment_type><![CDATA[]]></wp:comment_type>
+      <wp:comment_parent>0</wp:comment_parent>
+      <wp:comment_user_id>0</wp:comment_user_id>
+    </wp:comment>
+    <wp:comment>
+      <wp:comment_id>13</wp:comment_id>
+      <wp:comment_author><![CDATA[Spammer]]></wp:comment_author>
+      <wp:comment_content><![CDATA[Buy now]]></wp:comment_content>
+      <wp:comment_approved><![CDATA[spam]]></wp:comment_approved>
+    </wp:comment>
+  </item>
+  <item>
+    <title><![CDATA[bear.jpg]]></title>
+    <dc:creator><![CDATA[jane]]></dc:creator>
+    <wp:post_id>8</wp:post_id>
+    <wp:status><![CDATA[inherit]]></wp:status>
+    <wp:post_type><![CDATA[attachment]]></wp:post_type>
+    <wp:attachment_url><![CDATA[https://old.example.com/wp-content/uploads/2024/01/bear.jpg]]></wp:attachment_url>
+  </item>
+  <item>
+    <title><![CDATA[About]]></title>
+    <wp:post_id>9</wp:post_id>
+    <wp:post_date><![CDATA[2024-01-01 00:00:00]]></wp:post_date>
+    <wp:post_date_gmt><![CDATA[0000-00-00 00:00:00]]></wp:post_date_gmt>
+    <wp:post_name><![CDATA[]]></wp:post_name>
+    <wp:status><![CDATA[draft]]></wp:status>
+    <wp:post_type><![CDATA[page]]></wp:post_type>
+  </item>
+  <item>
+    <title><![CDATA[Gone]]></title>
+    <wp:post_id>10</wp:post_id>
+    <wp:status><![CDATA[trash]]></wp:status>
+    <wp:post_type><![CDATA[post]]></wp:post_type>
+  </item>
+</channel>
+</rss>"#;
+
+    #[test]
+    fn export() {
+        let rss: Rss = quick_xml::de::from_str(EXPORT).unwrap();
+        let content = content_of(rss.channel);
+
+        assert!(content.problems.is_empty());
+        assert_eq!(content.authors[0].name, "jane");
+        assert_eq!(content.authors[0].display_name, "Jane Doe");
+        assert_eq!(content.attachments.len(), 1);
+        assert_eq!(content.attachments[0].owner.as_deref(), Some("jane"));
+        assert_eq!(content.posts.len(), 2);
+
+        let post = &content.posts[0];
+        assert_eq!(post.slug, "hello-world");
+        assert_eq!(post.author.as_deref(), Some("jane"));
+        assert_eq!(post.date.to_rfc3339(), "2024-01-31T09:00:00+00:00");
+        assert_eq!(post.status, PostStatus::Published);
+        assert_eq!(post.tags, ["Rust"]);
+        assert_eq!(post.categories, ["Notes"]);
+        assert_eq!(post.body_markdown, "First **post**.\n\nSecond paragraph");
+        assert!(post.comments_enabled);
+        assert_eq!(
+            post.redirects,
+            ["https://old.example.com/2024/01/31/hello-world/"]
+        );
+
+        let (parent, reply) = (&post.comments[0], &post.comments[1]);
+        assert_eq!(post.comments.len(), 2);
+        assert_eq!((parent.id.as_str(), parent.parent.as_deref()), ("11", None));
+        assert_eq!(parent.body, "Nice post\nReally");
+        assert_eq!(parent.status, CommentStatus::Pending);
+        assert_eq!(parent.author, None);
+        assert_eq!(parent.author_email, None);
+        assert_eq!(reply.parent.as_deref(), Some("11"));
+        assert_eq!(reply.author.as_deref(), Some("jane"));
+        assert_eq!(reply.status, CommentStatus::Approved);
+
+        let page = &content.posts[1];
+        assert_eq!(page.slug, "about");
+        assert_eq!(page.status, PostStatus::Draft);
+        assert_eq!(page.date.to_rfc3339(), "2024-01-01T00:00:00+00:00");
+        assert!(!page.comments_enabled);
+    }
+
+    #[test]
+    fn paragraphs() {
+        assert_eq!(
+            autop("One\nline\r\n\r\n<ul>\n<li>Item</li>\n</ul>\n\nold news"),
+            "<p>One<br />\nline</p>\n<ul>\n<li>Item</li>\n</ul>\n<p>old news</p>"
+        );
+        assert_eq!(
+            text_of("<p>Fish &amp; chips</p><p>&lt;3</p>"),
+            "Fish & chips\n\n<3"
+        );
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(
+            html_to_markdown(
+                "[caption id=\"attachment_8\"]<img src=\"/bear.jpg\" alt=\"Bear\" /> A bear[/caption]"
+            ),
+            "![Bear](/bear.jpg) A bear"
+        );
+        assert_eq!(
+            html_to_markdown(
+                "<!-- wp:paragraph -->\n<p>Hi <a href=\"/x\">there</a></p>\n<!-- /wp:paragraph -->"
+            ),
+            "Hi [there](/x)"
+        );
+        assert_eq!(
+            html_to_markdown(
+                "First\nstill first.\n\nSecond paragraph.\n\n<pre>let a = 1;\n\nlet b = 2;</pre>"
+            ),
+            "First  \nstill first.\n\nSecond paragraph.\n\n```\nlet a = 1;\n\nlet b = 2;\n```"
+        );
+        // Elements without a Markdown equivalent are kept as HTML
+        let html = "<p>Press <kbd>Ctrl</kbd></p>";
+        assert_eq!(html_to_markdown(html), html);
+    }
+}
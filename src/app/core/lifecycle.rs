@@ -0,0 +1,167 @@
+//! # Lifecycle
+//!
+//! Publication state machine of posts. A post starts as a draft, may be
+//! scheduled for a later date, gets published either right away or by the
+//! [scheduler](super::scheduler) once its date has come, and is eventually
+//! archived.
+
+use chrono::{DateTime, Utc};
+
+use crate::app::core::database::models::{Post, PostStatus};
+use crate::result::{ensure_valid, FieldError, Result};
+
+/// Publication related state of a post
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lifecycle {
+    pub status: PostStatus,
+    /// Date at which a scheduled post gets published, for scheduled posts only
+    pub publish_at: Option<DateTime<Utc>>,
+    /// Date at which the post was first published, reset by going back to draft
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Lifecycle {
+            status: PostStatus::Draft,
+            publish_at: None,
+            published_at: None,
+        }
+    }
+}
+
+impl From<&Post> for Lifecycle {
+    fn from(post: &Post) -> Self {
+        Lifecycle {
+            status: post.status,
+            publish_at: post.publish_at,
+            published_at: post.published_at,
+        }
+    }
+}
+
+impl Lifecycle {
+    /// State reached by requesting a `status` and a `publish_at` date, both
+    /// defaulting to the current ones, failing on forbidden transitions
+    pub fn next<'a>(
+        &self,
+        status: Option<PostStatus>,
+        publish_at: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> Result<'a, Lifecycle> {
+        let status = status.unwrap_or(self.status);
+        let mut errors = Vec::new();
+
+        if !self.status.can_become(status) {
+            let message = format!("cannot go from {} to {}", self.status, status);
+            errors.push(FieldError::new("status", message));
+        }
+        let publish_at = match status {
+            PostStatus::Scheduled => match publish_at.or(self.publish_at) {
+                Some(date) if date > now => Some(date),
+                _ => {
+                    errors.push(FieldError::new(
+                        "publish_at",
+                        "must be a future date to schedule a post",
+                    ));
+                    None
+                }
+            },
+            _ if publish_at.is_some() => {
+                errors.push(FieldError::new(
+                    "publish_at",
+                    "only applies to scheduled posts",
+                ));
+                None
+            }
+            _ => None,
+        };
+        ensure_valid(errors)?;
+
+        let published_at = match status {
+            PostStatus::Published | PostStatus::Archived => self.published_at.or(Some(now)),
+            PostStatus::Draft | PostStatus::Scheduled => None,
+        };
+        Ok(Lifecycle {
+            status,
+            publish_at,
+            published_at,
+        })
+    }
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::Lifecycle;
+    use crate::app::core::database::models::PostStatus::*;
+    use crate::result::Error;
+
+    fn fields(error: Error) -> Vec<&str> {
+        match error {
+            Error::Validation(errors) => errors.iter().map(|e| e.field).collect(),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn publish_right_away() {
+        let now = Utc::now();
+        let published = Lifecycle::default()
+            .next(Some(Published), None, now)
+            .unwrap();
+
+        assert_eq!(published.status, Published);
+        assert_eq!(published.published_at, Some(now));
+
+        // Archiving keeps the original publication date
+        let later = now + Duration::days(1);
+        let archived = published.next(Some(Archived), None, later).unwrap();
+        assert_eq!(archived.published_at, Some(now));
+    }
+
+    #[test]
+    fn schedule() {
+        let now = Utc::now();
+        let tomorrow = now + Duration::days(1);
+        let scheduled = Lifecycle::default()
+            .next(Some(Scheduled), Some(tomorrow), now)
+            .unwrap();
+
+        assert_eq!(scheduled.publish_at, Some(tomorrow));
+        assert_eq!(scheduled.published_at, None);
+
+        // Back to draft forgets the date
+        let draft = scheduled.next(Some(Draft), None, now).unwrap();
+        assert_eq!(draft.publish_at, None);
+    }
+
+    #[test]
+    fn schedule_in_the_past() {
+        let now = Utc::now();
+        let error = Lifecycle::default()
+            .next(Some(Scheduled), Some(now - Duration::hours(1)), now)
+            .unwrap_err();
+
+        assert_eq!(fields(error), ["publish_at"]);
+        let error = Lifecycle::default()
+            .next(Some(Scheduled), None, now)
+            .unwrap_err();
+        assert_eq!(fields(error), ["publish_at"]);
+    }
+
+    #[test]
+    fn forbidden_transitions() {
+        let now = Utc::now();
+        let error = Lifecycle::default()
+            .next(Some(Archived), Some(now), now)
+            .unwrap_err();
+
+        assert_eq!(fields(error), ["status", "publish_at"]);
+        assert!(!Published.can_become(Scheduled));
+        assert!(Archived.can_become(Published));
+    }
+}
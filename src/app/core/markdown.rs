@@ -0,0 +1,291 @@
+//! # Markdown
+//!
+//! Rendering of post bodies, written in CommonMark with the GitHub flavored
+//! tables, task lists, footnotes and strikethrough extensions, to HTML safe to
+//! embed in any page.
+//!
+//! Fenced code blocks tagged with a known language are highlighted server-side
+//! into spans bearing `hl-` prefixed classes, colored by the stylesheet of a
+//! highlighting theme as emitted by [theme_css].
+
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+use ammonia::Builder;
+use pulldown_cmark::{html, CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use syntect::highlighting::ThemeSet;
+use syntect::html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+use crate::result::RenderError;
+
+/// Classes of highlighted code, shared by the rendered posts and stylesheets
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+static THEMES: LazyLock<ThemeSet> = LazyLock::new(ThemeSet::load_defaults);
+
+/// Prefix of the footnote ids, kept apart from those of the embedding page
+const ID_PREFIX: &str = "fn-";
+
+/// Sanitizer stripping scripts, event handlers, dangerous URLs and anything
+/// not produced by the Markdown renderer
+static SANITIZER: LazyLock<Builder<'static>> = LazyLock::new(|| {
+    let mut builder = Builder::default();
+    builder
+        .add_tags(["input"])
+        .add_tag_attributes("input", ["type", "checked", "disabled"])
+        .add_tag_attributes("th", ["style"])
+        .add_tag_attributes("td", ["style"])
+        .add_tag_attributes("div", ["class", "id"])
+        .add_tag_attributes("sup", ["class"])
+        .add_tag_attributes("code", ["class"])
+        .add_tag_attributes("pre", ["class"])
+        .add_tag_attributes("span", ["class"])
+        .id_prefix(Some(ID_PREFIX))
+        .attribute_filter(|element, attribute, value| match (element, attribute) {
+            ("a", "href") if value.starts_with('#') => {
+                Some(Cow::Owned(format!("#{}{}", ID_PREFIX, &value[1..])))
+            }
+            ("div", "class") if value == "footnote-definition" => Some(Cow::Borrowed(value)),
+            ("sup", "class") if value == "footnote-reference" => Some(Cow::Borrowed(value)),
+            ("pre", "class") if value == "hl-code" => Some(Cow::Borrowed(value)),
+            ("code", "class") if is_language_class(value) => Some(Cow::Borrowed(value)),
+            ("span", "class") if value.split(' ').all(|class| class.starts_with("hl-")) => {
+                Some(Cow::Borrowed(value))
+            }
+            (_, "class") => None,
+            ("input", "type") if value == "checkbox" => Some(Cow::Borrowed(value)),
+            ("input", "type") => None,
+            ("th" | "td", "style") => match value {
+                "text-align: left" | "text-align: center" | "text-align: right" => {
+                    Some(Cow::Borrowed(value))
+                }
+                _ => None,
+            },
+            _ => Some(Cow::Borrowed(value)),
+        });
+    builder
+});
+
+/// Class of a code block as named by the renderer after its language
+fn is_language_class(class: &str) -> bool {
+    class
+        .strip_prefix("language-")
+        .is_some_and(|language| !language.is_empty() && !language.contains(' '))
+}
+
+fn options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+}
+
+/// Highlight a snippet, unless its language is unknown
+fn highlight(language: &str, code: &str) -> Option<String> {
+    let syntax = SYNTAXES.find_syntax_by_token(language)?;
+    let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAXES, CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        generator
+            .parse_html_for_line_which_includes_newline(line)
+            .ok()?;
+    }
+    Some(format!(
+        "<pre class=\"hl-code\"><code class=\"language-{}\">{}</code></pre>\n",
+        language,
+        generator.finalize()
+    ))
+}
+
+/// Replace the fenced code blocks of a document by their highlighted HTML,
+/// leaving the blocks of unknown languages untouched
+fn highlight_code_blocks<'a>(parser: Parser<'a>) -> Vec<Event<'a>> {
+    let mut events = Vec::new();
+    let mut block = None;
+
+    for event in parser {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))
+                if !info.trim().is_empty() =>
+            {
+                block = Some((info, String::new()));
+            }
+            Event::Text(text) if block.is_some() => {
+                if let Some((_, code)) = block.as_mut() {
+                    code.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) if block.is_some() => {
+                let (info, code) = block.take().unwrap();
+                let language = info.split_whitespace().next().unwrap_or_default();
+                match highlight(language, &code) {
+                    Some(html) => events.push(Event::Html(html.into())),
+                    None => events.extend([
+                        Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))),
+                        Event::Text(code.into()),
+                        Event::End(TagEnd::CodeBlock),
+                    ]),
+                }
+            }
+            event => events.push(event),
+        }
+    }
+    events
+}
+
+/// Render a Markdown document to sanitized HTML
+pub fn render(markdown: &str) -> String {
+    let events = highlight_code_blocks(Parser::new_ext(markdown, options()));
+    let mut unsafe_html = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, events.into_iter());
+    SANITIZER.clean(&unsafe_html).to_string()
+}
+
+/// Plain text opening of a Markdown document of at most `length` characters,
+/// cut between words and ending with an ellipsis if so
+pub fn summary(markdown: &str, length: usize) -> String {
+    let mut text = String::new();
+    for event in Parser::new_ext(markdown, options()) {
+        match event {
+            Event::Text(chunk) | Event::Code(chunk) => text.push_str(&chunk),
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => text.push(' '),
+            _ => {}
+        }
+        if text.len() > length * 4 {
+            break;
+        }
+    }
+
+    let mut summary = String::new();
+    for word in text.split_whitespace() {
+        let separator = usize::from(!summary.is_empty());
+        if summary.chars().count() + separator + word.chars().count() > length {
+            summary.push('…');
+            break;
+        }
+        if separator == 1 {
+            summary.push(' ');
+        }
+        summary.push_str(word);
+    }
+    summary
+}
+
+/// Names of the available highlighting themes
+pub fn themes() -> impl Iterator<Item = &'static str> {
+    THEMES.themes.keys().map(String::as_str)
+}
+
+/// Stylesheet coloring the highlighted code blocks after the given theme
+pub fn theme_css(name: &str) -> Result<String, RenderError> {
+    let theme = THEMES
+        .themes
+        .get(name)
+        .ok_or_else(|| RenderError::UnknownTheme(name.to_string()))?;
+    Ok(css_for_theme_with_class_style(theme, CLASS_STYLE)?)
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::{render, summary, theme_css};
+    use crate::result::RenderError;
+
+    #[test]
+    fn commonmark() {
+        assert_eq!(
+            render("# Title\n\nSome *emphasis*."),
+            "<h1>Title</h1>\n<p>Some <em>emphasis</em>.</p>\n"
+        );
+    }
+
+    #[test]
+    fn gfm_extensions() {
+        let html = render("| a | b |\n|:-:|---|\n| 1 | 2 |\n\n- [x] done\n- [ ] todo\n\n~~gone~~");
+
+        assert!(html.contains(r#"<th style="text-align: center">a</th>"#));
+        assert!(html.contains(r#"<input disabled="" type="checkbox" checked="">"#));
+        assert!(html.contains("<del>gone</del>"));
+    }
+
+    #[test]
+    fn footnotes() {
+        let html = render("Text[^note]\n\n[^note]: The note");
+
+        assert!(html.contains(r##"<sup class="footnote-reference"><a href="#fn-note""##));
+        assert!(html.contains(r#"<div class="footnote-definition" id="fn-note">"#));
+    }
+
+    #[test]
+    fn forged_classes_are_stripped() {
+        let html = render(
+            "<div class=\"modal\" id=\"login\">x</div>\n\n<span class=\"hl-x admin\">y</span>",
+        );
+
+        assert!(!html.contains("modal"));
+        assert!(!html.contains("admin"));
+        assert!(html.contains(r#"id="fn-login""#));
+    }
+
+    #[test]
+    fn scripts_are_stripped() {
+        let html = render(
+            "<script>alert(1)</script>\n\n<img src=x onerror=alert(1)>\n\n[link](javascript:alert(1))",
+        );
+
+        assert!(!html.contains("script"));
+        assert!(!html.contains("onerror"));
+        assert!(!html.contains("javascript:"));
+    }
+
+    #[test]
+    fn forged_attributes_are_stripped() {
+        let html = render(
+            "<input type=\"text\" value=\"x\">\n\n<table><tr><td style=\"background: url(x)\">1</td></tr></table>",
+        );
+
+        assert!(!html.contains("type=\"text\""));
+        assert!(!html.contains("background"));
+    }
+
+    #[test]
+    fn code_highlighting() {
+        let html = render("```rust\nlet x = \"<a>\";\n```");
+
+        assert!(html.starts_with(r#"<pre class="hl-code"><code class="language-rust">"#));
+        assert!(html.contains(r#"<span class="hl-storage hl-type hl-rust">let</span>"#));
+        assert!(html.contains("&lt;a&gt;"));
+    }
+
+    #[test]
+    fn code_unknown_language() {
+        assert_eq!(
+            render("```nope\n<b>x</b>\n```"),
+            "<pre><code class=\"language-nope\">&lt;b&gt;x&lt;/b&gt;\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn summaries() {
+        let markdown = "# Title\n\nSome *emphasis* and `code`.\n\n- an item";
+
+        assert_eq!(
+            summary(markdown, 100),
+            "Title Some emphasis and code. an item"
+        );
+        assert_eq!(summary(markdown, 22), "Title Some emphasis…");
+        assert_eq!(summary("", 10), "");
+    }
+
+    #[test]
+    fn theme_stylesheet() {
+        assert!(theme_css("InspiredGitHub").unwrap().contains(".hl-code {"));
+        assert!(matches!(
+            theme_css("Nope"),
+            Err(RenderError::UnknownTheme(name)) if name == "Nope"
+        ));
+    }
+}
@@ -0,0 +1,565 @@
+//! # Metadata
+//!
+//! Uploaded images are stripped of their metadata before being stored, phones
+//! recording among others where their photos were taken. EXIF, XMP and IPTC
+//! metadata, text chunks and comments are removed from JPEG, PNG, WebP and GIF
+//! images without decoding them, leaving their quality untouched. The EXIF
+//! tags of the primary image named in the `media.keep_metadata` list, such as
+//! `Orientation` or `Copyright`, are written back while embedded thumbnails
+//! never are.
+//!
+//! Images are also previewed: their dimensions as displayed are recorded along
+//! with a [BlurHash](https://blurha.sh) and their average color, for them to
+//! be laid out and hinted at while they load.
+
+use std::io::Cursor;
+
+use exif::experimental::Writer;
+use exif::{Field, In};
+use image::error::{DecodingError, ImageFormatHint};
+use image::{GenericImageView, ImageError, ImageFormat};
+
+use crate::app::core::rendition;
+
+/// Largest side of the thumbnail BlurHashes and colors are computed from
+const THUMBNAIL_SIZE: u32 = 32;
+
+/// Image without its metadata, of the same format, or the file itself if it is
+/// not an image of a known format
+pub fn strip(data: &[u8], mime_type: &str, keep: &[String]) -> Result<Vec<u8>, ImageError> {
+    let (format, stripped) = match mime_type {
+        "image/jpeg" => (ImageFormat::Jpeg, strip_jpeg(data, keep)),
+        "image/png" => (ImageFormat::Png, strip_png(data, keep)),
+        "image/webp" => (ImageFormat::WebP, strip_webp(data, keep)),
+        "image/gif" => (ImageFormat::Gif, strip_gif(data)),
+        _ => return Ok(data.to_vec()),
+    };
+    stripped.ok_or_else(|| {
+        ImageError::Decoding(DecodingError::new(
+            ImageFormatHint::from(format),
+            "Malformed image",
+        ))
+    })
+}
+
+/// TIFF structure holding the fields of an EXIF one named in `keep`, if any
+fn kept_exif(tiff: &[u8], keep: &[String]) -> Option<Vec<u8>> {
+    let exif = exif::Reader::new().read_raw(tiff.to_vec()).ok()?;
+    let fields: Vec<&Field> = exif
+        .fields()
+        .filter(|field| field.ifd_num == In::PRIMARY)
+        .filter(|field| keep.contains(&field.tag.to_string()))
+        .collect();
+    if fields.is_empty() {
+        return None;
+    }
+
+    let mut writer = Writer::new();
+    for field in fields {
+        writer.push_field(field);
+    }
+    let mut kept = Cursor::new(Vec::new());
+    writer.write(&mut kept, exif.little_endian()).ok()?;
+    Some(kept.into_inner())
+}
+
+/* -------------------------------------------- JPEG ------------------------------------------- */
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+/// Length of the entropy coded data of a scan, up to the marker ending it.
+/// Its `0xFF` bytes are followed by a stuffed zero, or are restart markers.
+fn scan_length(data: &[u8]) -> usize {
+    let mut length = 0;
+    while let Some(offset) = data[length..].iter().position(|byte| *byte == 0xFF) {
+        length += offset;
+        match data.get(length + 1) {
+            Some(0x00 | 0xD0..=0xD7) => length += 2,
+            _ => return length,
+        }
+    }
+    data.len()
+}
+
+/// JPEG segments of the first image, dropping APP1 (EXIF, XMP), APP2 (MPF),
+/// APP13 (IPTC) and comment segments. Anything after its end, such as the
+/// secondary images of a Multi-Picture file and their own EXIF, is dropped.
+fn strip_jpeg(data: &[u8], keep: &[String]) -> Option<Vec<u8>> {
+    let mut rest = data.strip_prefix(&[0xFF, 0xD8])?;
+    let mut segments: Vec<&[u8]> = Vec::new();
+    let mut exif = None;
+    loop {
+        // Markers may be preceded by any number of fill bytes
+        let fill = rest.iter().take_while(|byte| **byte == 0xFF).count();
+        let marker = *rest.get(fill).filter(|_| fill > 0)?;
+        let segment = &rest[fill - 1..];
+        match marker {
+            // End of image
+            0xD9 => {
+                segments.push(&segment[..2]);
+                break;
+            }
+            0x01 | 0xD0..=0xD7 => {
+                segments.push(&segment[..2]);
+                rest = &segment[2..];
+            }
+            _ => {
+                let length = u16::from_be_bytes([*segment.get(2)?, *segment.get(3)?]) as usize;
+                let remainder = segment.get(2 + length..)?;
+                if length < 2 {
+                    return None;
+                }
+                rest = remainder;
+                match marker {
+                    // Start of scan, followed by its image data
+                    0xDA => {
+                        let scan = scan_length(remainder);
+                        segments.push(&segment[..2 + length + scan]);
+                        rest = &remainder[scan..];
+                    }
+                    0xE1 => {
+                        if let Some(tiff) = segment[4..2 + length].strip_prefix(EXIF_HEADER) {
+                            exif = exif.or_else(|| kept_exif(tiff, keep));
+                        }
+                    }
+                    0xE2 if segment[4..2 + length].starts_with(b"MPF\0") => {}
+                    0xED | 0xFE => {}
+                    _ => segments.push(&segment[..2 + length]),
+                }
+            }
+        }
+    }
+
+    let mut stripped = Vec::with_capacity(data.len());
+    stripped.extend_from_slice(&[0xFF, 0xD8]);
+    // EXIF follows the JFIF segments, if any
+    let jfif = segments.iter().take_while(|s| s[1] == 0xE0).count();
+    for segment in &segments[..jfif] {
+        stripped.extend_from_slice(segment);
+    }
+    if let Some(tiff) = exif.filter(|tiff| tiff.len() + 8 <= u16::MAX as usize) {
+        stripped.extend_from_slice(&[0xFF, 0xE1]);
+        stripped.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+        stripped.extend_from_slice(EXIF_HEADER);
+        stripped.extend_from_slice(&tiff);
+    }
+    for segment in &segments[jfif..] {
+        stripped.extend_from_slice(segment);
+    }
+    Some(stripped)
+}
+
+/* -------------------------------------------- PNG -------------------------------------------- */
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+fn png_chunk(stripped: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+    stripped.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = stripped.len();
+    stripped.extend_from_slice(kind);
+    stripped.extend_from_slice(data);
+    let crc = crc32fast::hash(&stripped[start..]);
+    stripped.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// PNG chunks, dropping the EXIF, text and modification time ones
+fn strip_png(data: &[u8], keep: &[String]) -> Option<Vec<u8>> {
+    let mut rest = data.strip_prefix(PNG_SIGNATURE)?;
+    let mut chunks: Vec<&[u8]> = Vec::new();
+    let mut exif = None;
+    while chunks.last().is_none_or(|chunk| &chunk[4..8] != b"IEND") {
+        let length = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        let chunk = rest.get(..length.checked_add(12)?)?;
+        rest = &rest[length + 12..];
+        match &chunk[4..8] {
+            b"eXIf" => exif = exif.or_else(|| kept_exif(&chunk[8..8 + length], keep)),
+            b"tEXt" | b"zTXt" | b"iTXt" | b"tIME" => {}
+            _ => chunks.push(chunk),
+        }
+    }
+
+    let mut stripped = Vec::with_capacity(data.len());
+    stripped.extend_from_slice(PNG_SIGNATURE);
+    for chunk in chunks {
+        // EXIF precedes the image data
+        if &chunk[4..8] == b"IDAT" {
+            if let Some(tiff) = exif.take() {
+                png_chunk(&mut stripped, b"eXIf", &tiff);
+            }
+        }
+        stripped.extend_from_slice(chunk);
+    }
+    Some(stripped)
+}
+
+/* -------------------------------------------- WebP ------------------------------------------- */
+
+/// Flags of the extended format chunk telling EXIF and XMP metadata are present
+const VP8X_EXIF: u8 = 0x08;
+const VP8X_XMP: u8 = 0x04;
+
+/// WebP chunks, dropping the EXIF and XMP ones. EXIF may only be written back
+/// to images of the extended format
+fn strip_webp(data: &[u8], keep: &[String]) -> Option<Vec<u8>> {
+    if data.get(..4)? != b"RIFF" || data.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    let size = u32::from_le_bytes(data[4..8].try_into().ok()?) as usize;
+    let mut rest = data.get(12..size.checked_add(8)?)?;
+    let mut chunks = Vec::new();
+    let mut exif = None;
+    while !rest.is_empty() {
+        let length = u32::from_le_bytes(rest.get(4..8)?.try_into().ok()?) as usize;
+        let padded = length.checked_add(8 + length % 2)?;
+        let chunk = rest.get(..padded)?;
+        rest = &rest[padded..];
+        match &chunk[..4] {
+            b"EXIF" => {
+                let tiff = &chunk[8..8 + length];
+                let tiff = tiff.strip_prefix(EXIF_HEADER).unwrap_or(tiff);
+                exif = exif.or_else(|| kept_exif(tiff, keep));
+            }
+            b"XMP " => {}
+            _ => chunks.push(chunk.to_vec()),
+        }
+    }
+
+    let extended = chunks.iter_mut().find(|chunk| chunk.starts_with(b"VP8X"));
+    let exif = match extended.and_then(|chunk| chunk.get_mut(8)) {
+        Some(flags) => {
+            *flags &= !(VP8X_EXIF | VP8X_XMP);
+            if exif.is_some() {
+                *flags |= VP8X_EXIF;
+            }
+            exif
+        }
+        None => None,
+    };
+    if let Some(tiff) = exif {
+        let mut chunk = b"EXIF".to_vec();
+        chunk.extend_from_slice(&(tiff.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(&tiff);
+        if tiff.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunks.push(chunk);
+    }
+
+    let body = chunks.concat();
+    let mut stripped = Vec::with_capacity(body.len() + 12);
+    stripped.extend_from_slice(b"RIFF");
+    stripped.extend_from_slice(&(body.len() as u32 + 4).to_le_bytes());
+    stripped.extend_from_slice(b"WEBP");
+    stripped.extend_from_slice(&body);
+    Some(stripped)
+}
+
+/* -------------------------------------------- GIF -------------------------------------------- */
+
+/// Length of a sequence of data sub-blocks, terminator included
+fn sub_blocks(data: &[u8]) -> Option<usize> {
+    let mut length = 0;
+    loop {
+        let size = *data.get(length)? as usize;
+        length += size + 1;
+        if size == 0 {
+            return Some(length);
+        }
+    }
+}
+
+/// Length of the color table a packed field tells of
+fn color_table(packed: u8) -> usize {
+    match packed & 0x80 {
+        0 => 0,
+        _ => 3 << ((packed & 0x07) + 1),
+    }
+}
+
+/// GIF blocks, dropping comments and XMP
+fn strip_gif(data: &[u8]) -> Option<Vec<u8>> {
+    if !data.starts_with(b"GIF87a") && !data.starts_with(b"GIF89a") {
+        return None;
+    }
+    let header = 13 + color_table(*data.get(10)?);
+    let mut stripped = data.get(..header)?.to_vec();
+    let mut rest = &data[header..];
+    loop {
+        let length = match *rest.first()? {
+            // Trailer
+            0x3B => {
+                stripped.push(0x3B);
+                return Some(stripped);
+            }
+            // Image descriptor, color table and image data
+            0x2C => {
+                let table = color_table(*rest.get(9)?);
+                11 + table + sub_blocks(rest.get(11 + table..)?)?
+            }
+            // Extension, dropped if a comment or XMP
+            0x21 => {
+                let length = 2 + sub_blocks(rest.get(2..)?)?;
+                let label = rest[1];
+                let application = rest.get(3..14);
+                if label == 0xFE || (label == 0xFF && application == Some(b"XMP DataXMP")) {
+                    rest = &rest[length..];
+                    continue;
+                }
+                length
+            }
+            _ => return None,
+        };
+        stripped.extend_from_slice(rest.get(..length)?);
+        rest = &rest[length..];
+    }
+}
+
+/* ------------------------------------------ Previews ----------------------------------------- */
+
+/// What is shown of an image while it loads
+#[derive(Debug, PartialEq, Eq)]
+pub struct Preview {
+    /// Width in pixels, as displayed
+    pub width: u32,
+    /// Height in pixels, as displayed
+    pub height: u32,
+    pub blurhash: String,
+    /// Average color, as `#rrggbb`
+    pub color: String,
+}
+
+/// Preview of an image, turned upright as its EXIF orientation says
+pub fn preview(data: &[u8]) -> Result<Preview, ImageError> {
+    let image = rendition::decode(data)?;
+    let (width, height) = image.dimensions();
+    let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgba8();
+
+    let (x_components, y_components) = match width >= height {
+        true => (4, 3),
+        false => (3, 4),
+    };
+    let blurhash = blurhash::encode(
+        x_components,
+        y_components,
+        thumbnail.width(),
+        thumbnail.height(),
+        thumbnail.as_raw(),
+    )
+    .expect("BlurHashes have 1 to 9 components");
+
+    let pixels = thumbnail.pixels().len() as u64;
+    let mut sums = [0u64; 3];
+    for pixel in thumbnail.pixels() {
+        for (sum, channel) in sums.iter_mut().zip(pixel.0) {
+            *sum += channel as u64;
+        }
+    }
+    let [red, green, blue] = sums.map(|sum| sum / pixels);
+    Ok(Preview {
+        width,
+        height,
+        blurhash,
+        color: format!("#{:02x}{:02x}{:02x}", red, green, blue),
+    })
+}
+
+/// Image stripped of its metadata, along with its preview
+pub fn process(
+    data: &[u8],
+    mime_type: &str,
+    keep: &[String],
+) -> Result<(Vec<u8>, Preview), ImageError> {
+    let stripped = strip(data, mime_type, keep)?;
+    let preview = preview(&stripped)?;
+    Ok((stripped, preview))
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use exif::experimental::Writer;
+    use exif::{Field, In, Reader, Tag, Value};
+    use image::codecs::webp::WebPEncoder;
+    use image::{DynamicImage, ExtendedColorType, ImageEncoder, ImageFormat, RgbImage};
+
+    use super::{preview, strip};
+
+    /// EXIF of a phone photo, with its orientation, copyright and location
+    fn exif() -> Vec<u8> {
+        let fields = [
+            Field {
+                tag: Tag::Orientation,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![6]),
+            },
+            Field {
+                tag: Tag::Copyright,
+                ifd_num: In::PRIMARY,
+                value: Value::Ascii(vec![b"John".to_vec()]),
+            },
+            Field {
+                tag: Tag::GPSLatitudeRef,
+                ifd_num: In::PRIMARY,
+                value: Value::Ascii(vec![b"N".to_vec()]),
+            },
+        ];
+        let mut writer = Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut tiff = Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        tiff.into_inner()
+    }
+
+    fn image(format: ImageFormat) -> Vec<u8> {
+        let image = RgbImage::from_fn(8, 4, |x, _| image::Rgb([x as u8 * 32, 64, 128]));
+        let mut data = Vec::new();
+        DynamicImage::ImageRgb8(image)
+            .write_to(&mut Cursor::new(&mut data), format)
+            .unwrap();
+        data
+    }
+
+    fn tags(stripped: &[u8]) -> Vec<Tag> {
+        match Reader::new().read_from_container(&mut Cursor::new(stripped)) {
+            Ok(exif) => exif.fields().map(|field| field.tag).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn contains(data: &[u8], needle: &[u8]) -> bool {
+        data.windows(needle.len()).any(|window| window == needle)
+    }
+
+    #[test]
+    fn jpegs() {
+        let original = image(ImageFormat::Jpeg);
+        let exif = exif();
+        let xmp = b"http://ns.adobe.com/xap/1.0/\0<x:xmpmeta/>";
+        let mut data = original[..2].to_vec();
+        data.extend_from_slice(&[0xFF, 0xE1]);
+        data.extend_from_slice(&(exif.len() as u16 + 8).to_be_bytes());
+        data.extend_from_slice(b"Exif\0\0");
+        data.extend_from_slice(&exif);
+        data.extend_from_slice(&[0xFF, 0xE1, 0, xmp.len() as u8 + 2]);
+        data.extend_from_slice(xmp);
+        data.extend_from_slice(&[0xFF, 0xFE, 0, 7, b'P', b'a', b'r', b'i', b's']);
+        data.extend_from_slice(&original[2..]);
+        let keep = ["Orientation".to_string(), "Copyright".to_string()];
+
+        let stripped = strip(&data, "image/jpeg", &keep).unwrap();
+        assert_eq!(tags(&stripped), [Tag::Orientation, Tag::Copyright]);
+        assert!(!contains(&stripped, b"xmpmeta") && !contains(&stripped, b"Paris"));
+        assert_eq!(
+            image::load_from_memory(&stripped).unwrap(),
+            image::load_from_memory(&original).unwrap()
+        );
+
+        assert_eq!(strip(&data, "image/jpeg", &[]).unwrap(), original);
+        assert!(strip(&data[..40], "image/jpeg", &keep).is_err());
+    }
+
+    #[test]
+    fn jpeg_secondary_images() {
+        let original = image(ImageFormat::Jpeg);
+        let exif = exif();
+        let mpf = b"MPF\0II*\0";
+        let mut data = original[..2].to_vec();
+        data.extend_from_slice(&[0xFF, 0xE2, 0, mpf.len() as u8 + 2]);
+        data.extend_from_slice(mpf);
+        data.extend_from_slice(&original[2..]);
+        // Secondary image of a Multi-Picture file, with its own EXIF
+        data.extend_from_slice(&original[..2]);
+        data.extend_from_slice(&[0xFF, 0xE1]);
+        data.extend_from_slice(&(exif.len() as u16 + 8).to_be_bytes());
+        data.extend_from_slice(b"Exif\0\0");
+        data.extend_from_slice(&exif);
+        data.extend_from_slice(&original[2..]);
+
+        let stripped = strip(&data, "image/jpeg", &[]).unwrap();
+        assert_eq!(stripped, original);
+        assert!(!contains(&stripped, b"Exif") && !contains(&stripped, b"MPF"));
+    }
+
+    #[test]
+    fn pngs() {
+        let original = image(ImageFormat::Png);
+        let mut data = original[..33].to_vec();
+        super::png_chunk(&mut data, b"eXIf", &exif());
+        super::png_chunk(&mut data, b"tEXt", b"Location\0Paris");
+        data.extend_from_slice(&original[33..]);
+
+        let stripped = strip(&data, "image/png", &["Copyright".to_string()]).unwrap();
+        assert_eq!(tags(&stripped), [Tag::Copyright]);
+        assert!(!contains(&stripped, b"Paris"));
+        image::load_from_memory(&stripped).unwrap();
+
+        assert_eq!(strip(&data, "image/png", &[]).unwrap(), original);
+        assert!(strip(&data[..40], "image/png", &[]).is_err());
+    }
+
+    #[test]
+    fn webps() {
+        let original = image(ImageFormat::WebP);
+        let mut data = original.clone();
+        data.extend_from_slice(b"XMP \x06\0\0\0Paris!");
+        let size = data.len() as u32 - 8;
+        data[4..8].copy_from_slice(&size.to_le_bytes());
+
+        assert_eq!(strip(&data, "image/webp", &[]).unwrap(), original);
+        assert!(strip(&data[..20], "image/webp", &[]).is_err());
+
+        // EXIF makes for the extended format, whose flags must be kept in line
+        let mut extended = Vec::new();
+        let mut encoder = WebPEncoder::new_lossless(&mut extended);
+        encoder.set_exif_metadata(exif()).unwrap();
+        encoder
+            .write_image(&[255; 12], 2, 2, ExtendedColorType::Rgb8)
+            .unwrap();
+
+        let stripped = strip(&extended, "image/webp", &["Orientation".to_string()]).unwrap();
+        assert_eq!(tags(&stripped), [Tag::Orientation]);
+        assert_eq!(stripped[20] & 0x0C, 0x08);
+        image::load_from_memory(&stripped).unwrap();
+
+        let stripped = strip(&extended, "image/webp", &[]).unwrap();
+        assert_eq!(tags(&stripped), []);
+        assert_eq!(stripped[20] & 0x0C, 0);
+        image::load_from_memory(&stripped).unwrap();
+    }
+
+    #[test]
+    fn gifs() {
+        let original = image(ImageFormat::Gif);
+        let header = 13 + super::color_table(original[10]);
+        let mut data = original[..header].to_vec();
+        data.extend_from_slice(b"\x21\xFE\x05Paris\0");
+        data.extend_from_slice(b"\x21\xFF\x0BXMP DataXMP\x05<x:x>\0");
+        data.extend_from_slice(&original[header..]);
+
+        assert_eq!(strip(&data, "image/gif", &[]).unwrap(), original);
+        assert!(strip(&data[..header + 4], "image/gif", &[]).is_err());
+        assert_eq!(
+            strip(b"%PDF-1.7", "application/pdf", &[]).unwrap(),
+            b"%PDF-1.7"
+        );
+    }
+
+    #[test]
+    fn previews() {
+        let plain = preview(&image(ImageFormat::Png)).unwrap();
+        assert_eq!((plain.width, plain.height), (8, 4));
+        assert_eq!(plain.blurhash.len(), 28);
+        assert_eq!(plain.color, "#794080");
+
+        let mut rotated = image(ImageFormat::Png)[..33].to_vec();
+        super::png_chunk(&mut rotated, b"eXIf", &exif());
+        rotated.extend_from_slice(&image(ImageFormat::Png)[33..]);
+        let rotated = preview(&rotated).unwrap();
+        assert_eq!((rotated.width, rotated.height), (4, 8));
+    }
+}
@@ -1 +1,16 @@
+pub mod comments;
 pub mod database;
+pub mod feed;
+pub mod import;
+pub mod lifecycle;
+pub mod markdown;
+pub mod metadata;
+pub mod rendition;
+pub mod revision;
+pub mod scheduler;
+pub mod search;
+pub mod sitemap;
+pub mod spam;
+pub mod storage;
+pub mod theme;
+pub mod validation;
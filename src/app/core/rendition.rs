@@ -0,0 +1,303 @@
+//! # Renditions
+//!
+//! Resized, cropped or converted copies of uploaded images, requested as
+//! `/media/<hash>?w=800&h=600&fmt=webp`. A rendition is generated on its first
+//! request then kept by the storage next to the original. Only the configured
+//! sizes may be requested, so that renditions cannot be generated endlessly.
+//!
+//! Images are turned upright as their EXIF orientation says, and never
+//! enlarged unless cropped to both a width and a height. AVIF encoding, slow
+//! and heavy to build, requires the `avif` feature.
+
+use std::io::Cursor;
+use std::str::FromStr;
+
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageError, ImageReader};
+
+/// Format renditions are encoded in
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Webp,
+    #[cfg(feature = "avif")]
+    Avif,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+            ImageFormat::Webp => "webp",
+            #[cfg(feature = "avif")]
+            ImageFormat::Avif => "avif",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Png => "image/png",
+            ImageFormat::Webp => "image/webp",
+            #[cfg(feature = "avif")]
+            ImageFormat::Avif => "image/avif",
+        }
+    }
+
+    /// Format renditions of an image of the given MIME type are encoded in
+    /// unless requested otherwise, if it is an image renditions can be made of,
+    /// AVIF images being encoded but not decoded
+    pub fn of_original(mime_type: &str) -> Option<ImageFormat> {
+        match mime_type {
+            "image/jpeg" => Some(ImageFormat::Jpeg),
+            "image/png" | "image/gif" => Some(ImageFormat::Png),
+            "image/webp" => Some(ImageFormat::Webp),
+            _ => None,
+        }
+    }
+
+    fn encoding(&self) -> image::ImageFormat {
+        match self {
+            ImageFormat::Jpeg => image::ImageFormat::Jpeg,
+            ImageFormat::Png => image::ImageFormat::Png,
+            ImageFormat::Webp => image::ImageFormat::WebP,
+            #[cfg(feature = "avif")]
+            ImageFormat::Avif => image::ImageFormat::Avif,
+        }
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "jpeg" | "jpg" => Ok(ImageFormat::Jpeg),
+            "png" => Ok(ImageFormat::Png),
+            "webp" => Ok(ImageFormat::Webp),
+            #[cfg(feature = "avif")]
+            "avif" => Ok(ImageFormat::Avif),
+            other => Err(format!("Unknown image format {}", other)),
+        }
+    }
+}
+
+/// Derived copy of an image, fitting the given width or height, or cropped to
+/// both
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Rendition {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub format: ImageFormat,
+}
+
+impl Rendition {
+    /// Whether the rendition is of the allowed sizes
+    pub fn is_allowed(&self, sizes: &[u32]) -> bool {
+        [self.width, self.height]
+            .iter()
+            .flatten()
+            .all(|size| sizes.contains(size))
+    }
+
+    /// Storage key of the rendition of the media of the given hash
+    pub fn key(&self, hash: &str) -> String {
+        format!(
+            "renditions/{}/{}x{}.{}",
+            hash,
+            self.width.unwrap_or(0),
+            self.height.unwrap_or(0),
+            self.format.extension()
+        )
+    }
+
+    /// Render the rendition of the given encoded image
+    pub fn render(&self, data: &[u8]) -> Result<Vec<u8>, ImageError> {
+        let image = decode(data)?;
+        let image = match (self.width, self.height) {
+            (Some(width), Some(height)) => {
+                image.resize_to_fill(width, height, FilterType::Lanczos3)
+            }
+            (Some(width), None) if width < image.width() => {
+                image.resize(width, u32::MAX, FilterType::Lanczos3)
+            }
+            (None, Some(height)) if height < image.height() => {
+                image.resize(u32::MAX, height, FilterType::Lanczos3)
+            }
+            _ => image,
+        };
+        // Encoders only take 8-bit pixels, and JPEG no transparency
+        let image = match self.format {
+            ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8()),
+            _ if image.color().has_alpha() => DynamicImage::ImageRgba8(image.to_rgba8()),
+            _ => DynamicImage::ImageRgb8(image.to_rgb8()),
+        };
+
+        let mut encoded = Vec::new();
+        image.write_to(&mut Cursor::new(&mut encoded), self.format.encoding())?;
+        Ok(encoded)
+    }
+}
+
+/// Decode an image, turned upright as its EXIF orientation says
+pub fn decode(data: &[u8]) -> Result<DynamicImage, ImageError> {
+    let mut decoder = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()?
+        .into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/* ------------------------------------------ Srcsets ------------------------------------------ */
+
+/// Width images take in the default theme, as the `sizes` of their `srcset`
+const SIZES: &str = "(max-width: 46rem) 100vw, 46rem";
+
+/// `srcset` of an uploaded image served from `src`, in every given width
+pub fn srcset(src: &str, widths: &[u32]) -> String {
+    let mut widths = widths.to_vec();
+    widths.sort_unstable();
+    widths
+        .iter()
+        .map(|width| format!("{}?w={} {}w", src, width, width))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Give the uploaded images of a sanitized HTML document a `srcset` in every
+/// given width, leaving other images alone
+pub fn responsive_images(html: &str, widths: &[u32]) -> String {
+    let mut responsive = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find("<img ") {
+        let Some(length) = rest[start..].find('>') else {
+            break;
+        };
+        let (before, tag) = (&rest[..start], &rest[start..start + length]);
+        rest = &rest[start + length..];
+        responsive.push_str(before);
+
+        let src = tag
+            .split_once(" src=\"")
+            .and_then(|(_, value)| value.split_once('"'))
+            .map(|(src, _)| src)
+            .filter(|src| src.starts_with("/media/") && !src.contains(['?', '&']));
+        let tag = tag.trim_end_matches('/').trim_end();
+        match src {
+            Some(src) if !tag.contains(" srcset=") && !widths.is_empty() => {
+                responsive.push_str(&format!(
+                    "{} srcset=\"{}\" sizes=\"{}\"",
+                    tag,
+                    srcset(src, widths),
+                    SIZES
+                ));
+            }
+            _ => responsive.push_str(tag),
+        }
+    }
+    responsive.push_str(rest);
+    responsive
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{DynamicImage, GenericImageView, RgbaImage};
+
+    use super::{responsive_images, srcset, ImageFormat, Rendition};
+    use crate::app::core::markdown;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba([(x * 4) as u8, (y * 4) as u8, 128, 255])
+        });
+        let mut data = Vec::new();
+        DynamicImage::ImageRgba8(image)
+            .write_to(&mut Cursor::new(&mut data), image::ImageFormat::Png)
+            .unwrap();
+        data
+    }
+
+    fn rendition(width: Option<u32>, height: Option<u32>, format: ImageFormat) -> Rendition {
+        Rendition {
+            width,
+            height,
+            format,
+        }
+    }
+
+    #[test]
+    fn allowed_sizes() {
+        let sizes = [320, 640];
+        let webp = |width, height| rendition(width, height, ImageFormat::Webp);
+
+        assert!(webp(Some(320), None).is_allowed(&sizes));
+        assert!(webp(Some(640), Some(320)).is_allowed(&sizes));
+        assert!(webp(None, None).is_allowed(&sizes));
+        assert!(!webp(Some(641), None).is_allowed(&sizes));
+        assert!(!webp(Some(320), Some(1)).is_allowed(&sizes));
+        assert_eq!(
+            webp(Some(320), None).key("3f2a"),
+            "renditions/3f2a/320x0.webp"
+        );
+        assert_eq!("jpg".parse(), Ok(ImageFormat::Jpeg));
+        assert!("bmp".parse::<ImageFormat>().is_err());
+        assert_eq!(
+            ImageFormat::of_original("image/gif"),
+            Some(ImageFormat::Png)
+        );
+    }
+
+    #[test]
+    fn resizing() {
+        let original = png(80, 40);
+        let render = |rendition: Rendition| {
+            let data = rendition.render(&original).unwrap();
+            let format = image::guess_format(&data).unwrap();
+            (format, image::load_from_memory(&data).unwrap().dimensions())
+        };
+
+        assert_eq!(
+            render(rendition(Some(40), None, ImageFormat::Webp)),
+            (image::ImageFormat::WebP, (40, 20))
+        );
+        assert_eq!(
+            render(rendition(None, Some(10), ImageFormat::Jpeg)),
+            (image::ImageFormat::Jpeg, (20, 10))
+        );
+        assert_eq!(
+            render(rendition(Some(30), Some(30), ImageFormat::Png)),
+            (image::ImageFormat::Png, (30, 30))
+        );
+        assert_eq!(
+            render(rendition(Some(160), None, ImageFormat::Png)),
+            (image::ImageFormat::Png, (80, 40))
+        );
+        assert!(rendition(Some(40), None, ImageFormat::Png)
+            .render(b"not an image")
+            .is_err());
+    }
+
+    #[test]
+    fn srcsets() {
+        assert_eq!(
+            srcset("/media/3f2a", &[640, 320]),
+            "/media/3f2a?w=320 320w, /media/3f2a?w=640 640w"
+        );
+
+        let html = markdown::render("![Polar bear](/media/3f2a \"Bear\") ![Logo](/logo.png)");
+        let responsive = responsive_images(&html, &[320]);
+        assert!(responsive.contains(
+            r#"<img src="/media/3f2a" alt="Polar bear" title="Bear" srcset="/media/3f2a?w=320 320w" sizes="(max-width: 46rem) 100vw, 46rem">"#
+        ));
+        assert!(responsive.contains(r#"<img src="/logo.png" alt="Logo">"#));
+        assert_eq!(responsive_images(&html, &[]), html);
+    }
+}
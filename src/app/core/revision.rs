@@ -0,0 +1,97 @@
+//! # Revision
+//!
+//! Comparison of the successive versions of a post, each version being laid
+//! out as a plain text document so that any two can be diffed line by line.
+
+use similar::TextDiff;
+
+use crate::app::core::database::models::{Post, PostRevision};
+
+/// Number of unchanged lines surrounding each change of a diff
+const CONTEXT: usize = 3;
+
+/// Content of a post at some point of its history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Version<'a> {
+    pub slug: &'a str,
+    pub title: &'a str,
+    pub body_markdown: &'a str,
+}
+
+impl<'a> From<&'a Post> for Version<'a> {
+    fn from(post: &'a Post) -> Self {
+        Version {
+            slug: &post.slug,
+            title: &post.title,
+            body_markdown: &post.body_markdown,
+        }
+    }
+}
+
+impl<'a> From<&'a PostRevision> for Version<'a> {
+    fn from(revision: &'a PostRevision) -> Self {
+        Version {
+            slug: &revision.slug,
+            title: &revision.title,
+            body_markdown: &revision.body_markdown,
+        }
+    }
+}
+
+impl<'a> Version<'a> {
+    fn document(&self) -> String {
+        let mut document = format!(
+            "title: {}\nslug: {}\n\n{}",
+            self.title, self.slug, self.body_markdown
+        );
+        if !document.ends_with('\n') {
+            document.push('\n');
+        }
+        document
+    }
+}
+
+/// Unified diff turning the `old` version into the `new` one, both being
+/// named in the diff header
+pub fn diff(old: Version<'_>, old_name: &str, new: Version<'_>, new_name: &str) -> String {
+    let (old, new) = (old.document(), new.document());
+    TextDiff::from_lines(&old, &new)
+        .unified_diff()
+        .context_radius(CONTEXT)
+        .header(old_name, new_name)
+        .to_string()
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, Version};
+
+    const OLD: Version = Version {
+        slug: "hello",
+        title: "Hello",
+        body_markdown: "First line\nSecond line",
+    };
+
+    #[test]
+    fn unified() {
+        let new = Version {
+            title: "Hello, world",
+            body_markdown: "First line\nSecond line, amended\n",
+            ..OLD
+        };
+
+        assert_eq!(
+            diff(OLD, "revision 1", new, "current"),
+            "--- revision 1\n+++ current\n@@ -1,5 +1,5 @@\n\
+             -title: Hello\n+title: Hello, world\n slug: hello\n \n First line\n\
+             -Second line\n+Second line, amended\n"
+        );
+    }
+
+    #[test]
+    fn identical() {
+        assert_eq!(diff(OLD, "a", OLD, "b"), "");
+    }
+}
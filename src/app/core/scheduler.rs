@@ -0,0 +1,56 @@
+//! # Scheduler
+//!
+//! Background task publishing scheduled posts once their date has come, started
+//! along with the web server.
+
+use std::time::Duration;
+
+use chrono::Utc;
+use rocket::fairing::{AdHoc, Fairing};
+use rocket::tokio::{self, select, time::interval};
+use rocket_db_pools::diesel::PgPool;
+use rocket_db_pools::Database;
+
+use crate::app::core::database::models::Post;
+use crate::database::DbConnection;
+
+/// Delay between two lookups of posts to publish
+const PERIOD: Duration = Duration::from_secs(30);
+
+/// Fairing spawning the scheduler at liftoff, the task ending with the server
+pub fn fairing() -> impl Fairing {
+    AdHoc::on_liftoff("Post Scheduler", |rocket| {
+        Box::pin(async move {
+            let pool = match DbConnection::fetch(rocket) {
+                Some(db) => (**db).clone(),
+                None => return error!("Post scheduler needs the database pool"),
+            };
+            let shutdown = rocket.shutdown();
+
+            tokio::spawn(async move {
+                let mut ticks = interval(PERIOD);
+                loop {
+                    select! {
+                        _ = ticks.tick() => publish(&pool).await,
+                        _ = shutdown.clone() => break,
+                    }
+                }
+            });
+        })
+    })
+}
+
+async fn publish(pool: &PgPool) {
+    let published = match pool.get().await {
+        Ok(mut conn) => Post::publish_scheduled(&mut conn, Utc::now())
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+
+    match published {
+        Ok(0) => {}
+        Ok(count) => info!("Published {} scheduled posts", count),
+        Err(e) => error!("Publication of scheduled posts failed: {}", e),
+    }
+}
@@ -0,0 +1,208 @@
+//! # Search
+//!
+//! Full-text search over published posts, backed by the `search_vector`
+//! column PostgreSQL keeps up to date, each post being indexed with its own
+//! text search configuration. Queries are written in a web search like syntax
+//! turned into a `tsquery` here, `websearch_to_tsquery` knowing nothing of
+//! prefixes:
+//!
+//! - words must all be found, `or` between two of them matching either
+//! - `"quoted words"` must be found in this order
+//! - `word*` matches the words starting with `word`
+//! - `-word` or `-"quoted words"` must not be found
+//!
+//! Matches are ranked by relevance, titles weighing more than bodies, and
+//! served with highlighted snippets of the bodies.
+
+use diesel::QueryResult;
+use rocket_db_pools::diesel::AsyncPgConnection;
+use serde::Serialize;
+
+use crate::api::Pagination;
+use crate::app::core::database::models::{Post, PostDetails, SearchHit};
+
+/// Longest query accepted, in characters
+pub const MAX_QUERY_LENGTH: usize = 256;
+
+/// Delimiters of the matches in snippets, never found in HTML once escaped
+const START: char = '\u{2}';
+const STOP: char = '\u{3}';
+
+/// `ts_headline` options of the snippets
+const HEADLINE_OPTIONS: &str = "StartSel=\u{2}, StopSel=\u{3}, MinWords=15, MaxWords=35, \
+                                MaxFragments=2, FragmentDelimiter=\" … \"";
+
+/// Published post matching a search, along with its relevance
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    pub post: PostDetails,
+    pub rank: f32,
+    /// Excerpts of the body, matches being wrapped in `<mark>` elements
+    pub snippet: String,
+}
+
+/// Word or phrase of a query
+#[derive(Debug)]
+struct Term {
+    words: Vec<String>,
+    prefix: bool,
+    negated: bool,
+}
+
+impl Term {
+    fn to_tsquery(&self) -> String {
+        let mut query = self.words.join(" <-> ");
+        if self.prefix {
+            query.push_str(":*");
+        }
+        match (self.negated, self.words.len()) {
+            (false, _) => query,
+            (true, 1) => format!("!{}", query),
+            (true, _) => format!("!({})", query),
+        }
+    }
+}
+
+/// Words of a term, operators and punctuation being left out
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// `tsquery` of a search query, if it has any word to search for
+pub fn to_tsquery(input: &str) -> Option<String> {
+    // Terms all to be found, each one being a list of alternatives
+    let mut groups: Vec<Vec<Term>> = Vec::new();
+    let mut either = false;
+
+    let mut rest = input.trim_start();
+    while !rest.is_empty() {
+        let (negated, after) = match rest.strip_prefix('-') {
+            Some(after) => (true, after),
+            None => (false, rest),
+        };
+        let (text, phrase, remainder) = match after.strip_prefix('"') {
+            Some(quoted) => match quoted.split_once('"') {
+                Some((text, remainder)) => (text, true, remainder),
+                None => (quoted, true, ""),
+            },
+            None => {
+                let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                (&after[..end], false, &after[end..])
+            }
+        };
+        rest = remainder.trim_start();
+
+        if !phrase && !negated && text.eq_ignore_ascii_case("or") {
+            either = !groups.is_empty();
+            continue;
+        }
+        let words = words(text);
+        if words.is_empty() {
+            continue;
+        }
+        let term = Term {
+            words,
+            prefix: !phrase && text.ends_with('*'),
+            negated,
+        };
+        match groups.last_mut() {
+            Some(group) if either => group.push(term),
+            _ => groups.push(vec![term]),
+        }
+        either = false;
+    }
+
+    let clauses: Vec<String> = groups
+        .iter()
+        .map(|group| match group.as_slice() {
+            [term] => term.to_tsquery(),
+            terms => {
+                let alternatives: Vec<String> = terms.iter().map(Term::to_tsquery).collect();
+                format!("({})", alternatives.join(" | "))
+            }
+        })
+        .collect();
+    match clauses.is_empty() {
+        true => None,
+        false => Some(clauses.join(" & ")),
+    }
+}
+
+/// Escape a snippet as HTML, wrapping its matches in `<mark>` elements
+pub fn highlight(snippet: &str) -> String {
+    tera::escape_html(snippet)
+        .replace(START, "<mark>")
+        .replace(STOP, "</mark>")
+}
+
+/// Published posts matching the `tsquery`, most relevant first
+pub async fn search(
+    conn: &mut AsyncPgConnection,
+    query: &str,
+    pagination: Pagination,
+) -> QueryResult<Vec<SearchResult>> {
+    let hits = SearchHit::search(conn, query, HEADLINE_OPTIONS, pagination).await?;
+    let ids: Vec<i32> = hits.iter().map(|hit| hit.id).collect();
+    let posts = Post::find_all(conn, &ids).await?;
+    let mut posts = PostDetails::load(conn, posts).await?;
+
+    Ok(hits
+        .into_iter()
+        .filter_map(|hit| {
+            let index = posts.iter().position(|post| post.post.id == hit.id)?;
+            Some(SearchResult {
+                post: posts.swap_remove(index),
+                rank: hit.rank,
+                snippet: highlight(&hit.snippet),
+            })
+        })
+        .collect())
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::{highlight, to_tsquery};
+
+    #[test]
+    fn queries() {
+        let query = |input: &str| to_tsquery(input).unwrap();
+
+        assert_eq!(query("rust  async"), "rust & async");
+        assert_eq!(
+            query("\"zero cost\" abstractions"),
+            "zero <-> cost & abstractions"
+        );
+        assert_eq!(query("program*"), "program:*");
+        assert_eq!(
+            query("rust -python -\"c plus\""),
+            "rust & !python & !(c <-> plus)"
+        );
+        assert_eq!(query("rust OR go or zig web"), "(rust | go | zig) & web");
+        assert_eq!(
+            query("it's \"unclosed phrase"),
+            "it <-> s & unclosed <-> phrase"
+        );
+        assert_eq!(query("or élan"), "élan");
+    }
+
+    #[test]
+    fn operators_are_not_injected() {
+        assert_eq!(to_tsquery("a:*&!|()' b").unwrap(), "a & b");
+        assert_eq!(to_tsquery("\"\" - * or !"), None);
+        assert_eq!(to_tsquery("   "), None);
+    }
+
+    #[test]
+    fn highlighting() {
+        assert_eq!(
+            highlight("Writing \u{2}Rust\u{3} <script> & \u{2}tests\u{3}"),
+            "Writing <mark>Rust</mark> &lt;script&gt; &amp; <mark>tests</mark>"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! # Sitemap
+//!
+//! Pages of the blog worth indexing, in the sitemaps.org XML format. Search
+//! engines reject sitemaps of more than [MAX_URLS] URLs, larger ones being
+//! split into numbered sitemaps listed by a sitemap index.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+
+use crate::config::SiteConfig;
+use crate::result::SerdeError;
+
+/// Most URLs a single sitemap may list
+pub const MAX_URLS: usize = 50_000;
+
+const XML_DECLARATION: &str = r#"<?xml version="1.0" encoding="utf-8"?>"#;
+const NAMESPACE: &str = "http://www.sitemaps.org/schemas/sitemap/0.9";
+
+/// Page of the blog, along with the date of its last change if known
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Url {
+    pub loc: String,
+    pub lastmod: Option<DateTime<Utc>>,
+}
+
+/// Home page, published posts, and tags and authors of these, each given
+/// along with its last change
+pub fn urls(
+    site: &SiteConfig,
+    posts: &[(String, DateTime<Utc>)],
+    tags: &[(String, Option<DateTime<Utc>>)],
+    authors: &[(String, Option<DateTime<Utc>>)],
+) -> Vec<Url> {
+    let mut urls = vec![Url {
+        loc: site.url_of("/"),
+        lastmod: posts.iter().map(|(_, updated)| *updated).max(),
+    }];
+    urls.extend(posts.iter().map(|(slug, updated)| Url {
+        loc: site.url_of(&format!("/posts/{}", slug)),
+        lastmod: Some(*updated),
+    }));
+    urls.extend(tags.iter().map(|(slug, updated)| Url {
+        loc: site.url_of(&format!("/tags/{}", slug)),
+        lastmod: *updated,
+    }));
+    urls.extend(authors.iter().map(|(username, updated)| Url {
+        loc: site.url_of(&format!("/authors/{}", username)),
+        lastmod: *updated,
+    }));
+    urls
+}
+
+/// Number of sitemaps the URLs are split into, an index being needed past one
+pub fn pages(urls: &[Url]) -> usize {
+    urls.len().div_ceil(MAX_URLS).max(1)
+}
+
+/// URLs listed by the sitemap of the given 1-based number, if it exists
+pub fn page(urls: &[Url], number: usize) -> Option<&[Url]> {
+    urls.chunks(MAX_URLS).nth(number.checked_sub(1)?)
+}
+
+/// Sitemap listing the given URLs
+pub fn urlset(urls: &[Url]) -> Result<String, SerdeError> {
+    let document = UrlSet {
+        xmlns: NAMESPACE,
+        url: urls.iter().map(Entry::from).collect(),
+    };
+    Ok(format!(
+        "{}{}",
+        XML_DECLARATION,
+        quick_xml::se::to_string(&document)?
+    ))
+}
+
+/// Sitemap index listing the sitemaps the URLs are split into, `sitemap_url`
+/// giving the location of each from its number
+pub fn index(urls: &[Url], sitemap_url: impl Fn(usize) -> String) -> Result<String, SerdeError> {
+    let sitemaps: Vec<Url> = urls
+        .chunks(MAX_URLS)
+        .enumerate()
+        .map(|(i, chunk)| Url {
+            loc: sitemap_url(i + 1),
+            lastmod: chunk.iter().filter_map(|url| url.lastmod).max(),
+        })
+        .collect();
+    let document = SitemapIndex {
+        xmlns: NAMESPACE,
+        sitemap: sitemaps.iter().map(Entry::from).collect(),
+    };
+    Ok(format!(
+        "{}{}",
+        XML_DECLARATION,
+        quick_xml::se::to_string(&document)?
+    ))
+}
+
+/* ------------------------------------------ Document ----------------------------------------- */
+
+#[derive(Serialize)]
+#[serde(rename = "urlset")]
+struct UrlSet<'a> {
+    #[serde(rename = "@xmlns")]
+    xmlns: &'a str,
+    url: Vec<Entry<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename = "sitemapindex")]
+struct SitemapIndex<'a> {
+    #[serde(rename = "@xmlns")]
+    xmlns: &'a str,
+    sitemap: Vec<Entry<'a>>,
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    loc: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lastmod: Option<String>,
+}
+
+impl<'a> From<&'a Url> for Entry<'a> {
+    fn from(url: &'a Url) -> Self {
+        Entry {
+            loc: &url.loc,
+            lastmod: url
+                .lastmod
+                .map(|date| date.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        }
+    }
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::{index, page, pages, urlset, Url, MAX_URLS};
+
+    fn url(n: usize) -> Url {
+        Url {
+            loc: format!("https://blog.example/posts/{}", n),
+            lastmod: format!("2024-11-{:02}T10:00:00Z", n % 28 + 1).parse().ok(),
+        }
+    }
+
+    #[test]
+    fn single_sitemap() {
+        let urls = [
+            Url {
+                loc: "https://blog.example/?a&b".to_string(),
+                lastmod: None,
+            },
+            url(1),
+        ];
+
+        assert_eq!(pages(&urls), 1);
+        assert_eq!(
+            urlset(&urls).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+             <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\
+             <url><loc>https://blog.example/?a&amp;b</loc></url>\
+             <url><loc>https://blog.example/posts/1</loc><lastmod>2024-11-02T10:00:00Z</lastmod></url>\
+             </urlset>"
+        );
+    }
+
+    #[test]
+    fn split_sitemaps() {
+        let urls: Vec<Url> = (0..MAX_URLS + 2).map(url).collect();
+
+        assert_eq!(pages(&urls), 2);
+        assert_eq!(page(&urls, 1).unwrap().len(), MAX_URLS);
+        assert_eq!(page(&urls, 2).unwrap(), &urls[MAX_URLS..]);
+        assert!(page(&urls, 0).is_none());
+        assert!(page(&urls, 3).is_none());
+
+        let xml = index(&urls, |n| {
+            format!("https://blog.example/sitemaps/{}.xml", n)
+        })
+        .unwrap();
+        assert!(
+            xml.contains("<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">")
+        );
+        assert!(xml.contains(
+            "<sitemap><loc>https://blog.example/sitemaps/2.xml</loc>\
+             <lastmod>2024-11-22T10:00:00Z</lastmod></sitemap>"
+        ));
+    }
+}
@@ -0,0 +1,194 @@
+//! # Spam
+//!
+//! Naive Bayes classifier scoring new comments, running entirely offline. It
+//! learns from the comments moderators approve (ham) or mark as spam, the
+//! number of comments of each class every token was found in being kept in
+//! the database.
+//!
+//! Tokens are the words of a comment along with its author name, email domain
+//! and linked hosts, each counted once per comment. A comment is scored after
+//! its [INTERESTING] tokens whose spam probability is the farthest from
+//! neutral, following Paul Graham's *A Plan for Spam* with Gary Robinson's
+//! handling of rare tokens.
+
+use std::collections::BTreeSet;
+
+use diesel::QueryResult;
+use rocket_db_pools::diesel::AsyncPgConnection;
+
+use crate::app::core::database::models::{
+    Comment, CommentStatus, SpamCorpus, SpamLabel, SpamToken,
+};
+
+/// Number of tokens a comment is scored after
+pub const INTERESTING: usize = 15;
+
+/// Words shorter or longer than these are ignored
+const WORD_LENGTHS: (usize, usize) = (2, 32);
+
+/// Longest token the database stores
+const MAX_TOKEN_LENGTH: usize = 64;
+
+/// Weight of the neutral probability assumed for rarely seen tokens
+const PRIOR_WEIGHT: f64 = 1.0;
+
+/// Tokens of a comment, sorted and without duplicates
+pub fn tokens(author_name: &str, author_email: Option<&str>, body: &str) -> Vec<String> {
+    let words = |text: &str| -> Vec<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| (WORD_LENGTHS.0..=WORD_LENGTHS.1).contains(&word.chars().count()))
+            .map(str::to_lowercase)
+            .collect()
+    };
+    let hosts = body.split_whitespace().filter_map(|word| {
+        let (_, url) = word.split_once("://")?;
+        let host = url.split(['/', '?', '#', ':']).next()?;
+        Some(format!("url:{}", host.to_lowercase()))
+    });
+    let domain = author_email
+        .and_then(|email| email.rsplit_once('@'))
+        .map(|(_, domain)| format!("email:{}", domain.to_lowercase()));
+
+    let mut tokens: BTreeSet<String> = words(body).into_iter().collect();
+    tokens.extend(
+        words(author_name)
+            .into_iter()
+            .map(|w| format!("name:{}", w)),
+    );
+    tokens.extend(hosts.chain(domain));
+    tokens.retain(|token| token.len() <= MAX_TOKEN_LENGTH);
+    tokens.into_iter().collect()
+}
+
+/// Probability of a comment of the given known tokens to be spam, unknown
+/// until the filter learned from both spam and ham
+pub fn probability(corpus: &SpamCorpus, stats: &[SpamToken]) -> Option<f32> {
+    if corpus.spam <= 0 || corpus.ham <= 0 {
+        return None;
+    }
+
+    let mut probabilities: Vec<f64> = stats
+        .iter()
+        .filter(|stats| stats.spam + stats.ham > 0)
+        .map(|stats| {
+            let spam = (stats.spam as f64 / corpus.spam as f64).min(1.0);
+            let ham = (stats.ham as f64 / corpus.ham as f64).min(1.0);
+            let seen = (stats.spam + stats.ham) as f64;
+            let p = spam / (spam + ham);
+            ((PRIOR_WEIGHT * 0.5 + seen * p) / (PRIOR_WEIGHT + seen)).clamp(0.01, 0.99)
+        })
+        .collect();
+    probabilities.sort_by(|a, b| (b - 0.5).abs().total_cmp(&(a - 0.5).abs()));
+    probabilities.truncate(INTERESTING);
+
+    // Combined in log space, products of small probabilities underflowing
+    let eta: f64 = probabilities.iter().map(|p| (1.0 - p).ln() - p.ln()).sum();
+    Some((1.0 / (1.0 + eta.exp())) as f32)
+}
+
+/// Label a moderator gives to a comment by moving it to the given status,
+/// `None` for the ones not worth learning from
+pub fn label(status: CommentStatus) -> Option<SpamLabel> {
+    match status {
+        CommentStatus::Spam => Some(SpamLabel::Spam),
+        CommentStatus::Approved => Some(SpamLabel::Ham),
+        CommentStatus::Pending | CommentStatus::Deleted => None,
+    }
+}
+
+/// Spam probability of a comment of the given tokens
+pub async fn score(conn: &mut AsyncPgConnection, tokens: &[String]) -> QueryResult<Option<f32>> {
+    let corpus = SpamCorpus::get(conn).await?;
+    let stats = SpamToken::find_all(conn, tokens).await?;
+    Ok(probability(&corpus, &stats))
+}
+
+/// Learn from a comment given a new label, forgetting the former one
+pub async fn train(
+    conn: &mut AsyncPgConnection,
+    comment: &Comment,
+    label: Option<SpamLabel>,
+) -> QueryResult<()> {
+    if comment.trained_as == label {
+        return Ok(());
+    }
+    let tokens = tokens(
+        &comment.author_name,
+        comment.author_email.as_deref(),
+        &comment.body,
+    );
+    if let Some(former) = comment.trained_as {
+        SpamToken::forget(conn, &tokens, former).await?;
+        SpamCorpus::forget(conn, former).await?;
+    }
+    if let Some(label) = label {
+        SpamToken::learn(conn, &tokens, label).await?;
+        SpamCorpus::learn(conn, label).await?;
+    }
+    Comment::set_trained_as(conn, comment.id, label).await?;
+    Ok(())
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::{probability, tokens};
+    use crate::app::core::database::models::{SpamCorpus, SpamToken};
+
+    fn stats(token: &str, spam: i32, ham: i32) -> SpamToken {
+        SpamToken {
+            token: token.to_string(),
+            spam,
+            ham,
+        }
+    }
+
+    #[test]
+    fn tokenizer() {
+        assert_eq!(
+            tokens(
+                "Cheap Pills",
+                Some("bob@Spam.example"),
+                "Buy now at https://pills.example/buy?now a a BUY!"
+            ),
+            [
+                "at",
+                "buy",
+                "email:spam.example",
+                "example",
+                "https",
+                "name:cheap",
+                "name:pills",
+                "now",
+                "pills",
+                "url:pills.example",
+            ]
+        );
+    }
+
+    #[test]
+    fn untrained() {
+        let corpus = SpamCorpus { spam: 3, ham: 0 };
+        assert_eq!(probability(&corpus, &[stats("buy", 3, 0)]), None);
+    }
+
+    #[test]
+    fn classification() {
+        let corpus = SpamCorpus { spam: 20, ham: 20 };
+        let spammy = [
+            stats("buy", 18, 1),
+            stats("pills", 15, 0),
+            stats("the", 10, 10),
+        ];
+        let hammy = [
+            stats("rust", 0, 12),
+            stats("thanks", 1, 9),
+            stats("the", 10, 10),
+        ];
+
+        assert!(probability(&corpus, &spammy).unwrap() > 0.99);
+        assert!(probability(&corpus, &hammy).unwrap() < 0.01);
+        assert_eq!(probability(&corpus, &[]), Some(0.5));
+    }
+}
@@ -0,0 +1,89 @@
+//! Files of a local directory
+
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use rocket::tokio::fs;
+
+use super::{check_key, Storage};
+use crate::result::StorageError;
+
+pub struct LocalStorage {
+    directory: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        LocalStorage {
+            directory: directory.into(),
+        }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, StorageError> {
+        check_key(key)?;
+        Ok(self.directory.join(key))
+    }
+}
+
+#[rocket::async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, _content_type: &str, data: &[u8]) -> Result<(), StorageError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        // Readers never see a partly written file
+        let mut partial = path.clone().into_os_string();
+        partial.push(".part");
+        fs::write(&partial, data).await?;
+        fs::rename(&partial, &path).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        match fs::read(self.path(key)?).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match fs::remove_file(self.path(key)?).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::{LocalStorage, Storage};
+
+    #[rocket::async_test]
+    async fn round_trip() {
+        let directory = std::env::temp_dir().join(format!("polar-storage-{}", std::process::id()));
+        let storage = LocalStorage::new(&directory);
+
+        storage
+            .put("renditions/a1/640.png", "image/png", b"png")
+            .await
+            .unwrap();
+        assert_eq!(
+            storage
+                .get("renditions/a1/640.png")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some(&b"png"[..])
+        );
+        storage.delete("renditions/a1/640.png").await.unwrap();
+        storage.delete("renditions/a1/640.png").await.unwrap();
+        assert_eq!(storage.get("renditions/a1/640.png").await.unwrap(), None);
+        assert!(storage.get("../escape").await.is_err());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+//! # Storage
+//!
+//! Backends uploaded files are kept in, either a local directory or an
+//! S3-compatible bucket. Files are addressed by keys made of `/`-separated
+//! segments of ASCII letters, digits, dots, dashes and underscores, media
+//! being stored under the [hash] of their content so that the same file
+//! uploaded twice is only stored once.
+
+mod local;
+mod s3;
+
+use sha2::{Digest, Sha256};
+
+pub use local::LocalStorage;
+pub use s3::S3Storage;
+
+use crate::config::StorageConfig;
+use crate::result::StorageError;
+
+#[rocket::async_trait]
+pub trait Storage: Send + Sync {
+    /// Store the data under the key, replacing anything stored there
+    async fn put(&self, key: &str, content_type: &str, data: &[u8]) -> Result<(), StorageError>;
+
+    /// Data stored under the key, if any
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError>;
+
+    /// Remove the data stored under the key, if any
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+}
+
+/// Storage backend of the configuration
+pub fn from_config(config: &StorageConfig) -> Result<Box<dyn Storage>, StorageError> {
+    Ok(match config {
+        StorageConfig::Local { directory } => Box::new(LocalStorage::new(directory)),
+        StorageConfig::S3 {
+            endpoint,
+            region,
+            bucket,
+            access_key,
+            secret_key,
+        } => Box::new(S3Storage::new(
+            endpoint, region, bucket, access_key, secret_key,
+        )?),
+    })
+}
+
+/// Fail unless the key is made of valid segments, none of them going up or
+/// staying in the same directory
+pub fn check_key(key: &str) -> Result<(), StorageError> {
+    let valid = key.split('/').all(|segment| {
+        !segment.is_empty()
+            && !segment.starts_with('.')
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+    });
+    match valid {
+        true => Ok(()),
+        false => Err(StorageError::InvalidKey(key.to_string())),
+    }
+}
+
+/// Lowercase hexadecimal SHA-256 hash of the data
+pub fn hash(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::{check_key, hash};
+
+    #[test]
+    fn keys() {
+        assert!(check_key("3f2a9c").is_ok());
+        assert!(check_key("renditions/3f2a9c/640.webp").is_ok());
+        for key in [
+            "",
+            "/3f2a9c",
+            "a//b",
+            "../etc/passwd",
+            "a/./b",
+            ".hidden",
+            "a b",
+            "é",
+        ] {
+            assert!(check_key(key).is_err(), "{}", key);
+        }
+    }
+
+    #[test]
+    fn hashes() {
+        assert_eq!(
+            hash(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(hash(b"polar").len(), 64);
+    }
+}
@@ -0,0 +1,232 @@
+//! # Theme
+//!
+//! Server-side rendering of the public pages from [Tera] templates. Polar ships
+//! a default theme, every template found in the configured theme directory
+//! overriding the built-in one of the same name.
+//!
+//! Besides the ones of Tera, templates may use the `srcset` filter, giving the
+//! `srcset` of an uploaded image from its `/media` URL, and the
+//! `responsive_images` filter, giving every uploaded image of an HTML document
+//! its `srcset`.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use tera::{try_get_value, Context, Tera, Value};
+
+use crate::app::core::{markdown, rendition};
+use crate::config::{MediaConfig, ThemeConfig};
+use crate::result::{ConfigurationError, Result};
+
+macro_rules! builtin {
+    ($($name:literal),+ $(,)?) => {
+        [$(($name, include_str!(concat!("../../../resources/themes/default/", $name)))),+]
+    };
+}
+
+/// Templates of the default theme, embedded in the binary
+const TEMPLATES: [(&str, &str); 9] = builtin![
+    "base.html",
+    "_post_list.html",
+    "_tags.html",
+    "_comments.html",
+    "home.html",
+    "post.html",
+    "author.html",
+    "tag.html",
+    "404.html",
+];
+
+/// Stylesheet of the default theme
+pub const STYLESHEET: &str = include_str!("../../../resources/themes/default/static/polar.css");
+
+/// Loaded templates of the active theme, managed by rocket
+pub struct Theme {
+    tera: Tera,
+    highlight_css: String,
+}
+
+impl Theme {
+    /// Load the templates of the configured theme directory, if any, on top of
+    /// the built-in ones, images being offered in the configured sizes
+    pub fn load<'a>(config: &ThemeConfig, media: &MediaConfig) -> Result<'a, Theme> {
+        let mut tera = match &config.directory {
+            Some(directory) if !Path::new(directory).is_dir() => {
+                return Err(ConfigurationError::misconfigured("theme.directory").into())
+            }
+            Some(directory) => Tera::parse(&format!("{}/**/*.html", directory))?,
+            None => Tera::default(),
+        };
+
+        let mut builtin = Tera::default();
+        builtin.add_raw_templates(TEMPLATES)?;
+        tera.extend(&builtin)?;
+        tera.build_inheritance_chains()?;
+
+        let widths = media.sizes.clone();
+        tera.register_filter(
+            "srcset",
+            move |value: &Value, _: &HashMap<String, Value>| {
+                let src = try_get_value!("srcset", "value", String, value);
+                Ok(Value::String(rendition::srcset(&src, &widths)))
+            },
+        );
+        let widths = media.sizes.clone();
+        tera.register_filter(
+            "responsive_images",
+            move |value: &Value, _: &HashMap<String, Value>| {
+                let html = try_get_value!("responsive_images", "value", String, value);
+                Ok(Value::String(rendition::responsive_images(&html, &widths)))
+            },
+        );
+
+        Ok(Theme {
+            tera,
+            highlight_css: markdown::theme_css(&config.highlight)?,
+        })
+    }
+
+    /// Directory of the static files of the configured theme, if it has any
+    pub fn static_dir(config: &ThemeConfig) -> Option<String> {
+        config
+            .directory
+            .as_ref()
+            .map(|directory| format!("{}/static", directory))
+            .filter(|directory| Path::new(directory).is_dir())
+    }
+
+    pub fn render<'a>(&self, template: &str, context: &Context) -> Result<'a, String> {
+        Ok(self.tera.render(template, context)?)
+    }
+
+    /// Stylesheet of the configured code highlighting theme
+    pub fn highlight_css(&self) -> &str {
+        &self.highlight_css
+    }
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    use serde_json::json;
+    use tera::Context;
+
+    use super::Theme;
+    use crate::config::{MediaConfig, ThemeConfig};
+    use crate::result::{ConfigurationError, Error};
+
+    #[test]
+    fn builtin_templates() {
+        let theme = Theme::load(&ThemeConfig::default(), &MediaConfig::default()).unwrap();
+        let mut context = Context::new();
+        context.insert("path", "/<nowhere>");
+
+        let html = theme.render("404.html", &context).unwrap();
+
+        assert!(html.contains("<title>Page not found &middot; Polar</title>"));
+        assert!(html.contains("<code>&#x2F;&lt;nowhere&gt;</code>"));
+        assert!(theme.highlight_css().contains(".hl-code"));
+    }
+
+    #[test]
+    fn comment_threads() {
+        let theme = Theme::load(&ThemeConfig::default(), &MediaConfig::default()).unwrap();
+        let comment = |id: i32, body: &str, replies: serde_json::Value| {
+            json!({
+                "id": id,
+                "author": null,
+                "author_name": "Jane",
+                "body": body,
+                "created_at": "2024-10-27T12:00:00Z",
+                "replies": replies,
+            })
+        };
+        let mut context = Context::new();
+        context.insert(
+            "author",
+            &json!({"username": "john", "display_name": "John"}),
+        );
+        context.insert(
+            "post",
+            &json!({"title": "Hello", "body_html": "", "tags": []}),
+        );
+        context.insert(
+            "comments",
+            &json!([comment(
+                1,
+                "First\n<b>!</b>",
+                json!([comment(2, "Reply", json!([]))])
+            )]),
+        );
+
+        let html = theme.render("post.html", &context).unwrap();
+
+        assert!(html.contains(r#"<li class="comment" id="comment-2">"#));
+        assert!(html.contains("First<br>&lt;b&gt;!&lt;&#x2F;b&gt;"));
+        assert_eq!(html.matches(r#"<ol class="comments">"#).count(), 2);
+    }
+
+    #[test]
+    fn responsive_images() {
+        let theme = Theme::load(&ThemeConfig::default(), &MediaConfig::default()).unwrap();
+        let mut context = Context::new();
+        context.insert(
+            "author",
+            &json!({"username": "john", "display_name": "John"}),
+        );
+        context.insert(
+            "post",
+            &json!({"title": "Hello", "body_html": "<p><img src=\"/media/3f2a\" alt=\"\"></p>", "tags": []}),
+        );
+
+        let html = theme.render("post.html", &context).unwrap();
+
+        assert!(html.contains(r#"srcset="/media/3f2a?w=320 320w, /media/3f2a?w=640 640w,"#));
+    }
+
+    #[test]
+    fn directory_overrides() {
+        let directory = temp_dir().join(format!("polar-theme-{}", std::process::id()));
+        create_dir_all(directory.join("static")).unwrap();
+        write(
+            directory.join("404.html"),
+            r#"{% extends "base.html" %}{% block content %}Lost: {{ path }}{% endblock content %}"#,
+        )
+        .unwrap();
+
+        let config = ThemeConfig {
+            directory: Some(directory.to_string_lossy().to_string()),
+            ..ThemeConfig::default()
+        };
+        let theme = Theme::load(&config, &MediaConfig::default()).unwrap();
+        let mut context = Context::new();
+        context.insert("path", "here");
+        let html = theme.render("404.html", &context);
+        let static_dir = Theme::static_dir(&config);
+        remove_dir_all(&directory).unwrap();
+
+        let html = html.unwrap();
+        assert!(html.contains("<title>Polar</title>"));
+        assert!(html.contains("Lost: here"));
+        assert_eq!(static_dir, Some(format!("{}/static", directory.display())));
+    }
+
+    #[test]
+    fn missing_directory() {
+        let config = ThemeConfig {
+            directory: Some("/i/definitely/dont/exist".to_string()),
+            ..ThemeConfig::default()
+        };
+
+        assert!(matches!(
+            Theme::load(&config, &MediaConfig::default()),
+            Err(Error::ConfigurationError(
+                ConfigurationError::MisconfiguredEntry("theme.directory")
+            ))
+        ));
+    }
+}
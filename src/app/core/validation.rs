@@ -0,0 +1,198 @@
+//! Input validation rules shared by the API and the commands
+
+use crate::result::FieldError;
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+pub const MAX_COMMENT_LENGTH: usize = 10_000;
+
+/// Slugs are made of lowercase alphanumeric words separated by single dashes
+pub fn is_slug(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 255
+        && value.split('-').all(|word| {
+            !word.is_empty()
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        })
+}
+
+/// Usernames are made of 1 to 64 alphanumeric characters, dots, dashes or underscores
+pub fn is_username(value: &str) -> bool {
+    (1..=64).contains(&value.len())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+/// Loose email check, actual deliverability being out of reach anyway
+pub fn is_email(value: &str) -> bool {
+    match value.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && value.len() <= 255
+        }
+        None => false,
+    }
+}
+
+pub fn check_user<'a>(
+    username: Option<&str>,
+    email: Option<&str>,
+    password: Option<&str>,
+) -> Vec<FieldError<'a>> {
+    let mut errors = Vec::new();
+    if username.is_some_and(|username| !is_username(username)) {
+        errors.push(FieldError::new(
+            "username",
+            "must be 1 to 64 alphanumeric characters, dots, dashes or underscores",
+        ));
+    }
+    if email.is_some_and(|email| !is_email(email)) {
+        errors.push(FieldError::new("email", "must be a valid email address"));
+    }
+    if password.is_some_and(|password| password.chars().count() < MIN_PASSWORD_LENGTH) {
+        errors.push(FieldError::new(
+            "password",
+            format!("must be at least {} characters long", MIN_PASSWORD_LENGTH),
+        ));
+    }
+    errors
+}
+
+pub fn check_post<'a>(slug: Option<&str>, title: Option<&str>) -> Vec<FieldError<'a>> {
+    let mut errors = Vec::new();
+    if slug.is_some_and(|slug| !is_slug(slug)) {
+        errors.push(FieldError::new(
+            "slug",
+            "must be lowercase alphanumeric words separated by dashes",
+        ));
+    }
+    if title.is_some_and(|title| title.trim().is_empty() || title.len() > 255) {
+        errors.push(FieldError::new("title", "must be 1 to 255 characters long"));
+    }
+    errors
+}
+
+/// Tags and categories
+pub fn check_term<'a>(slug: Option<&str>, name: Option<&str>) -> Vec<FieldError<'a>> {
+    let mut errors = Vec::new();
+    if slug.is_some_and(|slug| !is_slug(slug)) {
+        errors.push(FieldError::new(
+            "slug",
+            "must be lowercase alphanumeric words separated by dashes",
+        ));
+    }
+    if name.is_some_and(|name| !is_slug(&slug::slugify(name)) || name.len() > 255) {
+        errors.push(FieldError::new(
+            "name",
+            "must be 1 to 255 characters long, including a letter or digit",
+        ));
+    }
+    errors
+}
+
+/// Names of the tags or categories of a post, given as the `field`
+pub fn check_terms<'a>(field: &'a str, names: &[String]) -> Vec<FieldError<'a>> {
+    match names
+        .iter()
+        .all(|name| check_term(None, Some(name)).is_empty())
+    {
+        true => Vec::new(),
+        false => vec![FieldError::new(
+            field,
+            "must each be 1 to 255 characters long, including a letter or digit",
+        )],
+    }
+}
+
+/// Anonymous comments give a name and optionally an email address
+pub fn check_comment<'a>(
+    author_name: Option<&str>,
+    author_email: Option<&str>,
+    body: &str,
+) -> Vec<FieldError<'a>> {
+    let mut errors = Vec::new();
+    if author_name.is_some_and(|name| name.trim().is_empty() || name.len() > 255) {
+        errors.push(FieldError::new(
+            "author_name",
+            "must be 1 to 255 characters long",
+        ));
+    }
+    if author_email.is_some_and(|email| !is_email(email)) {
+        errors.push(FieldError::new(
+            "author_email",
+            "must be a valid email address",
+        ));
+    }
+    if body.trim().is_empty() || body.chars().count() > MAX_COMMENT_LENGTH {
+        errors.push(FieldError::new(
+            "body",
+            format!("must be 1 to {} characters long", MAX_COMMENT_LENGTH),
+        ));
+    }
+    errors
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_comment, check_post, check_term, check_user, is_email, is_slug, is_username,
+    };
+
+    #[test]
+    fn slugs() {
+        assert!(is_slug("hello-world-2"));
+        assert!(!is_slug(""));
+        assert!(!is_slug("Hello-World"));
+        assert!(!is_slug("hello--world"));
+        assert!(!is_slug("-hello"));
+        assert!(!is_slug("hello world"));
+    }
+
+    #[test]
+    fn usernames_and_emails() {
+        assert!(is_username("jane.doe_42"));
+        assert!(!is_username("jane doe"));
+        assert!(is_email("jane@example.org"));
+        assert!(!is_email("jane@localhost"));
+        assert!(!is_email("@example.org"));
+    }
+
+    #[test]
+    fn collected_errors() {
+        let errors = check_user(Some("jane"), Some("jane"), Some("short"));
+        let fields: Vec<&str> = errors.iter().map(|e| e.field).collect();
+        assert_eq!(fields, vec!["email", "password"]);
+
+        assert!(check_post(None, Some("A title")).is_empty());
+        assert_eq!(check_post(Some("Nope"), Some(" ")).len(), 2);
+    }
+
+    #[test]
+    fn terms() {
+        assert!(check_term(None, Some("Rust & WebAssembly")).is_empty());
+        assert!(check_term(Some("rust"), None).is_empty());
+
+        let fields: Vec<&str> = check_term(Some("Rust"), Some("?!"))
+            .iter()
+            .map(|e| e.field)
+            .collect();
+        assert_eq!(fields, ["slug", "name"]);
+    }
+
+    #[test]
+    fn comments() {
+        assert!(check_comment(Some("Jane"), None, "Nice post!").is_empty());
+
+        let fields: Vec<&str> = check_comment(Some(" "), Some("jane"), &"a".repeat(10_001))
+            .iter()
+            .map(|e| e.field)
+            .collect();
+        assert_eq!(fields, ["author_name", "author_email", "body"]);
+    }
+}
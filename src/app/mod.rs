@@ -1,16 +1,44 @@
 use std::process::exit;
 
-use crate::cli::{Cli, Command, DumpFormat};
+use crate::app::core::database::models::Post;
+use crate::app::core::markdown;
+use crate::app::core::{scheduler, storage, theme};
+use crate::cli::{Build, Cli, Command, DumpFormat, Import, Theme, User};
 use crate::config::Config;
-use crate::database::{migrate as db_migrate, DbConnection};
+use crate::database::{establish_async_connection, migrate as db_migrate, DbConnection};
 use crate::result::Result;
+use crate::security::Accounts;
 use figment::Figment;
 use rocket::fairing::AdHoc;
+use rocket::fs::FileServer;
+use rocket::local::asynchronous::Client;
+use rocket::Rocket;
 use rocket_db_pools::Database;
 
+pub mod commands;
 pub mod core;
 pub mod routes;
 
+/// Pages and API of the blog, left to be launched
+pub(crate) fn blog<'a>(figment: &Figment, config: &Config) -> Result<'a, Rocket<rocket::Build>> {
+    let mut rocket = rocket::custom(figment)
+        .attach(AdHoc::config::<Config>())
+        .attach(DbConnection::init())
+        .manage(theme::Theme::load(&config.theme, &config.media)?)
+        .manage(storage::from_config(&config.media.storage)?)
+        .manage(Box::new(routes::Users) as Box<dyn Accounts>)
+        .mount("/", routes::collect())
+        .register("/", routes::catchers())
+        .mount(routes::api::BASE, routes::api::collect())
+        .register(routes::api::BASE, routes::api::catchers());
+
+    // Files of the theme take precedence over the built-in stylesheets
+    if let Some(directory) = theme::Theme::static_dir(&config.theme) {
+        rocket = rocket.mount("/static", FileServer::from(directory).rank(-20));
+    }
+    Ok(rocket)
+}
+
 pub struct App {
     args: Cli,
     config: Config,
@@ -21,6 +49,7 @@ impl App {
     pub fn new<'a>(args: Cli) -> Result<'a, Self> {
         let figment = Config::figment(&args)?;
         let config: Config = figment.extract()?;
+        config.validate()?;
         Ok(Self {
             args,
             figment,
@@ -29,17 +58,29 @@ impl App {
     }
 
     pub async fn serve<'a>(&self) -> Result<'a, ()> {
-        rocket::custom(&self.figment)
-            .attach(AdHoc::config::<Config>())
-            .attach(DbConnection::init())
-            .mount("/", routes::collect())
+        blog(&self.figment, &self.config)?
+            .attach(scheduler::fairing())
             .launch()
             .await?;
         Ok(())
     }
 
-    pub fn migrate<'a>(&self) -> Result<'a, ()> {
+    /// Request every page from a local instance of the blog, written as is
+    pub async fn build<'a>(&self, build: &Build) -> Result<'a, ()> {
+        let mut conn = establish_async_connection(&self.config.database).await?;
+        let client = Client::untracked(blog(&self.figment, &self.config)?).await?;
+        commands::build::run(&client, &mut conn, &self.config, &build.out).await
+    }
+
+    pub async fn migrate<'a>(&self) -> Result<'a, ()> {
         db_migrate(&self.config.database)?;
+
+        // Posts written before bodies were rendered, or whose rendering was reset
+        let mut conn = establish_async_connection(&self.config.database).await?;
+        let rendered = Post::render_missing(&mut conn, markdown::render).await?;
+        if rendered > 0 {
+            println!("Rendered {} post bodies", rendered);
+        }
         Ok(())
     }
 
@@ -49,11 +90,30 @@ impl App {
         Ok(())
     }
 
+    pub async fn user<'a>(&self, user: &User) -> Result<'a, ()> {
+        let mut conn = establish_async_connection(&self.config.database).await?;
+        commands::user::run(&mut conn, &user.action).await
+    }
+
+    pub fn theme<'a>(&self, theme: &Theme) -> Result<'a, ()> {
+        commands::theme::run(&theme.action)
+    }
+
+    pub async fn import<'a>(&self, import: &Import) -> Result<'a, ()> {
+        let mut conn = establish_async_connection(&self.config.database).await?;
+        let limits = self.figment.extract_inner("limits")?;
+        commands::import::run(&mut conn, &self.config, &limits, &import.action).await
+    }
+
     pub async fn run(&self) {
         if let Err(e) = match &self.args.command {
             Command::Serve(_) => self.serve().await,
-            Command::Migrate(_) => self.migrate(),
+            Command::Build(build) => self.build(build).await,
+            Command::Migrate(_) => self.migrate().await,
             Command::Show(show) => self.show(show.format),
+            Command::User(user) => self.user(user).await,
+            Command::Theme(theme) => self.theme(theme),
+            Command::Import(import) => self.import(import).await,
         } {
             eprintln!("Error: {}", e.to_string());
             exit(1);
@@ -0,0 +1,62 @@
+//! `/auth` endpoints
+
+use rocket::serde::json::Json;
+use rocket::{Route, State};
+use rocket_db_pools::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::api::ApiResponse;
+use crate::app::core::database::models::User;
+use crate::config::Config;
+use crate::database::DbConnection;
+use crate::result::{Error, Result};
+use crate::security::{issue_token, reject_password, verify_password, Claims};
+
+pub fn collect() -> Vec<Route> {
+    routes![login]
+}
+
+/* ------------------------------------------ Payloads ----------------------------------------- */
+
+#[derive(Debug, Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Token {
+    pub token: String,
+    pub token_type: &'static str,
+    pub expires_in: u16,
+}
+
+/* ------------------------------------------- Routes ------------------------------------------ */
+
+#[post("/auth/login", data = "<credentials>")]
+async fn login(
+    mut db: Connection<DbConnection>,
+    config: &State<Config>,
+    credentials: Json<Credentials>,
+) -> Result<'static, ApiResponse<Token>> {
+    let user = match User::find_by_username(&mut db, &credentials.username).await {
+        Ok(user) => user,
+        Err(diesel::result::Error::NotFound) => {
+            // As long as a wrong password, not to tell which usernames exist
+            reject_password(&credentials.password);
+            return Err(Error::Unauthorized("Invalid credentials"));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if !verify_password(&credentials.password, &user.password_hash) || user.disabled {
+        return Err(Error::Unauthorized("Invalid credentials"));
+    }
+
+    let security = &config.security;
+    let claims = Claims::new(&user.username, user.role, security.jwt_lifetime);
+    Ok(ApiResponse::ok(Token {
+        token: issue_token(&claims, &security.jwt_secret)?,
+        token_type: "Bearer",
+        expires_in: security.jwt_lifetime,
+    }))
+}
@@ -0,0 +1,112 @@
+//! `/categories` endpoints, categories being curated by editors
+
+use rocket::serde::json::Json;
+use rocket::Route;
+use rocket_db_pools::Connection;
+use serde::Deserialize;
+
+use crate::api::ApiResponse;
+use crate::app::core::database::models::{Category, CategoryChanges, NewCategory};
+use crate::app::core::validation::check_term;
+use crate::database::DbConnection;
+use crate::result::{ensure_valid, Error, Result};
+use crate::security::{roles::Editor, Authorized};
+
+pub fn collect() -> Vec<Route> {
+    routes![list, get, create, update, delete]
+}
+
+/* ------------------------------------------ Payloads ----------------------------------------- */
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCategory {
+    pub name: String,
+    pub slug: Option<String>,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateCategory {
+    pub name: Option<String>,
+    pub slug: Option<String>,
+    pub description: Option<String>,
+}
+
+/* ------------------------------------------- Routes ------------------------------------------ */
+
+#[get("/categories")]
+async fn list(mut db: Connection<DbConnection>) -> Result<'static, ApiResponse<Vec<Category>>> {
+    Ok(ApiResponse::ok(Category::list(&mut db).await?))
+}
+
+#[get("/categories/<slug>")]
+async fn get(
+    mut db: Connection<DbConnection>,
+    slug: &str,
+) -> Result<'static, ApiResponse<Category>> {
+    Ok(ApiResponse::ok(
+        Category::find_by_slug(&mut db, slug).await?,
+    ))
+}
+
+#[post("/categories", data = "<payload>")]
+async fn create(
+    mut db: Connection<DbConnection>,
+    _editor: Authorized<Editor>,
+    payload: Json<CreateCategory>,
+) -> Result<'static, ApiResponse<Category>> {
+    let slug = payload
+        .slug
+        .clone()
+        .unwrap_or_else(|| slug::slugify(&payload.name));
+    ensure_valid(check_term(Some(&slug), Some(&payload.name)))?;
+
+    let category = Category::create(
+        &mut db,
+        &NewCategory {
+            slug: &slug,
+            name: &payload.name,
+            description: &payload.description,
+        },
+    )
+    .await?;
+    Ok(ApiResponse::created(category))
+}
+
+/// Renaming a category follows its slug, unless another one is given
+#[patch("/categories/<slug>", data = "<payload>")]
+async fn update(
+    mut db: Connection<DbConnection>,
+    _editor: Authorized<Editor>,
+    slug: &str,
+    payload: Json<UpdateCategory>,
+) -> Result<'static, ApiResponse<Category>> {
+    let new_slug = match (&payload.slug, &payload.name) {
+        (Some(slug), _) => Some(slug.clone()),
+        (None, Some(name)) => Some(slug::slugify(name)),
+        (None, None) => None,
+    };
+    ensure_valid(check_term(new_slug.as_deref(), payload.name.as_deref()))?;
+
+    let changes = CategoryChanges {
+        slug: new_slug.as_deref(),
+        name: payload.name.as_deref(),
+        description: payload.description.as_deref(),
+    };
+    Ok(ApiResponse::ok(
+        Category::update(&mut db, slug, &changes).await?,
+    ))
+}
+
+#[delete("/categories/<slug>")]
+async fn delete(
+    mut db: Connection<DbConnection>,
+    _editor: Authorized<Editor>,
+    slug: &str,
+) -> Result<'static, ApiResponse<()>> {
+    match Category::delete(&mut db, slug).await? {
+        0 => Err(Error::NotFound),
+        _ => Ok(ApiResponse::no_content()),
+    }
+}
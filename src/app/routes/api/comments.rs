@@ -0,0 +1,185 @@
+//! `/posts/<id>/comments` endpoints open to readers, `/comments` moderation
+//! endpoints reserved to administrators
+
+use diesel::OptionalExtension;
+use rocket::serde::json::Json;
+use rocket::{Route, State};
+use rocket_db_pools::diesel::scoped_futures::ScopedFutureExt;
+use rocket_db_pools::diesel::{AsyncConnection, AsyncPgConnection};
+use rocket_db_pools::Connection;
+use serde::Deserialize;
+
+use crate::api::{ApiResponse, Pagination};
+use crate::app::core::comments::{self, Thread};
+use crate::app::core::database::models::{
+    Comment, CommentStatus, NewComment, Post, PostStatus, User,
+};
+use crate::app::core::spam;
+use crate::app::core::validation::check_comment;
+use crate::config::Config;
+use crate::database::DbConnection;
+use crate::result::{ensure_valid, Error, FieldError, Result};
+use crate::security::{roles::Admin, AuthenticatedUser, Authorized};
+
+/// Most comments moderated at once
+const MAX_MODERATED: usize = 100;
+
+pub fn collect() -> Vec<Route> {
+    routes![list, create, queue, moderate]
+}
+
+/* ------------------------------------------ Payloads ----------------------------------------- */
+
+#[derive(Debug, Deserialize)]
+pub struct CreateComment {
+    /// Comment replied to, if any
+    pub parent_id: Option<i32>,
+    /// Name of an anonymous author, authenticated ones using their display name
+    pub author_name: Option<String>,
+    /// Never shown, only available to anonymous authors
+    pub author_email: Option<String>,
+    pub body: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Moderate {
+    pub ids: Vec<i32>,
+    pub status: CommentStatus,
+}
+
+/* ------------------------------------------- Routes ------------------------------------------ */
+
+/// Comments only exist on published posts, as long as they are enabled
+async fn find_post<'a>(conn: &mut AsyncPgConnection, config: &Config, id: i32) -> Result<'a, Post> {
+    let post = Post::find(conn, id).await?;
+    match config.comments.enabled && post.status == PostStatus::Published {
+        true => Ok(post),
+        false => Err(Error::NotFound),
+    }
+}
+
+#[get("/posts/<id>/comments")]
+async fn list(
+    mut db: Connection<DbConnection>,
+    config: &State<Config>,
+    id: i32,
+) -> Result<'static, ApiResponse<Vec<Thread>>> {
+    let post = find_post(&mut db, config, id).await?;
+    let approved = Comment::for_post(&mut db, post.id, CommentStatus::Approved).await?;
+    Ok(ApiResponse::ok(comments::thread(approved)))
+}
+
+#[post("/posts/<id>/comments", data = "<payload>")]
+async fn create(
+    mut db: Connection<DbConnection>,
+    config: &State<Config>,
+    user: Option<AuthenticatedUser>,
+    id: i32,
+    payload: Json<CreateComment>,
+) -> Result<'static, ApiResponse<Comment>> {
+    let post = find_post(&mut db, config, id).await?;
+    if !comments::accepts_comments(&config.comments, &post) {
+        return Err(Error::Forbidden);
+    }
+
+    let author = match &user {
+        Some(user) => Some(User::find_by_username(&mut db, user.username()).await?),
+        None if config.comments.anonymous => None,
+        None => return Err(Error::Unauthorized("Authentication required")),
+    };
+    let (author_name, author_email) = match &author {
+        Some(author) => (Some(author.display_name.as_str()), None),
+        None => (
+            payload.author_name.as_deref().map(str::trim),
+            payload.author_email.as_deref().map(str::trim),
+        ),
+    };
+    let mut errors = check_comment(author_name, author_email, &payload.body);
+    if author_name.is_none() {
+        errors.push(FieldError::new("author_name", "is required"));
+    }
+    ensure_valid(errors)?;
+
+    // Replies are only allowed to visible comments of the same post
+    if let Some(parent_id) = payload.parent_id {
+        let parent = Comment::find(&mut db, parent_id).await.optional()?;
+        if !parent.is_some_and(|p| p.post_id == post.id && p.status == CommentStatus::Approved) {
+            ensure_valid(vec![FieldError::new(
+                "parent_id",
+                "is not a comment of the post",
+            )])?;
+        }
+    }
+
+    let author_name = author_name.unwrap_or_default();
+    let body = payload.body.trim();
+    let spam_score = spam::score(&mut db, &spam::tokens(author_name, author_email, body)).await?;
+
+    let new_comment = NewComment {
+        post_id: post.id,
+        parent_id: payload.parent_id,
+        author: user.as_ref().map(AuthenticatedUser::username),
+        author_name,
+        author_email,
+        body,
+        status: comments::initial_status(
+            &config.comments,
+            user.as_ref().map(|u| u.role()),
+            spam_score,
+        ),
+        spam_score,
+        created_at: None,
+        updated_at: None,
+    };
+    Ok(ApiResponse::created(
+        Comment::create(&mut db, &new_comment).await?,
+    ))
+}
+
+/// Moderation queue, pending comments by default
+#[get("/comments?<status>&<pagination..>")]
+async fn queue(
+    mut db: Connection<DbConnection>,
+    _admin: Authorized<Admin>,
+    status: Option<&str>,
+    pagination: Pagination,
+) -> Result<'static, ApiResponse<Vec<Comment>>> {
+    let status = status
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(CommentStatus::Pending);
+    Ok(ApiResponse::ok(
+        Comment::list(&mut db, status, pagination).await?,
+    ))
+}
+
+/// Approve or reject comments in bulk, the spam filter learning from the
+/// approved and spam ones
+#[post("/comments/moderate", data = "<payload>")]
+async fn moderate(
+    mut db: Connection<DbConnection>,
+    _admin: Authorized<Admin>,
+    payload: Json<Moderate>,
+) -> Result<'static, ApiResponse<Vec<Comment>>> {
+    if payload.ids.is_empty() || payload.ids.len() > MAX_MODERATED {
+        ensure_valid(vec![FieldError::new(
+            "ids",
+            format!("must list 1 to {} comments", MAX_MODERATED),
+        )])?;
+    }
+    let (ids, status) = (&payload.ids, payload.status);
+
+    let moderated = db
+        .transaction(|conn| {
+            async move {
+                if status != CommentStatus::Deleted {
+                    for comment in Comment::find_all(conn, ids).await? {
+                        spam::train(conn, &comment, spam::label(status)).await?;
+                    }
+                }
+                Comment::set_status(conn, ids, status).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+    Ok(ApiResponse::ok(moderated))
+}
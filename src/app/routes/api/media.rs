@@ -0,0 +1,117 @@
+//! `/media` endpoint
+
+use std::panic;
+
+use diesel::OptionalExtension;
+use rocket::form::Form;
+use rocket::fs::TempFile;
+use rocket::tokio::io::AsyncReadExt;
+use rocket::tokio::task;
+use rocket::{Route, State};
+use rocket_db_pools::Connection;
+
+use crate::api::ApiResponse;
+use crate::app::core::database::models::{Media, NewMedia};
+use crate::app::core::metadata;
+use crate::app::core::rendition::ImageFormat;
+use crate::app::core::storage::{self, Storage};
+use crate::config::Config;
+use crate::database::DbConnection;
+use crate::result::{ensure_valid, Error, FieldError, Result, StorageError};
+use crate::security::{roles::Author, Authorized};
+
+pub fn collect() -> Vec<Route> {
+    routes![upload]
+}
+
+/* ------------------------------------------ Payloads ----------------------------------------- */
+
+/// `multipart/form-data` upload, its size being bounded by the `file/<extension>`
+/// limit of its MIME type
+#[derive(Debug, FromForm)]
+pub struct Upload<'r> {
+    pub file: TempFile<'r>,
+}
+
+/* ------------------------------------------- Routes ------------------------------------------ */
+
+/// Name of the uploaded file without its directories, kept for the record only
+fn file_name(file: &TempFile<'_>) -> Option<String> {
+    let raw = file.raw_name()?.dangerous_unsafe_unsanitized_raw().as_str();
+    let name = raw.rsplit(['/', '\\']).next()?.trim();
+    match name.is_empty() {
+        true => None,
+        false => Some(name.chars().take(255).collect()),
+    }
+}
+
+/// Store the file, stripped of its metadata if it is an image, unless the same
+/// content was uploaded before, in which case the existing media is returned
+#[post("/media", data = "<upload>")]
+async fn upload(
+    mut db: Connection<DbConnection>,
+    config: &State<Config>,
+    storage: &State<Box<dyn Storage>>,
+    author: Authorized<Author>,
+    upload: Form<Upload<'_>>,
+) -> Result<'static, ApiResponse<Media>> {
+    let file = &upload.file;
+    let mime_type = file
+        .content_type()
+        .map(|ct| format!("{}/{}", ct.top(), ct.sub()).to_lowercase())
+        .unwrap_or_default();
+    let mut errors = Vec::new();
+    if !config.media.types.contains(&mime_type) {
+        errors.push(FieldError::new(
+            "file",
+            format!("must be one of {}", config.media.types.join(", ")),
+        ));
+    }
+    if file.len() == 0 {
+        errors.push(FieldError::new("file", "must not be empty"));
+    }
+    ensure_valid(errors)?;
+
+    let mut data = Vec::new();
+    file.open()
+        .await
+        .map_err(StorageError::from)?
+        .read_to_end(&mut data)
+        .await
+        .map_err(StorageError::from)?;
+    let mut preview = None;
+    if ImageFormat::of_original(&mime_type).is_some() {
+        let (mime, keep) = (mime_type.clone(), config.media.keep_metadata.clone());
+        let processed = task::spawn_blocking(move || metadata::process(&data, &mime, &keep))
+            .await
+            .unwrap_or_else(|e| panic::resume_unwind(e.into_panic()));
+        let Ok((stripped, image)) = processed else {
+            return Err(Error::Validation(vec![FieldError::new(
+                "file",
+                "must be a valid image",
+            )]));
+        };
+        (data, preview) = (stripped, Some(image));
+    }
+    let hash = storage::hash(&data);
+    if let Some(existing) = Media::find_by_hash(&mut db, &hash).await.optional()? {
+        return Ok(ApiResponse::ok(existing));
+    }
+
+    storage.put(&hash, &mime_type, &data).await?;
+    let file_name = file_name(file).unwrap_or_else(|| hash.clone());
+    let new_media = NewMedia {
+        hash: &hash,
+        owner: author.user.username(),
+        file_name: &file_name,
+        mime_type: &mime_type,
+        size: data.len() as i64,
+        width: preview.as_ref().map(|preview| preview.width as i32),
+        height: preview.as_ref().map(|preview| preview.height as i32),
+        blurhash: preview.as_ref().map(|preview| preview.blurhash.as_str()),
+        color: preview.as_ref().map(|preview| preview.color.as_str()),
+    };
+    Ok(ApiResponse::created(
+        Media::create(&mut db, &new_media).await?,
+    ))
+}
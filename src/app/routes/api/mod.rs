@@ -1 +1,172 @@
+//! # API
+//!
+//! JSON REST API consumed by front ends, mounted under [BASE].
 
+mod auth;
+mod categories;
+mod comments;
+mod media;
+mod posts;
+mod revisions;
+mod search;
+mod tags;
+mod users;
+
+use rocket::{Catcher, Request, Route};
+
+use crate::result::{Error as ApiError, FieldError};
+use crate::security::AuthFailure;
+
+pub const BASE: &str = "/api/v1";
+
+pub fn collect() -> Vec<Route> {
+    let mut routes = auth::collect();
+    routes.extend(categories::collect());
+    routes.extend(comments::collect());
+    routes.extend(media::collect());
+    routes.extend(posts::collect());
+    routes.extend(revisions::collect());
+    routes.extend(search::collect());
+    routes.extend(tags::collect());
+    routes.extend(users::collect());
+    routes
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    catchers![
+        malformed,
+        unauthorized,
+        forbidden,
+        not_found,
+        not_acceptable,
+        payload_too_large,
+        unprocessable
+    ]
+}
+
+/* ------------------------------------------ Catchers ----------------------------------------- */
+
+#[catch(400)]
+fn malformed() -> ApiError<'static> {
+    ApiError::Malformed("The request cannot be parsed")
+}
+
+#[catch(401)]
+fn unauthorized(req: &Request) -> ApiError<'static> {
+    let AuthFailure(reason) = req.local_cache(AuthFailure::default);
+    ApiError::Unauthorized(reason.unwrap_or("Authentication required"))
+}
+
+#[catch(403)]
+fn forbidden() -> ApiError<'static> {
+    ApiError::Forbidden
+}
+
+#[catch(404)]
+fn not_found() -> ApiError<'static> {
+    ApiError::NotFound
+}
+
+#[catch(406)]
+fn not_acceptable() -> ApiError<'static> {
+    ApiError::NotAcceptable
+}
+
+#[catch(413)]
+fn payload_too_large() -> ApiError<'static> {
+    ApiError::PayloadTooLarge
+}
+
+#[catch(422)]
+fn unprocessable() -> ApiError<'static> {
+    ApiError::Validation(vec![FieldError::new(
+        "body",
+        "does not match the expected schema",
+    )])
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use figment::providers::Serialized;
+    use figment::Figment;
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::blocking::Client;
+    use rocket_db_pools::Database;
+
+    use super::BASE;
+    use crate::api::Pagination;
+    use crate::app::blog;
+    use crate::config::Config;
+    use crate::database::DbConnection;
+    use crate::security::{issue_token, Claims, Role};
+
+    /// Paginated route of the API which needs no database
+    #[get("/pages?<pagination..>")]
+    fn pages(pagination: Pagination) -> String {
+        pagination.page.to_string()
+    }
+
+    /// Whole blog, its database being connected to on the first request
+    /// needing it
+    fn client(database_url: &str) -> Client {
+        let config = Config::default();
+        let figment = Figment::from(rocket::Config::default())
+            .merge(Serialized::defaults(&config))
+            .merge((
+                format!("databases.{}.url", DbConnection::NAME),
+                database_url,
+            ));
+        Client::tracked(blog(&figment, &config).unwrap().mount(BASE, routes![pages])).unwrap()
+    }
+
+    fn assert_unprocessable(client: &Client, path: &str) {
+        let response = client.get(format!("{}{}", BASE, path)).dispatch();
+        assert_eq!(response.status(), Status::UnprocessableEntity, "{}", path);
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("application", "problem+json"))
+        );
+    }
+
+    #[test]
+    fn forwarded_validation() {
+        let client = client(&Config::default().database.to_string());
+        for path in ["/pages?page=0", "/pages?page=abc", "/pages?per_page=500"] {
+            assert_unprocessable(&client, path);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs the migrated database of POLAR_TEST_DATABASE_URL"]
+    fn posts_pagination() {
+        let client = client(&std::env::var("POLAR_TEST_DATABASE_URL").unwrap());
+        for path in ["/posts?page=0", "/posts?per_page=500", "/posts?page=abc"] {
+            assert_unprocessable(&client, path);
+        }
+
+        // Pages forwarded the same way are not found
+        for path in ["/feed.xml", "/tags/rust/feed.xml"] {
+            let response = client.get(path).dispatch();
+            assert_eq!(response.status(), Status::NotFound, "{}", path);
+            assert_eq!(response.content_type(), Some(ContentType::HTML));
+        }
+    }
+
+    #[test]
+    #[ignore = "needs the migrated database of POLAR_TEST_DATABASE_URL"]
+    fn unknown_accounts() {
+        let client = client(&std::env::var("POLAR_TEST_DATABASE_URL").unwrap());
+        let claims = Claims::new("nobody", Role::Admin, 60);
+        let token = issue_token(&claims, &Config::default().security.jwt_secret).unwrap();
+
+        // Tokens outlive the accounts they were issued to
+        let response = client
+            .get(format!("{}/users", BASE))
+            .header(Header::new("Authorization", format!("Bearer {}", token)))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        assert!(response.into_string().unwrap().contains("Disabled account"));
+    }
+}
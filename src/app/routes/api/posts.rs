@@ -0,0 +1,314 @@
+//! `/posts` endpoints
+
+use chrono::{DateTime, Utc};
+use diesel::QueryResult;
+use rocket::serde::json::Json;
+use rocket::{Route, State};
+use rocket_db_pools::diesel::scoped_futures::ScopedFutureExt;
+use rocket_db_pools::diesel::{AsyncConnection, AsyncPgConnection};
+use rocket_db_pools::Connection;
+use serde::Deserialize;
+
+use crate::api::{ApiResponse, Pagination};
+use crate::app::core::database::models::{
+    Category, NewPost, NewTag, Post, PostChanges, PostDetails, PostFilter, PostRevision,
+    PostStatus, Tag, Visibility,
+};
+use crate::app::core::lifecycle::Lifecycle;
+use crate::app::core::markdown;
+use crate::app::core::validation::{check_post, check_terms};
+use crate::config::Config;
+use crate::database::DbConnection;
+use crate::result::{ensure_valid, Error, FieldError, Result};
+use crate::security::{roles::Author, AuthenticatedUser, Authorized, Role};
+
+pub fn collect() -> Vec<Route> {
+    routes![list, get, create, update, delete]
+}
+
+/* ------------------------------------------ Payloads ----------------------------------------- */
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePost {
+    pub slug: Option<String>,
+    pub title: String,
+    #[serde(default)]
+    pub body_markdown: String,
+    pub status: Option<PostStatus>,
+    /// Publication date, required to schedule the post
+    pub publish_at: Option<DateTime<Utc>>,
+    /// Names of the tags of the post, missing ones being created
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Slugs of the categories of the post
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// Whether readers may comment the post, defaults to true
+    pub comments_enabled: Option<bool>,
+    /// Text search configuration the post is indexed with, defaults to the
+    /// configured one
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdatePost {
+    pub slug: Option<String>,
+    pub title: Option<String>,
+    pub body_markdown: Option<String>,
+    pub status: Option<PostStatus>,
+    pub publish_at: Option<DateTime<Utc>>,
+    pub tags: Option<Vec<String>>,
+    pub categories: Option<Vec<String>>,
+    pub comments_enabled: Option<bool>,
+    pub language: Option<String>,
+}
+
+/* ------------------------------------------- Routes ------------------------------------------ */
+
+/// Authors may only alter their own posts, editors and admins anyone's
+pub(super) fn ensure_can_edit<'a>(user: &AuthenticatedUser, post: &Post) -> Result<'a, ()> {
+    match user.owns_or_is(&post.author, Role::Editor) {
+        true => Ok(()),
+        false => Err(Error::Forbidden),
+    }
+}
+
+/// Unpublished posts only exist for their author and editors
+fn visibility(user: Option<&AuthenticatedUser>) -> Visibility<'_> {
+    match user {
+        Some(user) if user.role() >= Role::Editor => Visibility::All,
+        Some(user) => Visibility::Own(user.username()),
+        None => Visibility::Public,
+    }
+}
+
+fn is_visible(post: &Post, user: Option<&AuthenticatedUser>) -> bool {
+    post.status == PostStatus::Published
+        || user.is_some_and(|user| user.owns_or_is(&post.author, Role::Editor))
+}
+
+/// Fetch the categories of the given slugs, failing if any is unknown
+async fn find_categories<'a>(
+    conn: &mut AsyncPgConnection,
+    slugs: &[String],
+) -> Result<'a, Vec<Category>> {
+    let categories = Category::find_all_by_slug(conn, slugs).await?;
+    let errors = slugs
+        .iter()
+        .filter(|slug| !categories.iter().any(|category| &category.slug == *slug))
+        .map(|slug| FieldError::new("categories", format!("has no category {}", slug)))
+        .collect();
+    ensure_valid(errors)?;
+    Ok(categories)
+}
+
+/// Fail unless the text search configuration of the given name exists
+async fn check_language<'a>(conn: &mut AsyncPgConnection, language: &str) -> Result<'a, ()> {
+    let errors = match Post::language_exists(conn, language).await? {
+        true => Vec::new(),
+        false => vec![FieldError::new(
+            "language",
+            format!("has no text search configuration {}", language),
+        )],
+    };
+    ensure_valid(errors)
+}
+
+/// Replace the tags of a post by the ones of the given names
+async fn set_tags(conn: &mut AsyncPgConnection, post_id: i32, names: &[String]) -> QueryResult<()> {
+    let slugs: Vec<String> = names.iter().map(slug::slugify).collect();
+    let new_tags: Vec<NewTag> = slugs
+        .iter()
+        .zip(names)
+        .map(|(slug, name)| NewTag {
+            slug,
+            name: name.trim(),
+        })
+        .collect();
+    let tags = Tag::ensure(conn, &new_tags).await?;
+    Tag::set_for_post(conn, post_id, &tags).await
+}
+
+#[get("/posts?<status>&<author>&<tag>&<category>&<pagination..>")]
+async fn list(
+    mut db: Connection<DbConnection>,
+    user: Option<AuthenticatedUser>,
+    status: Option<&str>,
+    author: Option<&str>,
+    tag: Option<&str>,
+    category: Option<&str>,
+    pagination: Pagination,
+) -> Result<'static, ApiResponse<Vec<PostDetails>>> {
+    let status = status
+        .map(str::parse::<PostStatus>)
+        .transpose()
+        .map_err(|_| {
+            Error::Validation(vec![FieldError::new(
+                "status",
+                "must be one of draft, scheduled, published or archived",
+            )])
+        })?;
+    let filter = PostFilter {
+        visibility: visibility(user.as_ref()),
+        status,
+        author,
+        tag,
+        category,
+    };
+    let posts = Post::list(&mut db, &filter, pagination).await?;
+    Ok(ApiResponse::ok(PostDetails::load(&mut db, posts).await?))
+}
+
+#[get("/posts/<id>")]
+async fn get(
+    mut db: Connection<DbConnection>,
+    user: Option<AuthenticatedUser>,
+    id: i32,
+) -> Result<'static, ApiResponse<PostDetails>> {
+    let post = Post::find(&mut db, id).await?;
+    if !is_visible(&post, user.as_ref()) {
+        return Err(Error::NotFound);
+    }
+    Ok(ApiResponse::ok(PostDetails::load_one(&mut db, post).await?))
+}
+
+#[post("/posts", data = "<payload>")]
+async fn create(
+    mut db: Connection<DbConnection>,
+    config: &State<Config>,
+    author: Authorized<Author>,
+    payload: Json<CreatePost>,
+) -> Result<'static, ApiResponse<PostDetails>> {
+    let slug = payload
+        .slug
+        .clone()
+        .unwrap_or_else(|| slug::slugify(&payload.title));
+    let mut errors = check_post(Some(&slug), Some(&payload.title));
+    errors.extend(check_terms("tags", &payload.tags));
+    ensure_valid(errors)?;
+    let language = payload
+        .language
+        .as_deref()
+        .unwrap_or(&config.search.language);
+    check_language(&mut db, language).await?;
+    let categories = find_categories(&mut db, &payload.categories).await?;
+    let lifecycle = Lifecycle::default().next(payload.status, payload.publish_at, Utc::now())?;
+    let body_html = markdown::render(&payload.body_markdown);
+
+    let new_post = NewPost {
+        slug: &slug,
+        title: &payload.title,
+        body_markdown: &payload.body_markdown,
+        body_html: &body_html,
+        status: lifecycle.status,
+        author: author.user.username(),
+        published_at: lifecycle.published_at,
+        publish_at: lifecycle.publish_at,
+        comments_enabled: payload.comments_enabled.unwrap_or(true),
+        language,
+        created_at: None,
+        updated_at: None,
+    };
+    let (new_post, tags, categories) = (&new_post, &payload.tags, &categories);
+
+    let details = db
+        .transaction(|conn| {
+            async move {
+                let post = Post::create(conn, new_post).await?;
+                set_tags(conn, post.id, tags).await?;
+                Category::set_for_post(conn, post.id, categories).await?;
+                PostDetails::load_one(conn, post).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+    Ok(ApiResponse::created(details))
+}
+
+#[patch("/posts/<id>", data = "<payload>")]
+async fn update(
+    mut db: Connection<DbConnection>,
+    author: Authorized<Author>,
+    id: i32,
+    payload: Json<UpdatePost>,
+) -> Result<'static, ApiResponse<PostDetails>> {
+    let mut errors = check_post(payload.slug.as_deref(), payload.title.as_deref());
+    errors.extend(check_terms(
+        "tags",
+        payload.tags.as_deref().unwrap_or_default(),
+    ));
+    ensure_valid(errors)?;
+    let current = Post::find(&mut db, id).await?;
+    ensure_can_edit(&author.user, &current)?;
+    if let Some(language) = &payload.language {
+        check_language(&mut db, language).await?;
+    }
+    let categories = match &payload.categories {
+        Some(slugs) => Some(find_categories(&mut db, slugs).await?),
+        None => None,
+    };
+    let lifecycle =
+        Lifecycle::from(&current).next(payload.status, payload.publish_at, Utc::now())?;
+    let body_html = payload.body_markdown.as_deref().map(markdown::render);
+
+    let changes = PostChanges {
+        slug: payload.slug.as_deref(),
+        title: payload.title.as_deref(),
+        body_markdown: payload.body_markdown.as_deref(),
+        body_html: body_html.as_deref(),
+        status: Some(lifecycle.status),
+        published_at: Some(lifecycle.published_at),
+        publish_at: Some(lifecycle.publish_at),
+        comments_enabled: payload.comments_enabled,
+        language: payload.language.as_deref(),
+    };
+    // Only changes to the content of a post are worth a revision
+    let revised = payload
+        .slug
+        .as_ref()
+        .is_some_and(|slug| *slug != current.slug)
+        || payload
+            .title
+            .as_ref()
+            .is_some_and(|title| *title != current.title)
+        || payload
+            .body_markdown
+            .as_ref()
+            .is_some_and(|body| *body != current.body_markdown);
+    let (current, changes, tags, categories) = (&current, &changes, &payload.tags, &categories);
+    let username = author.user.username();
+
+    let details = db
+        .transaction(|conn| {
+            async move {
+                if revised {
+                    PostRevision::snapshot(conn, current, username).await?;
+                }
+                let post = Post::update(conn, id, changes).await?;
+                if let Some(tags) = tags {
+                    set_tags(conn, post.id, tags).await?;
+                }
+                if let Some(categories) = categories {
+                    Category::set_for_post(conn, post.id, categories).await?;
+                }
+                PostDetails::load_one(conn, post).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+    Ok(ApiResponse::ok(details))
+}
+
+#[delete("/posts/<id>")]
+async fn delete(
+    mut db: Connection<DbConnection>,
+    author: Authorized<Author>,
+    id: i32,
+) -> Result<'static, ApiResponse<()>> {
+    let post = Post::find(&mut db, id).await?;
+    ensure_can_edit(&author.user, &post)?;
+    match Post::delete(&mut db, id).await? {
+        0 => Err(Error::NotFound),
+        _ => Ok(ApiResponse::no_content()),
+    }
+}
@@ -0,0 +1,127 @@
+//! `/posts/<id>/revisions` endpoints, open to whoever may edit the post
+
+use rocket::Route;
+use rocket_db_pools::diesel::scoped_futures::ScopedFutureExt;
+use rocket_db_pools::diesel::AsyncConnection;
+use rocket_db_pools::Connection;
+use serde::Serialize;
+
+use super::posts::ensure_can_edit;
+use crate::api::ApiResponse;
+use crate::app::core::database::models::{Post, PostChanges, PostDetails, PostRevision};
+use crate::app::core::markdown;
+use crate::app::core::revision::{diff as unified_diff, Version};
+use crate::database::DbConnection;
+use crate::result::Result;
+use crate::security::{roles::Author, Authorized};
+
+pub fn collect() -> Vec<Route> {
+    routes![list, diff, get, restore]
+}
+
+/* ------------------------------------------ Payloads ----------------------------------------- */
+
+#[derive(Debug, Serialize)]
+pub struct RevisionDiff {
+    /// Older revision, or none for the current version
+    pub from: Option<i32>,
+    /// Newer revision, or none for the current version
+    pub to: Option<i32>,
+    /// Unified diff from the older to the newer version
+    pub diff: String,
+}
+
+/* ------------------------------------------- Routes ------------------------------------------ */
+
+#[get("/posts/<id>/revisions")]
+async fn list(
+    mut db: Connection<DbConnection>,
+    author: Authorized<Author>,
+    id: i32,
+) -> Result<'static, ApiResponse<Vec<PostRevision>>> {
+    let post = Post::find(&mut db, id).await?;
+    ensure_can_edit(&author.user, &post)?;
+    Ok(ApiResponse::ok(PostRevision::list(&mut db, id).await?))
+}
+
+#[get("/posts/<id>/revisions/<revision>")]
+async fn get(
+    mut db: Connection<DbConnection>,
+    author: Authorized<Author>,
+    id: i32,
+    revision: i32,
+) -> Result<'static, ApiResponse<PostRevision>> {
+    let post = Post::find(&mut db, id).await?;
+    ensure_can_edit(&author.user, &post)?;
+    Ok(ApiResponse::ok(
+        PostRevision::find(&mut db, id, revision).await?,
+    ))
+}
+
+/// Diff between two revisions, either one defaulting to the current version
+#[get("/posts/<id>/revisions/diff?<from>&<to>")]
+async fn diff(
+    mut db: Connection<DbConnection>,
+    author: Authorized<Author>,
+    id: i32,
+    from: Option<i32>,
+    to: Option<i32>,
+) -> Result<'static, ApiResponse<RevisionDiff>> {
+    let post = Post::find(&mut db, id).await?;
+    ensure_can_edit(&author.user, &post)?;
+    let old = match from {
+        Some(revision) => Some(PostRevision::find(&mut db, id, revision).await?),
+        None => None,
+    };
+    let new = match to {
+        Some(revision) => Some(PostRevision::find(&mut db, id, revision).await?),
+        None => None,
+    };
+
+    let name = |revision: Option<i32>| match revision {
+        Some(revision) => format!("revision {}", revision),
+        None => "current".to_string(),
+    };
+    let diff = unified_diff(
+        old.as_ref().map_or(Version::from(&post), Version::from),
+        &name(from),
+        new.as_ref().map_or(Version::from(&post), Version::from),
+        &name(to),
+    );
+    Ok(ApiResponse::ok(RevisionDiff { from, to, diff }))
+}
+
+/// Make a revision the current version, the replaced one becoming a revision
+#[post("/posts/<id>/revisions/<revision>/restore")]
+async fn restore(
+    mut db: Connection<DbConnection>,
+    author: Authorized<Author>,
+    id: i32,
+    revision: i32,
+) -> Result<'static, ApiResponse<PostDetails>> {
+    let current = Post::find(&mut db, id).await?;
+    ensure_can_edit(&author.user, &current)?;
+    let restored = PostRevision::find(&mut db, id, revision).await?;
+    let body_html = markdown::render(&restored.body_markdown);
+
+    let changes = PostChanges {
+        slug: Some(&restored.slug),
+        title: Some(&restored.title),
+        body_markdown: Some(&restored.body_markdown),
+        body_html: Some(&body_html),
+        ..PostChanges::default()
+    };
+    let (current, changes, username) = (&current, &changes, author.user.username());
+
+    let details = db
+        .transaction(|conn| {
+            async move {
+                PostRevision::snapshot(conn, current, username).await?;
+                let post = Post::update(conn, id, changes).await?;
+                PostDetails::load_one(conn, post).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+    Ok(ApiResponse::ok(details))
+}
@@ -0,0 +1,38 @@
+//! `/search` endpoint
+
+use rocket::Route;
+use rocket_db_pools::Connection;
+
+use crate::api::{ApiResponse, Pagination};
+use crate::app::core::search::{self, SearchResult, MAX_QUERY_LENGTH};
+use crate::database::DbConnection;
+use crate::result::{Error, FieldError, Result};
+
+pub fn collect() -> Vec<Route> {
+    routes![results]
+}
+
+/* ------------------------------------------- Routes ------------------------------------------ */
+
+#[get("/search?<q>&<pagination..>")]
+async fn results(
+    mut db: Connection<DbConnection>,
+    q: Option<&str>,
+    pagination: Pagination,
+) -> Result<'static, ApiResponse<Vec<SearchResult>>> {
+    let q = q.unwrap_or_default();
+    if q.chars().count() > MAX_QUERY_LENGTH {
+        return Err(Error::Validation(vec![FieldError::new(
+            "q",
+            format!("must be at most {} characters long", MAX_QUERY_LENGTH),
+        )]));
+    }
+    let query = search::to_tsquery(q).ok_or_else(|| {
+        Error::Validation(vec![FieldError::new(
+            "q",
+            "must contain a word to search for",
+        )])
+    })?;
+    let results = search::search(&mut db, &query, pagination).await?;
+    Ok(ApiResponse::ok(results))
+}
@@ -0,0 +1,124 @@
+//! `/tags` endpoints, tags being created on the fly when attached to posts and
+//! otherwise managed by editors
+
+use rocket::serde::json::Json;
+use rocket::Route;
+use rocket_db_pools::Connection;
+use serde::Deserialize;
+
+use crate::api::ApiResponse;
+use crate::app::core::database::models::{NewTag, Tag, TagChanges};
+use crate::app::core::validation::check_term;
+use crate::database::DbConnection;
+use crate::result::{ensure_valid, Error, FieldError, Result};
+use crate::security::{roles::Editor, Authorized};
+
+pub fn collect() -> Vec<Route> {
+    routes![list, get, create, update, delete, merge]
+}
+
+/* ------------------------------------------ Payloads ----------------------------------------- */
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTag {
+    pub name: String,
+    pub slug: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateTag {
+    pub name: Option<String>,
+    pub slug: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeTag {
+    /// Slug of the tag absorbing the merged one
+    pub into: String,
+}
+
+/* ------------------------------------------- Routes ------------------------------------------ */
+
+#[get("/tags")]
+async fn list(mut db: Connection<DbConnection>) -> Result<'static, ApiResponse<Vec<Tag>>> {
+    Ok(ApiResponse::ok(Tag::list(&mut db).await?))
+}
+
+#[get("/tags/<slug>")]
+async fn get(mut db: Connection<DbConnection>, slug: &str) -> Result<'static, ApiResponse<Tag>> {
+    Ok(ApiResponse::ok(Tag::find_by_slug(&mut db, slug).await?))
+}
+
+#[post("/tags", data = "<payload>")]
+async fn create(
+    mut db: Connection<DbConnection>,
+    _editor: Authorized<Editor>,
+    payload: Json<CreateTag>,
+) -> Result<'static, ApiResponse<Tag>> {
+    let slug = payload
+        .slug
+        .clone()
+        .unwrap_or_else(|| slug::slugify(&payload.name));
+    ensure_valid(check_term(Some(&slug), Some(&payload.name)))?;
+
+    let tag = Tag::create(
+        &mut db,
+        &NewTag {
+            slug: &slug,
+            name: &payload.name,
+        },
+    )
+    .await?;
+    Ok(ApiResponse::created(tag))
+}
+
+/// Renaming a tag follows its slug, unless another one is given
+#[patch("/tags/<slug>", data = "<payload>")]
+async fn update(
+    mut db: Connection<DbConnection>,
+    _editor: Authorized<Editor>,
+    slug: &str,
+    payload: Json<UpdateTag>,
+) -> Result<'static, ApiResponse<Tag>> {
+    let new_slug = match (&payload.slug, &payload.name) {
+        (Some(slug), _) => Some(slug.clone()),
+        (None, Some(name)) => Some(slug::slugify(name)),
+        (None, None) => None,
+    };
+    ensure_valid(check_term(new_slug.as_deref(), payload.name.as_deref()))?;
+
+    let changes = TagChanges {
+        slug: new_slug.as_deref(),
+        name: payload.name.as_deref(),
+    };
+    Ok(ApiResponse::ok(Tag::update(&mut db, slug, &changes).await?))
+}
+
+#[delete("/tags/<slug>")]
+async fn delete(
+    mut db: Connection<DbConnection>,
+    _editor: Authorized<Editor>,
+    slug: &str,
+) -> Result<'static, ApiResponse<()>> {
+    match Tag::delete(&mut db, slug).await? {
+        0 => Err(Error::NotFound),
+        _ => Ok(ApiResponse::no_content()),
+    }
+}
+
+/// Move all posts of a tag to another one, the merged tag being deleted
+#[post("/tags/<slug>/merge", data = "<payload>")]
+async fn merge(
+    mut db: Connection<DbConnection>,
+    _editor: Authorized<Editor>,
+    slug: &str,
+    payload: Json<MergeTag>,
+) -> Result<'static, ApiResponse<Tag>> {
+    if payload.into == slug {
+        let error = FieldError::new("into", "must differ from the merged tag");
+        return Err(Error::Validation(vec![error]));
+    }
+    Ok(ApiResponse::ok(
+        Tag::merge(&mut db, slug, &payload.into).await?,
+    ))
+}
@@ -0,0 +1,119 @@
+//! `/users` endpoints, reserved to administrators
+
+use rocket::serde::json::Json;
+use rocket::Route;
+use rocket_db_pools::Connection;
+use serde::Deserialize;
+
+use crate::api::ApiResponse;
+use crate::app::core::database::models::{NewUser, User, UserChanges};
+use crate::app::core::validation::check_user;
+use crate::database::DbConnection;
+use crate::result::{ensure_valid, Result};
+use crate::security::{hash_password, roles::Admin, Authorized, Role};
+
+pub fn collect() -> Vec<Route> {
+    routes![list, get, create, update]
+}
+
+/* ------------------------------------------ Payloads ----------------------------------------- */
+
+#[derive(Debug, Deserialize)]
+pub struct CreateUser {
+    pub username: String,
+    pub email: String,
+    pub password: String,
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub bio: String,
+    pub role: Option<Role>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateUser {
+    pub email: Option<String>,
+    pub password: Option<String>,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub role: Option<Role>,
+    pub disabled: Option<bool>,
+}
+
+/* ------------------------------------------- Routes ------------------------------------------ */
+
+#[get("/users")]
+async fn list(
+    mut db: Connection<DbConnection>,
+    _admin: Authorized<Admin>,
+) -> Result<'static, ApiResponse<Vec<User>>> {
+    Ok(ApiResponse::ok(User::list(&mut db).await?))
+}
+
+#[get("/users/<username>")]
+async fn get(
+    mut db: Connection<DbConnection>,
+    _admin: Authorized<Admin>,
+    username: &str,
+) -> Result<'static, ApiResponse<User>> {
+    Ok(ApiResponse::ok(
+        User::find_by_username(&mut db, username).await?,
+    ))
+}
+
+#[post("/users", data = "<payload>")]
+async fn create(
+    mut db: Connection<DbConnection>,
+    _admin: Authorized<Admin>,
+    payload: Json<CreateUser>,
+) -> Result<'static, ApiResponse<User>> {
+    ensure_valid(check_user(
+        Some(&payload.username),
+        Some(&payload.email),
+        Some(&payload.password),
+    ))?;
+    let password_hash = hash_password(&payload.password)?;
+    let email = payload.email.to_lowercase();
+    let user = User::create(
+        &mut db,
+        &NewUser {
+            username: &payload.username,
+            email: &email,
+            password_hash: &password_hash,
+            display_name: payload.display_name.as_deref().unwrap_or(&payload.username),
+            bio: &payload.bio,
+            role: payload.role.unwrap_or(Role::Reader),
+        },
+    )
+    .await?;
+    Ok(ApiResponse::created(user))
+}
+
+#[patch("/users/<username>", data = "<payload>")]
+async fn update(
+    mut db: Connection<DbConnection>,
+    _admin: Authorized<Admin>,
+    username: &str,
+    payload: Json<UpdateUser>,
+) -> Result<'static, ApiResponse<User>> {
+    ensure_valid(check_user(
+        None,
+        payload.email.as_deref(),
+        payload.password.as_deref(),
+    ))?;
+    let password_hash = match &payload.password {
+        Some(password) => Some(hash_password(password)?),
+        None => None,
+    };
+    let email = payload.email.as_ref().map(|email| email.to_lowercase());
+    let changes = UserChanges {
+        email: email.as_deref(),
+        password_hash: password_hash.as_deref(),
+        display_name: payload.display_name.as_deref(),
+        bio: payload.bio.as_deref(),
+        disabled: payload.disabled,
+        role: payload.role,
+    };
+    Ok(ApiResponse::ok(
+        User::update(&mut db, username, &changes).await?,
+    ))
+}
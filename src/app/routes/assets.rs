@@ -0,0 +1,21 @@
+//! `/static` stylesheets of the built-in theme, files of the configured theme
+//! directory being served in priority
+
+use rocket::http::ContentType;
+use rocket::{Route, State};
+
+use crate::app::core::theme::{Theme, STYLESHEET};
+
+pub fn collect() -> Vec<Route> {
+    routes![stylesheet, highlight]
+}
+
+#[get("/static/polar.css")]
+fn stylesheet() -> (ContentType, &'static str) {
+    (ContentType::CSS, STYLESHEET)
+}
+
+#[get("/static/highlight.css")]
+fn highlight(theme: &State<Theme>) -> (ContentType, &str) {
+    (ContentType::CSS, theme.highlight_css())
+}
@@ -0,0 +1,37 @@
+//! `/authors` pages
+
+use rocket::{Route, State};
+use rocket_db_pools::Connection;
+use tera::Context;
+
+use super::{fail, published, render, Author, Page, Paging};
+use crate::api::Pagination;
+use crate::app::core::database::models::{Post, PostDetails, User};
+use crate::app::core::theme::Theme;
+use crate::database::DbConnection;
+
+pub fn collect() -> Vec<Route> {
+    routes![get]
+}
+
+#[get("/authors/<username>?<pagination..>")]
+async fn get(
+    mut db: Connection<DbConnection>,
+    theme: &State<Theme>,
+    username: &str,
+    pagination: Pagination,
+) -> Page {
+    let author = User::find_by_username(&mut db, username)
+        .await
+        .map_err(fail)?;
+    let posts = Post::list(&mut db, &published(Some(username)), pagination)
+        .await
+        .map_err(fail)?;
+    let posts = PostDetails::load(&mut db, posts).await.map_err(fail)?;
+
+    let mut context = Context::new();
+    context.insert("author", &Author::from(&author));
+    context.insert("pagination", &Paging::of(pagination, &posts));
+    context.insert("posts", &posts);
+    render(theme, "author.html", &context)
+}
@@ -0,0 +1,76 @@
+//! `/sitemap.xml` and `/robots.txt` of search engine crawlers, sitemaps past
+//! the size limit being served under `/sitemaps`
+
+use rocket::http::{ContentType, Status};
+use rocket::request::FromParam;
+use rocket::{Route, State};
+use rocket_db_pools::Connection;
+
+use super::fail;
+use crate::app::core::database::models::{Post, Tag};
+use crate::app::core::sitemap::{self, Url};
+use crate::config::Config;
+use crate::database::DbConnection;
+
+pub fn collect() -> Vec<Route> {
+    routes![sitemap_root, sitemap_page, robots]
+}
+
+type Document = Result<(ContentType, String), Status>;
+
+/// Sitemaps past the first one are named `<number>.xml`
+struct SitemapNumber(usize);
+
+impl<'a> FromParam<'a> for SitemapNumber {
+    type Error = &'a str;
+
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        param
+            .strip_suffix(".xml")
+            .and_then(|number| number.parse().ok())
+            .map(SitemapNumber)
+            .ok_or(param)
+    }
+}
+
+/// Home page, published posts, and tags and authors of these
+async fn urls(db: &mut Connection<DbConnection>, config: &Config) -> Result<Vec<Url>, Status> {
+    let posts = Post::last_modified(db).await.map_err(fail)?;
+    let tags = Tag::last_modified(db).await.map_err(fail)?;
+    let authors = Post::last_modified_by_author(db).await.map_err(fail)?;
+    Ok(sitemap::urls(&config.site, &posts, &tags, &authors))
+}
+
+/// Sitemap of all URLs, or index of the sitemaps they are split into
+#[get("/sitemap.xml")]
+async fn sitemap_root(mut db: Connection<DbConnection>, config: &State<Config>) -> Document {
+    let urls = urls(&mut db, config).await?;
+    let xml = match sitemap::pages(&urls) {
+        1 => sitemap::urlset(&urls),
+        _ => sitemap::index(&urls, |n| {
+            config.site.url_of(&format!("/sitemaps/{}.xml", n))
+        }),
+    };
+    Ok((ContentType::XML, xml.map_err(fail)?))
+}
+
+#[get("/sitemaps/<number>")]
+async fn sitemap_page(
+    mut db: Connection<DbConnection>,
+    config: &State<Config>,
+    number: Result<SitemapNumber, &str>,
+) -> Document {
+    let SitemapNumber(number) = number.map_err(|_| Status::NotFound)?;
+    let urls = urls(&mut db, config).await?;
+    let page = sitemap::page(&urls, number).ok_or(Status::NotFound)?;
+    Ok((ContentType::XML, sitemap::urlset(page).map_err(fail)?))
+}
+
+#[get("/robots.txt")]
+fn robots(config: &State<Config>) -> (ContentType, String) {
+    let sitemap = config.site.url_of("/sitemap.xml");
+    (
+        ContentType::Plain,
+        format!("{}Sitemap: {}\n", config.robots, sitemap),
+    )
+}
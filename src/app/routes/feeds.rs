@@ -0,0 +1,285 @@
+//! `feed.atom`, `feed.rss` and `feed.json` feeds of the whole blog, of a tag
+//! and of an author
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Cursor;
+
+use chrono::{DateTime, Utc};
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::{FromParam, Request};
+use rocket::response::{Responder, Response, Result as ResponseResult};
+use rocket::{Route, State};
+use rocket_db_pools::Connection;
+
+use super::{fail, published};
+use crate::api::Pagination;
+use crate::app::core::database::models::{Post, PostDetails, PostFilter, Tag, User};
+use crate::app::core::feed::{Entry, Feed, FeedFormat};
+use crate::config::Config;
+use crate::database::DbConnection;
+
+pub fn collect() -> Vec<Route> {
+    routes![blog, tag, author]
+}
+
+/// Feed files are named `feed.<extension>`, requests for other names being
+/// forwarded to the next routes
+impl<'a> FromParam<'a> for FeedFormat {
+    type Error = &'a str;
+
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        param
+            .strip_prefix("feed.")
+            .and_then(|extension| extension.parse().ok())
+            .ok_or(param)
+    }
+}
+
+/* ----------------------------------------- Responder ----------------------------------------- */
+
+/// Rendered feed, answered with `304 Not Modified` when the client already
+/// holds it according to its `If-None-Match` or else `If-Modified-Since` header
+pub struct FeedResponse {
+    format: FeedFormat,
+    body: String,
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl FeedResponse {
+    fn etag(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.body.hash(&mut hasher);
+        format!("\"{:016x}\"", hasher.finish())
+    }
+
+    fn is_fresh(&self, req: &Request<'_>, etag: &str) -> bool {
+        if let Some(tags) = req.headers().get_one("If-None-Match") {
+            return tags
+                .split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == etag || tag == "*");
+        }
+        let since = req
+            .headers()
+            .get_one("If-Modified-Since")
+            .and_then(|date| DateTime::parse_from_rfc2822(date).ok());
+        match (self.last_modified, since) {
+            (Some(modified), Some(since)) => modified.timestamp() <= since.timestamp(),
+            _ => false,
+        }
+    }
+}
+
+fn content_type(format: FeedFormat) -> ContentType {
+    match format {
+        FeedFormat::Atom => ContentType::new("application", "atom+xml"),
+        FeedFormat::Rss => ContentType::new("application", "rss+xml"),
+        FeedFormat::Json => ContentType::new("application", "feed+json"),
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, 'a: 'r> Responder<'r, 'a> for FeedResponse {
+    fn respond_to(self, req: &'r Request<'_>) -> ResponseResult<'a> {
+        let etag = self.etag();
+        let mut response = Response::build();
+        response.header(Header::new("ETag", etag.clone()));
+        if let Some(modified) = self.last_modified {
+            let date = modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+            response.header(Header::new("Last-Modified", date));
+        }
+
+        if self.is_fresh(req, &etag) {
+            return response.status(Status::NotModified).ok();
+        }
+        response
+            .header(content_type(self.format))
+            .sized_body(self.body.len(), Cursor::new(self.body))
+            .ok()
+    }
+}
+
+/* ------------------------------------------- Routes ------------------------------------------ */
+
+/// Feed of the latest posts matching the filter
+async fn respond(
+    db: &mut Connection<DbConnection>,
+    config: &Config,
+    format: FeedFormat,
+    title: String,
+    path: &str,
+    filter: &PostFilter<'_>,
+) -> Result<FeedResponse, Status> {
+    let pagination = Pagination {
+        page: 1,
+        per_page: config.feed.entries,
+    };
+    let posts = Post::list(db, filter, pagination).await.map_err(fail)?;
+    let posts = PostDetails::load(db, posts).await.map_err(fail)?;
+    let usernames: Vec<&str> = posts.iter().map(|post| post.post.author.as_str()).collect();
+    let authors = User::find_all_by_username(db, &usernames)
+        .await
+        .map_err(fail)?;
+
+    let feed = Feed {
+        title,
+        description: config.site.description.clone(),
+        home_url: config.site.url_of(path),
+        feed_url: config
+            .site
+            .url_of(&format!("{}feed.{}", path, format.extension())),
+        entries: posts
+            .iter()
+            .filter_map(|post| {
+                let author = authors.iter().find(|a| a.username == post.post.author)?;
+                Some(Entry::new(&config.site, config.feed.content, post, author))
+            })
+            .collect(),
+    };
+    Ok(FeedResponse {
+        format,
+        body: feed.render(format).map_err(fail)?,
+        last_modified: feed.updated(),
+    })
+}
+
+#[get("/<format>")]
+async fn blog(
+    mut db: Connection<DbConnection>,
+    config: &State<Config>,
+    format: FeedFormat,
+) -> Result<FeedResponse, Status> {
+    let title = config.site.title.clone();
+    respond(&mut db, config, format, title, "/", &published(None)).await
+}
+
+#[get("/tags/<slug>/<format>")]
+async fn tag(
+    mut db: Connection<DbConnection>,
+    config: &State<Config>,
+    slug: &str,
+    format: FeedFormat,
+) -> Result<FeedResponse, Status> {
+    let tag = Tag::find_by_slug(&mut db, slug).await.map_err(fail)?;
+    let title = format!("{} · #{}", config.site.title, tag.name);
+    let path = format!("/tags/{}/", tag.slug);
+    let filter = PostFilter {
+        tag: Some(slug),
+        ..published(None)
+    };
+    respond(&mut db, config, format, title, &path, &filter).await
+}
+
+#[get("/authors/<username>/<format>")]
+async fn author(
+    mut db: Connection<DbConnection>,
+    config: &State<Config>,
+    username: &str,
+    format: FeedFormat,
+) -> Result<FeedResponse, Status> {
+    let author = User::find_by_username(&mut db, username)
+        .await
+        .map_err(fail)?;
+    let title = format!("{} · {}", config.site.title, author.display_name);
+    let path = format!("/authors/{}/", author.username);
+    respond(
+        &mut db,
+        config,
+        format,
+        title,
+        &path,
+        &published(Some(username)),
+    )
+    .await
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::blocking::Client;
+
+    use super::FeedResponse;
+    use crate::app::core::feed::FeedFormat;
+
+    #[get("/<format>")]
+    fn feed(format: FeedFormat) -> Result<FeedResponse, Status> {
+        let modified: DateTime<Utc> = "2024-11-02T10:00:00Z".parse().unwrap();
+        Ok(FeedResponse {
+            format,
+            body: "<feed/>".to_string(),
+            last_modified: Some(modified),
+        })
+    }
+
+    #[get("/<_>", rank = 2)]
+    fn page() -> &'static str {
+        "page"
+    }
+
+    fn client() -> Client {
+        Client::tracked(rocket::build().mount("/", routes![feed, page])).unwrap()
+    }
+
+    #[test]
+    fn validators() {
+        let client = client();
+        let response = client.get("/feed.atom").dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("application", "atom+xml"))
+        );
+        assert_eq!(
+            response.headers().get_one("Last-Modified"),
+            Some("Sat, 02 Nov 2024 10:00:00 GMT")
+        );
+        assert!(response.headers().get_one("ETag").is_some());
+        assert_eq!(
+            client.get("/feed.xml").dispatch().into_string().as_deref(),
+            Some("page")
+        );
+    }
+
+    #[test]
+    fn conditional_requests() {
+        let client = client();
+        let etag = client
+            .get("/feed.rss")
+            .dispatch()
+            .headers()
+            .get_one("ETag")
+            .unwrap()
+            .to_string();
+
+        let cached = client
+            .get("/feed.rss")
+            .header(Header::new("If-None-Match", format!("W/{}", etag)))
+            .dispatch();
+        assert_eq!(cached.status(), Status::NotModified);
+        assert!(cached.into_string().is_none());
+
+        let stale = client
+            .get("/feed.rss")
+            .header(Header::new("If-None-Match", "\"other\""))
+            .header(Header::new(
+                "If-Modified-Since",
+                "Sun, 03 Nov 2024 10:00:00 GMT",
+            ))
+            .dispatch();
+        assert_eq!(stale.status(), Status::Ok);
+
+        let since = |date: &str| {
+            client
+                .get("/feed.rss")
+                .header(Header::new("If-Modified-Since", date.to_string()))
+                .dispatch()
+                .status()
+        };
+        assert_eq!(since("Sat, 02 Nov 2024 10:00:00 GMT"), Status::NotModified);
+        assert_eq!(since("Fri, 01 Nov 2024 10:00:00 GMT"), Status::Ok);
+    }
+}
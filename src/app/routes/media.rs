@@ -0,0 +1,103 @@
+//! `/media` files uploaded by authors, and renditions of the images among them
+
+use rocket::http::{ContentType, Header, Status};
+use rocket::tokio::task;
+use rocket::{Route, State};
+use rocket_db_pools::Connection;
+
+use super::fail;
+use crate::app::core::database::models::Media;
+use crate::app::core::rendition::{ImageFormat, Rendition};
+use crate::app::core::storage::Storage;
+use crate::config::Config;
+use crate::database::DbConnection;
+use crate::result::RenderError;
+
+pub fn collect() -> Vec<Route> {
+    routes![file]
+}
+
+/// Uploaded file or rendition, cached for good as its content never changes
+/// under its hash
+#[derive(Responder)]
+struct MediaFile {
+    data: Vec<u8>,
+    content_type: ContentType,
+    cache_control: Header<'static>,
+    content_type_options: Header<'static>,
+}
+
+impl MediaFile {
+    fn new(data: Vec<u8>, mime_type: &str) -> Self {
+        MediaFile {
+            data,
+            content_type: ContentType::parse_flexible(mime_type).unwrap_or(ContentType::Binary),
+            cache_control: Header::new("Cache-Control", "public, max-age=31536000, immutable"),
+            content_type_options: Header::new("X-Content-Type-Options", "nosniff"),
+        }
+    }
+}
+
+/// Rendition of an image in the requested size and format, generated and
+/// stored on its first request
+async fn rendition(
+    storage: &dyn Storage,
+    media: &Media,
+    rendition: Rendition,
+) -> Result<MediaFile, Status> {
+    let key = rendition.key(&media.hash);
+    let mime_type = rendition.format.mime_type();
+    if let Some(data) = storage.get(&key).await.map_err(fail)? {
+        return Ok(MediaFile::new(data, mime_type));
+    }
+
+    let original = storage
+        .get(&media.hash)
+        .await
+        .map_err(fail)?
+        .ok_or(Status::NotFound)?;
+    let data = task::spawn_blocking(move || rendition.render(&original))
+        .await
+        .map_err(|_| Status::InternalServerError)?
+        .map_err(|e| fail(RenderError::from(e)))?;
+    storage.put(&key, mime_type, &data).await.map_err(fail)?;
+    Ok(MediaFile::new(data, mime_type))
+}
+
+/// Original file, or rendition of an image given a width `w`, a height `h`,
+/// both to crop it, or a format `fmt`
+#[get("/media/<hash>?<w>&<h>&<fmt>")]
+async fn file(
+    mut db: Connection<DbConnection>,
+    config: &State<Config>,
+    storage: &State<Box<dyn Storage>>,
+    hash: &str,
+    w: Option<u32>,
+    h: Option<u32>,
+    fmt: Option<&str>,
+) -> Result<MediaFile, Status> {
+    let media = Media::find_by_hash(&mut db, hash).await.map_err(fail)?;
+    if w.is_none() && h.is_none() && fmt.is_none() {
+        let data = storage
+            .get(&media.hash)
+            .await
+            .map_err(fail)?
+            .ok_or(Status::NotFound)?;
+        return Ok(MediaFile::new(data, &media.mime_type));
+    }
+
+    let original = ImageFormat::of_original(&media.mime_type).ok_or(Status::NotFound)?;
+    let format = match fmt {
+        Some(fmt) => fmt.parse().map_err(|_| Status::BadRequest)?,
+        None => original,
+    };
+    let requested = Rendition {
+        width: w,
+        height: h,
+        format,
+    };
+    if !requested.is_allowed(&config.media.sizes) {
+        return Err(Status::BadRequest);
+    }
+    rendition(storage.as_ref(), &media, requested).await
+}
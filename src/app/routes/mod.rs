@@ -3,15 +3,178 @@
 //! Group all routes of the application, classified by module, respecting the
 //! route path.
 
-mod api;
+pub mod api;
+mod assets;
+mod authors;
+mod crawlers;
+mod feeds;
+mod media;
+mod posts;
+mod redirects;
+mod tags;
 
-use rocket::Route;
+use rocket::http::Status;
+use rocket::request::Outcome;
+use rocket::response::content::RawHtml;
+use rocket::response::Redirect as Redirection;
+use rocket::{Catcher, Request, Route, State};
+use rocket_db_pools::Connection;
+use serde::Serialize;
+use tera::Context;
+
+use crate::api::Pagination;
+use crate::app::core::database::models::{Post, PostDetails, PostFilter, User, Visibility};
+use crate::app::core::theme::Theme;
+use crate::database::DbConnection;
+use crate::result::{ConfigurationError, Error};
+use crate::security::{Accounts, Role};
 
 pub fn collect() -> Vec<Route> {
-    routes!(index)
+    [
+        routes![index],
+        assets::collect(),
+        authors::collect(),
+        crawlers::collect(),
+        feeds::collect(),
+        media::collect(),
+        posts::collect(),
+        tags::collect(),
+    ]
+    .concat()
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    catchers![not_found, unprocessable]
+}
+
+/* ----------------------------------------- Accounts ------------------------------------------ */
+
+/// [Accounts] of the users table, for the authentication guards
+pub struct Users;
+
+#[rocket::async_trait]
+impl Accounts for Users {
+    async fn role(
+        &self,
+        req: &Request<'_>,
+        username: &str,
+    ) -> Outcome<Option<Role>, Error<'static>> {
+        let mut db = match req.guard::<Connection<DbConnection>>().await {
+            Outcome::Success(db) => db,
+            Outcome::Error((status, _)) => {
+                return Outcome::Error((status, ConfigurationError::missing("database").into()))
+            }
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+        match User::find_by_username(&mut db, username).await {
+            Ok(user) => Outcome::Success((!user.disabled).then_some(user.role)),
+            Err(diesel::result::Error::NotFound) => Outcome::Success(None),
+            Err(e) => Outcome::Error((Status::InternalServerError, e.into())),
+        }
+    }
+}
+
+/* ------------------------------------------- Pages ------------------------------------------- */
+
+/// Rendered page, or the status for which a catcher renders the page
+type Page = std::result::Result<RawHtml<String>, Status>;
+
+/// Public view of a [User], leaving out anything readers have no business with
+#[derive(Serialize)]
+struct Author<'a> {
+    username: &'a str,
+    display_name: &'a str,
+    bio: &'a str,
+}
+
+impl<'a> From<&'a User> for Author<'a> {
+    fn from(user: &'a User) -> Self {
+        Author {
+            username: &user.username,
+            display_name: &user.display_name,
+            bio: &user.bio,
+        }
+    }
+}
+
+/// Position of a page of posts, for templates to link the neighbouring ones
+#[derive(Serialize)]
+struct Paging {
+    page: i64,
+    has_next: bool,
+}
+
+impl Paging {
+    fn of(pagination: Pagination, posts: &[PostDetails]) -> Self {
+        Paging {
+            page: pagination.page,
+            has_next: posts.len() as i64 == pagination.per_page,
+        }
+    }
+}
+
+/// Filter of the posts readers may see
+fn published(author: Option<&str>) -> PostFilter<'_> {
+    PostFilter {
+        visibility: Visibility::Public,
+        author,
+        ..PostFilter::default()
+    }
+}
+
+fn render(theme: &Theme, template: &str, context: &Context) -> Page {
+    theme.render(template, context).map(RawHtml).map_err(fail)
+}
+
+/// Unknown resources are left to the `404` catcher, other errors are logged
+fn fail<'a, E: Into<Error<'a>>>(error: E) -> Status {
+    match error.into() {
+        Error::NotFound => Status::NotFound,
+        error => {
+            error!("Page rendering failed: {}", error);
+            Status::InternalServerError
+        }
+    }
+}
+
+#[get("/?<pagination..>")]
+async fn index(
+    mut db: Connection<DbConnection>,
+    theme: &State<Theme>,
+    pagination: Pagination,
+) -> Page {
+    let posts = Post::list(&mut db, &published(None), pagination)
+        .await
+        .map_err(fail)?;
+    let posts = PostDetails::load(&mut db, posts).await.map_err(fail)?;
+
+    let mut context = Context::new();
+    context.insert("pagination", &Paging::of(pagination, &posts));
+    context.insert("posts", &posts);
+    render(theme, "home.html", &context)
+}
+
+/// Former paths of posts are redirected to these, the `404` page of the theme
+/// being rendered for other paths
+async fn missing(req: &Request<'_>) -> std::result::Result<Redirection, (Status, Page)> {
+    if let Some(redirection) = redirects::moved(req).await {
+        return Ok(redirection);
+    }
+    let theme = req.rocket().state::<Theme>().ok_or(Status::NotFound);
+    let mut context = Context::new();
+    context.insert("path", req.uri().path().as_str());
+    let page = theme.and_then(|theme| render(theme, "404.html", &context));
+    Err((Status::NotFound, page))
+}
+
+#[catch(404)]
+async fn not_found(req: &Request<'_>) -> std::result::Result<Redirection, (Status, Page)> {
+    missing(req).await
 }
 
-#[get("/")]
-fn index() -> &'static str {
-    "Hello, world!"
+/// Paths routes forwarded for a segment or query they cannot parse, such as
+/// feeds of an unknown format, are no pages either
+#[catch(422)]
+async fn unprocessable(req: &Request<'_>) -> std::result::Result<Redirection, (Status, Page)> {
+    missing(req).await
 }
@@ -0,0 +1,49 @@
+//! `/posts` pages
+
+use rocket::http::Status;
+use rocket::{Route, State};
+use rocket_db_pools::Connection;
+use tera::Context;
+
+use super::{fail, render, Author, Page};
+use crate::app::core::comments;
+use crate::app::core::database::models::{
+    Comment, CommentStatus, Post, PostDetails, PostStatus, User,
+};
+use crate::app::core::theme::Theme;
+use crate::config::Config;
+use crate::database::DbConnection;
+
+pub fn collect() -> Vec<Route> {
+    routes![get]
+}
+
+#[get("/posts/<slug>")]
+async fn get(
+    mut db: Connection<DbConnection>,
+    config: &State<Config>,
+    theme: &State<Theme>,
+    slug: &str,
+) -> Page {
+    let post = Post::find_by_slug(&mut db, slug).await.map_err(fail)?;
+    if post.status != PostStatus::Published {
+        return Err(Status::NotFound);
+    }
+    let author = User::find_by_username(&mut db, &post.author)
+        .await
+        .map_err(fail)?;
+
+    let approved = match config.comments.enabled {
+        true => Comment::for_post(&mut db, post.id, CommentStatus::Approved)
+            .await
+            .map_err(fail)?,
+        false => Vec::new(),
+    };
+    let post = PostDetails::load_one(&mut db, post).await.map_err(fail)?;
+
+    let mut context = Context::new();
+    context.insert("author", &Author::from(&author));
+    context.insert("post", &post);
+    context.insert("comments", &comments::thread(approved));
+    render(theme, "post.html", &context)
+}
@@ -0,0 +1,108 @@
+//! Former URLs of imported posts, permanently redirected to the posts by the
+//! `404` catcher whichever route failed to find them
+
+use rocket::http::Method;
+use rocket::response::Redirect as Redirection;
+use rocket::Request;
+use rocket_db_pools::Connection;
+
+use super::fail;
+use crate::app::core::database::models::Redirect;
+use crate::app::core::import::redirect_path;
+use crate::database::DbConnection;
+
+/// Redirection to the published post the requested path formerly led to, if
+/// any
+pub async fn moved(req: &Request<'_>) -> Option<Redirection> {
+    if !matches!(req.method(), Method::Get | Method::Head) {
+        return None;
+    }
+    let path = redirect_path(req.uri().path().as_str())?;
+    let mut db = req.guard::<Connection<DbConnection>>().await.succeeded()?;
+    let slug = Redirect::target(&mut db, &path).await.map_err(fail).ok()?;
+    Some(Redirection::moved(format!("/posts/{}", slug)))
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use diesel::sql_query;
+    use rocket::http::Status;
+    use rocket::local::asynchronous::Client;
+    use rocket_db_pools::diesel::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+    use rocket_db_pools::Database;
+
+    use crate::app::core::theme::Theme;
+    use crate::config::{MediaConfig, ThemeConfig};
+    use crate::database::DbConnection;
+
+    /// Route of the posts, none being found
+    #[get("/posts/<_>")]
+    fn post() -> Status {
+        Status::NotFound
+    }
+
+    const FIXTURES: &str = "
+        INSERT INTO users (username, email, password_hash, display_name)
+            VALUES ('redirected', 'redirected@example.com', '', 'Redirected');
+        INSERT INTO posts (slug, title, status, author)
+            VALUES ('redirected', 'Redirected', 'published', 'redirected');
+        INSERT INTO redirects (path, post_id)
+            SELECT path, id FROM posts, (VALUES ('/2024/01/31/hello'), ('/posts/hello')) AS p(path)
+            WHERE slug = 'redirected';
+    ";
+
+    const CLEANUP: &str = "
+        DELETE FROM redirects WHERE post_id IN (SELECT id FROM posts WHERE slug = 'redirected');
+        DELETE FROM posts WHERE slug = 'redirected';
+        DELETE FROM users WHERE username = 'redirected';
+    ";
+
+    async fn execute(conn: &mut AsyncPgConnection, statements: &str) {
+        for statement in statements.split(';').filter(|s| !s.trim().is_empty()) {
+            sql_query(statement).execute(conn).await.unwrap();
+        }
+    }
+
+    #[rocket::async_test]
+    #[ignore = "needs the migrated database of POLAR_TEST_DATABASE_URL"]
+    async fn former_paths() {
+        let url = std::env::var("POLAR_TEST_DATABASE_URL").unwrap();
+        let mut conn = AsyncPgConnection::establish(&url).await.unwrap();
+        execute(&mut conn, CLEANUP).await;
+        execute(&mut conn, FIXTURES).await;
+
+        let figment = rocket::Config::figment()
+            .merge((format!("databases.{}.url", DbConnection::NAME), &url));
+        let rocket = rocket::custom(figment)
+            .attach(DbConnection::init())
+            .manage(Theme::load(&ThemeConfig::default(), &MediaConfig::default()).unwrap())
+            .mount("/", routes![post])
+            .register("/", super::super::catchers());
+        let client = Client::tracked(rocket).await.unwrap();
+
+        for path in [
+            "/2024/01/31/hello/",
+            "/posts/hello",
+            "/2024/01/31/hello?ref=feed",
+        ] {
+            let response = client.get(path).dispatch().await;
+            assert_eq!(response.status(), Status::MovedPermanently, "{}", path);
+            assert_eq!(
+                response.headers().get_one("Location"),
+                Some("/posts/redirected")
+            );
+        }
+        assert_eq!(
+            client.get("/2024/01/31/other").dispatch().await.status(),
+            Status::NotFound
+        );
+        assert_eq!(
+            client.post("/posts/hello").dispatch().await.status(),
+            Status::NotFound
+        );
+
+        execute(&mut conn, CLEANUP).await;
+    }
+}
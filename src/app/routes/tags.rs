@@ -0,0 +1,39 @@
+//! `/tags` pages
+
+use rocket::{Route, State};
+use rocket_db_pools::Connection;
+use tera::Context;
+
+use super::{fail, published, render, Page, Paging};
+use crate::api::Pagination;
+use crate::app::core::database::models::{Post, PostDetails, PostFilter, Tag};
+use crate::app::core::theme::Theme;
+use crate::database::DbConnection;
+
+pub fn collect() -> Vec<Route> {
+    routes![get]
+}
+
+#[get("/tags/<slug>?<pagination..>")]
+async fn get(
+    mut db: Connection<DbConnection>,
+    theme: &State<Theme>,
+    slug: &str,
+    pagination: Pagination,
+) -> Page {
+    let tag = Tag::find_by_slug(&mut db, slug).await.map_err(fail)?;
+    let filter = PostFilter {
+        tag: Some(slug),
+        ..published(None)
+    };
+    let posts = Post::list(&mut db, &filter, pagination)
+        .await
+        .map_err(fail)?;
+    let posts = PostDetails::load(&mut db, posts).await.map_err(fail)?;
+
+    let mut context = Context::new();
+    context.insert("pagination", &Paging::of(pagination, &posts));
+    context.insert("posts", &posts);
+    context.insert("tag", &tag);
+    render(theme, "tag.html", &context)
+}
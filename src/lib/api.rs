@@ -1,15 +1,71 @@
 use std::io::Cursor;
 
-use rocket::http::{ContentType, Status};
+use clap::ValueEnum;
+use rocket::http::{ContentType, Header, MediaType, Status};
 use rocket::request::Request;
 use rocket::response::{Responder, Response, Result as ResponseResult};
-use rocket::serde::{
-    json::serde_json::{self, json},
-    Serialize,
-};
+use rocket::serde::{json::serde_json::json, Serialize};
 
+use crate::lib::cli::DumpFormat;
 use crate::lib::result::Error as ApiError;
 
+// Content negotiation
+
+/// Envelope of TOML and XML documents, both formats requiring a single root
+/// table or element whatever the shape of the response.
+#[derive(Serialize)]
+#[serde(rename = "response")]
+struct Document<'a, T: Serialize> {
+    data: &'a T,
+}
+
+fn format_of(media_type: &MediaType) -> Option<DumpFormat> {
+    let top = media_type.top().as_str().to_ascii_lowercase();
+    let sub = media_type.sub().as_str().to_ascii_lowercase();
+    match (top.as_str(), sub.as_str()) {
+        ("*", "*") | ("application", "*") | ("application", "json") => Some(DumpFormat::Json),
+        ("application" | "text", "yaml" | "x-yaml") => Some(DumpFormat::Yaml),
+        ("application" | "text", "toml") => Some(DumpFormat::Toml),
+        ("application" | "text", "xml") => Some(DumpFormat::Xml),
+        _ => None,
+    }
+}
+
+fn content_type(format: DumpFormat) -> ContentType {
+    match format {
+        DumpFormat::Json => ContentType::JSON,
+        DumpFormat::Yaml => ContentType::new("application", "yaml"),
+        DumpFormat::Toml => ContentType::new("application", "toml"),
+        DumpFormat::Xml => ContentType::new("application", "xml"),
+    }
+}
+
+/// Pick the format of a response body from the `format` query parameter if
+/// any, from the `Accept` header otherwise, JSON being the default. `None` is
+/// returned when no supported format is acceptable to the client.
+pub fn negotiate(req: &Request<'_>) -> Option<DumpFormat> {
+    if let Some(format) = req.query_value::<&str>("format") {
+        return format
+            .ok()
+            .and_then(|format| DumpFormat::from_str(format, true).ok());
+    }
+
+    let accept = match req.accept() {
+        Some(accept) => accept,
+        None => return Some(DumpFormat::Json),
+    };
+
+    // Stable sort, equally weighted media types keep the client's order
+    let mut preferences: Vec<_> = accept
+        .iter()
+        .filter(|media_type| media_type.weight_or(1.0) > 0.0)
+        .collect();
+    preferences.sort_by(|a, b| b.weight_or(1.0).total_cmp(&a.weight_or(1.0)));
+    preferences
+        .into_iter()
+        .find_map(|media_type| format_of(media_type.media_type()))
+}
+
 // Api response definition
 
 #[derive(Debug)]
@@ -20,7 +76,7 @@ pub struct ApiResponse<T: Serialize> {
 
 impl<T: Serialize> ApiResponse<T> {
     #[inline]
-    fn new(response: T, status: Status) -> Self {
+    pub fn new(response: T, status: Status) -> Self {
         Self {
             response: Some(response),
             status,
@@ -28,12 +84,12 @@ impl<T: Serialize> ApiResponse<T> {
     }
 
     #[inline]
-    fn ok(response: T) -> Self {
+    pub fn ok(response: T) -> Self {
         Self::new(response, Status::Ok)
     }
 
     #[inline]
-    fn empty(status: Status) -> Self {
+    pub fn empty(status: Status) -> Self {
         Self {
             response: None,
             status,
@@ -41,54 +97,309 @@ impl<T: Serialize> ApiResponse<T> {
     }
 
     #[inline]
-    fn created(response: T) -> Self {
+    pub fn created(response: T) -> Self {
         Self::new(response, Status::Created)
     }
 
     #[inline]
-    fn accepted(response: T) -> Self {
+    pub fn accepted(response: T) -> Self {
         Self::new(response, Status::Accepted)
     }
 
     #[inline]
-    fn no_content() -> Self {
+    pub fn no_content() -> Self {
         Self::empty(Status::NoContent)
     }
 }
 
 #[rocket::async_trait]
 impl<'r, 'a: 'r, T: Serialize> Responder<'r, 'a> for ApiResponse<T> {
-    fn respond_to(self, _: &'r Request<'_>) -> ResponseResult<'a> {
-        serde_json::to_string(&self.response)
-            .map(|body| {
-                Response::build()
-                    .header(ContentType::JSON)
-                    .status(self.status)
-                    .sized_body(body.len(), Cursor::new(body))
-                    .finalize()
-            })
-            .map_err(|_| Status::InternalServerError)
+    fn respond_to(self, req: &'r Request<'_>) -> ResponseResult<'a> {
+        let response = match &self.response {
+            Some(response) => response,
+            None => return Response::build().status(self.status).ok(),
+        };
+
+        let format = negotiate(req).ok_or(Status::NotAcceptable)?;
+        let body = match format {
+            DumpFormat::Json | DumpFormat::Yaml => format.to_string(response),
+            DumpFormat::Toml | DumpFormat::Xml => format.to_string(&Document { data: response }),
+        }
+        .map_err(|_| Status::InternalServerError)?;
+
+        Response::build()
+            .header(content_type(format))
+            .header(Header::new("Vary", "Accept"))
+            .status(self.status)
+            .sized_body(body.len(), Cursor::new(body))
+            .ok()
+    }
+}
+
+// Pagination
+
+/// Paging query parameters shared by every listing endpoint
+#[derive(Debug, Clone, Copy, FromForm)]
+pub struct Pagination {
+    /// Bounded for offsets to stay far from overflowing
+    #[field(default = 1, validate = range(1..=1_000_000))]
+    pub page: i64,
+    #[field(default = 20, validate = range(1..=100))]
+    pub per_page: i64,
+}
+
+impl Pagination {
+    #[inline]
+    pub fn limit(&self) -> i64 {
+        self.per_page
+    }
+
+    #[inline]
+    pub fn offset(&self) -> i64 {
+        (self.page - 1) * self.per_page
+    }
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Pagination {
+            page: 1,
+            per_page: 20,
+        }
     }
 }
 
 // Api error definition
 
-fn error_status(error: ApiError) -> Status {
+fn error_status(error: &ApiError) -> Status {
     match error {
-        // TODO - Add error types
         ApiError::NotFound => Status::NotFound,
+        ApiError::Malformed(_) => Status::BadRequest,
+        ApiError::Validation(_) => Status::UnprocessableEntity,
+        ApiError::Conflict(_) => Status::Conflict,
+        ApiError::Unauthorized(_) => Status::Unauthorized,
+        ApiError::Forbidden => Status::Forbidden,
+        ApiError::NotAcceptable => Status::NotAcceptable,
+        ApiError::PayloadTooLarge => Status::PayloadTooLarge,
+        ApiError::RateLimited => Status::TooManyRequests,
         _ => Status::InternalServerError,
     }
 }
 
+/// Stable, machine-readable identifier of an error, clients may rely on it
+fn error_code(error: &ApiError) -> &'static str {
+    match error {
+        ApiError::NotFound => "not_found",
+        ApiError::Malformed(_) => "malformed_request",
+        ApiError::Validation(_) => "validation_failed",
+        ApiError::Conflict(_) => "conflict",
+        ApiError::Unauthorized(_) => "unauthorized",
+        ApiError::Forbidden => "forbidden",
+        ApiError::NotAcceptable => "not_acceptable",
+        ApiError::PayloadTooLarge => "payload_too_large",
+        ApiError::RateLimited => "rate_limited",
+        _ => "internal_error",
+    }
+}
+
+/// Errors are served as RFC 7807 problem details, the [error_code] being both
+/// part of the problem `type` and given on its own as a `code` extension
 #[rocket::async_trait]
 impl<'r, 'a: 'r> Responder<'r, 'a> for ApiError<'a> {
-    fn respond_to(self, _: &'r Request<'_>) -> ResponseResult<'a> {
-        let body = json!({ "reason": format!("{}", &self) }).to_string();
+    fn respond_to(self, req: &'r Request<'_>) -> ResponseResult<'a> {
+        let status = error_status(&self);
+        let code = error_code(&self);
+        let mut problem = json!({
+            "type": format!("urn:polar:problem:{}", code),
+            "title": status.reason_lossy(),
+            "status": status.code,
+            "detail": format!("{}", &self),
+            "instance": req.uri().path().as_str(),
+            "code": code,
+        });
+        if let ApiError::Validation(errors) = &self {
+            problem["errors"] = json!(errors);
+        }
+
+        let body = problem.to_string();
         Response::build()
-            .header(ContentType::JSON)
-            .status(error_status(self))
+            .header(ContentType::new("application", "problem+json"))
+            .status(status)
             .sized_body(body.len(), Cursor::new(body))
             .ok()
     }
 }
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::blocking::Client;
+    use rocket::serde::json::Value;
+
+    use serde::Serialize;
+
+    use super::{error_code, error_status, ApiError, ApiResponse, Pagination};
+    use crate::lib::result::FieldError;
+
+    #[derive(Serialize)]
+    struct Item {
+        id: i32,
+        name: &'static str,
+    }
+
+    #[get("/items")]
+    fn items() -> ApiResponse<Vec<Item>> {
+        ApiResponse::ok(vec![Item { id: 1, name: "a" }, Item { id: 2, name: "b" }])
+    }
+
+    #[get("/conflict")]
+    fn conflict() -> Result<(), ApiError<'static>> {
+        Err(ApiError::Conflict("posts_slug_key violated".to_string()))
+    }
+
+    #[get("/invalid")]
+    fn invalid() -> Result<(), ApiError<'static>> {
+        Err(ApiError::Validation(vec![
+            FieldError::new("slug", "must be lowercase"),
+            FieldError::new("title", "may not be empty"),
+        ]))
+    }
+
+    #[get("/offset?<pagination..>")]
+    fn offset(pagination: Pagination) -> String {
+        pagination.offset().to_string()
+    }
+
+    fn client() -> Client {
+        let rocket = rocket::build().mount("/", routes![conflict, invalid, items, offset]);
+        Client::tracked(rocket).unwrap()
+    }
+
+    #[test]
+    fn status_mapping() {
+        let invalid = ApiError::Validation(vec![FieldError::new("title", "may not be empty")]);
+
+        assert_eq!(error_status(&ApiError::NotFound), Status::NotFound);
+        assert_eq!(error_status(&invalid), Status::UnprocessableEntity);
+        assert_eq!(error_status(&ApiError::Forbidden), Status::Forbidden);
+        assert_eq!(
+            error_status(&ApiError::RateLimited),
+            Status::TooManyRequests
+        );
+        assert_eq!(error_code(&invalid), "validation_failed");
+        assert_eq!(
+            error_status(&ApiError::Malformed("invalid JSON")),
+            Status::BadRequest
+        );
+        assert_eq!(
+            error_code(&ApiError::Malformed("invalid JSON")),
+            "malformed_request"
+        );
+        assert_eq!(error_code(&ApiError::PayloadTooLarge), "payload_too_large");
+    }
+
+    #[test]
+    fn problem_details() {
+        let client = client();
+        let response = client.get("/conflict").dispatch();
+
+        assert_eq!(response.status(), Status::Conflict);
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("application", "problem+json"))
+        );
+        let body: Value = response.into_json().unwrap();
+        assert_eq!(body["type"], "urn:polar:problem:conflict");
+        assert_eq!(body["title"], "Conflict");
+        assert_eq!(body["status"], 409);
+        assert_eq!(body["detail"], "Conflict: posts_slug_key violated");
+        assert_eq!(body["instance"], "/conflict");
+        assert_eq!(body["code"], "conflict");
+        assert!(body.get("errors").is_none());
+    }
+
+    #[test]
+    fn problem_field_errors() {
+        let client = client();
+        let body: Value = client.get("/invalid").dispatch().into_json().unwrap();
+
+        assert_eq!(body["status"], 422);
+        assert_eq!(body["errors"][0]["field"], "slug");
+        assert_eq!(body["errors"][1]["message"], "may not be empty");
+    }
+
+    #[test]
+    fn pagination_bounds() {
+        let client = client();
+        let response = client.get("/offset?page=3&per_page=50").dispatch();
+        assert_eq!(response.into_string().unwrap(), "100");
+
+        let response = client
+            .get("/offset?page=9223372036854775807&per_page=100")
+            .dispatch();
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+    }
+
+    #[test]
+    fn negotiate_default_json() {
+        let client = client();
+        let response = client.get("/items").dispatch();
+
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        assert_eq!(
+            response.into_string().unwrap(),
+            r#"[{"id":1,"name":"a"},{"id":2,"name":"b"}]"#
+        );
+    }
+
+    #[test]
+    fn negotiate_accept_header() {
+        let client = client();
+        let response = client
+            .get("/items")
+            .header(Header::new(
+                "Accept",
+                "text/html, application/xml;q=0.9, */*;q=0.1",
+            ))
+            .dispatch();
+
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("application", "xml"))
+        );
+        assert_eq!(
+            response.into_string().unwrap(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><response><data><id>1</id><name>a</name></data><data><id>2</id><name>b</name></data></response>"
+        );
+    }
+
+    #[test]
+    fn negotiate_query_over_header() {
+        let client = client();
+        let response = client
+            .get("/items?format=toml")
+            .header(Header::new("Accept", "application/json"))
+            .dispatch();
+
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("application", "toml"))
+        );
+        assert!(response.into_string().unwrap().starts_with("[[data]]"));
+    }
+
+    #[test]
+    fn negotiate_not_acceptable() {
+        let client = client();
+        let response = client
+            .get("/items")
+            .header(Header::new("Accept", "image/png"))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotAcceptable);
+
+        let response = client.get("/items?format=csv").dispatch();
+        assert_eq!(response.status(), Status::NotAcceptable);
+    }
+}
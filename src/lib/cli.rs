@@ -1,12 +1,13 @@
 use crate::result::SerdeError;
+use crate::security::Role;
 use clap::{Args, Parser, Subcommand, ValueEnum};
 use figment::{
     map,
     value::{Dict, Map, Value},
     Error as FigmentError, Metadata, Profile, Provider,
 };
+use quick_xml::se as serde_xml;
 use serde::{Deserialize, Serialize};
-use serde_xml_rs as serde_xml;
 use std::collections::BTreeMap;
 use toml as serde_toml;
 
@@ -57,6 +58,28 @@ fn serve_data<'a>(data: &Serve) -> Map<&'a str, Value> {
     })
 }
 
+#[inline]
+fn build_data<'a>(data: &Build) -> Map<&'a str, Value> {
+    let database = map! {
+        "host" => ref_str(&data.database_host).map(Value::from),
+        "port" => data.database_port.map(Value::from),
+        "user" => ref_str(&data.database_user).map(Value::from),
+        "password" => ref_str(&data.database_password).map(Value::from),
+        "schema" => ref_str(&data.database_schema).map(Value::from)
+    };
+
+    let site = map! {
+        "url" => ref_str(&data.site_url).map(Value::from)
+    };
+
+    filter_none(map! {
+        // Pages are requested locally, the server has nothing to report
+        "log_level" => Some(Value::from("critical")),
+        "database" => Some(filter_none(database).into()),
+        "site" => Some(filter_none(site).into()),
+    })
+}
+
 #[inline]
 fn serve_dump<'a>(data: &Show) -> Map<&'a str, Value> {
     let fmt = data.format.unwrap_or(DumpFormat::Json);
@@ -65,6 +88,10 @@ fn serve_dump<'a>(data: &Show) -> Map<&'a str, Value> {
 
 /* ------------------------------------------ Format ------------------------------------------- */
 
+/// Declaration opening XML documents, as written before quick-xml replaced
+/// serde-xml-rs
+const XML_DECLARATION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+
 /// The file format in which the configuration should be dumped
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Serialize, Deserialize)]
 pub enum DumpFormat {
@@ -79,7 +106,7 @@ impl DumpFormat {
         Ok(match self {
             DumpFormat::Json => serde_json::to_string(value)?,
             DumpFormat::Yaml => serde_yaml::to_string(value)?,
-            DumpFormat::Xml => serde_xml::to_string(value)?,
+            DumpFormat::Xml => format!("{}{}", XML_DECLARATION, serde_xml::to_string(value)?),
             DumpFormat::Toml => serde_toml::to_string(value)?,
         })
     }
@@ -171,6 +198,41 @@ impl Default for Serve {
     }
 }
 
+// Build
+
+/// Export the published posts, tag and author pages, feeds, sitemaps and media
+/// as static files, for a read-only mirror of the blog
+#[derive(Args)]
+pub struct Build {
+    /// Directory to write the files to, created as needed
+    #[clap(short, long)]
+    pub out: String,
+
+    /// Public URL of the mirror, absolute links being built upon it
+    #[clap(long)]
+    pub site_url: Option<String>,
+
+    /// Database IP address to connect to
+    #[clap(short = 'd', long)]
+    pub database_host: Option<String>,
+
+    /// Database port number to connect to
+    #[clap(short = 'n', long)]
+    pub database_port: Option<u16>,
+
+    /// Username with which polar will authenticate to the database
+    #[clap(short = 'u', long)]
+    pub database_user: Option<String>,
+
+    /// Password with which polar will authenticate to the database
+    #[clap(short = 'w', long)]
+    pub database_password: Option<String>,
+
+    /// Database schema to use
+    #[clap(short = 's', long)]
+    pub database_schema: Option<String>,
+}
+
 // Show
 
 /// Dump Polar current active configuration to standard output
@@ -181,13 +243,159 @@ pub struct Show {
     pub format: Option<DumpFormat>,
 }
 
+// User
+
+/// Manage Polar user accounts
+#[derive(Args)]
+pub struct User {
+    #[clap(subcommand)]
+    pub action: UserAction,
+}
+
+#[derive(Subcommand)]
+pub enum UserAction {
+    /// Create a new user account, prompting for its password
+    Create(UserCreate),
+    /// List all user accounts
+    List,
+    /// Change the password of a user account
+    Passwd(UserPasswd),
+    /// Prevent a user account from logging in
+    Disable(UserDisable),
+}
+
+#[derive(Args)]
+pub struct UserCreate {
+    /// Unique name with which the user logs in
+    pub username: String,
+
+    /// Unique email address of the user
+    #[clap(short, long)]
+    pub email: String,
+
+    /// Name under which the user publishes, defaults to the username
+    #[clap(short = 'n', long)]
+    pub display_name: Option<String>,
+
+    /// Short presentation of the user
+    #[clap(short, long)]
+    pub bio: Option<String>,
+
+    /// Role granting the user its rights
+    #[clap(value_enum, short, long, default_value_t = Role::Author)]
+    pub role: Role,
+
+    /// Read the password from the first line of standard input instead of prompting
+    #[clap(long)]
+    pub password_stdin: bool,
+}
+
+#[derive(Args)]
+pub struct UserPasswd {
+    /// Name of the user whose password is changed
+    pub username: String,
+
+    /// Read the password from the first line of standard input instead of prompting
+    #[clap(long)]
+    pub password_stdin: bool,
+}
+
+#[derive(Args)]
+pub struct UserDisable {
+    /// Name of the user to disable
+    pub username: String,
+}
+
+// Theme
+
+/// Manage the themes of Polar rendered pages
+#[derive(Args)]
+pub struct Theme {
+    #[clap(subcommand)]
+    pub action: ThemeAction,
+}
+
+#[derive(Subcommand)]
+pub enum ThemeAction {
+    /// Print the stylesheet coloring highlighted code blocks
+    Css(ThemeCss),
+    /// List the available code highlighting themes
+    List,
+}
+
+#[derive(Args)]
+pub struct ThemeCss {
+    /// Name of the highlighting theme, as given by `polar theme list`
+    #[clap(default_value = "InspiredGitHub")]
+    pub name: String,
+}
+
+// Import
+
+/// Import posts written for another blog engine
+#[derive(Args)]
+pub struct Import {
+    #[clap(subcommand)]
+    pub action: ImportAction,
+}
+
+#[derive(Subcommand)]
+pub enum ImportAction {
+    /// Import a directory of Markdown files with YAML or TOML front matter
+    Markdown(ImportMarkdown),
+    /// Import a WordPress eXtended RSS export, as written by Tools > Export
+    Wordpress(ImportWordpress),
+}
+
+#[derive(Args)]
+pub struct ImportMarkdown {
+    /// Directory of the posts, read along with its subdirectories
+    pub directory: String,
+
+    /// Author of the posts naming none, created as needed like the others
+    #[clap(short, long)]
+    pub author: String,
+
+    /// Pattern of the former URLs of posts giving none, such as
+    /// `/:year/:month/:day/:slug/`
+    #[clap(short, long)]
+    pub permalink: Option<String>,
+
+    /// Only print what the import would create
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args)]
+pub struct ImportWordpress {
+    /// Export file
+    pub file: String,
+
+    /// Author of the posts naming none, created as needed like the others
+    #[clap(short, long)]
+    pub author: String,
+
+    /// Fetch the uploaded files into the media storage, pointing the posts to
+    /// them, instead of leaving the posts linking to the former blog
+    #[clap(long)]
+    pub fetch_media: bool,
+
+    /// Only print what the import would create
+    #[clap(long)]
+    pub dry_run: bool,
+}
+
 // Commands
 
 #[derive(Subcommand)]
 pub enum Command {
     Migrate(Migrate),
     Serve(Serve),
+    Build(Build),
     Show(Show),
+    User(User),
+    Theme(Theme),
+    Import(Import),
 }
 
 // Args
@@ -226,7 +434,9 @@ impl Provider for Cli {
         let data = match &self.command {
             Command::Migrate(migrate) => migrate_data(migrate),
             Command::Serve(serve) => serve_data(serve),
+            Command::Build(build) => build_data(build),
             Command::Show(dump) => serve_dump(dump),
+            Command::User(_) | Command::Theme(_) | Command::Import(_) => Map::new(),
         }
         .into_iter()
         .map(|(k, v)| (k.to_string(), v))
@@ -13,7 +13,7 @@ use serde::{Deserialize, Serialize};
 
 use super::cli::Cli;
 use super::database::DbConnection;
-use super::result::Error;
+use super::result::{ConfigurationError, Error};
 
 /* -------------------------------------- Util functions --------------------------------------- */
 
@@ -106,6 +106,226 @@ impl Default for SecurityConfig {
     }
 }
 
+/* ---------------------------------------- Site Config ---------------------------------------- */
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SiteConfig {
+    /// Public URL of the blog, absolute links being built upon it
+    pub url: String,
+    pub title: String,
+    pub description: String,
+}
+
+impl SiteConfig {
+    /// Absolute URL of the given path
+    pub fn url_of(&self, path: &str) -> String {
+        format!("{}{}", self.url.trim_end_matches('/'), path)
+    }
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        SiteConfig {
+            url: "http://127.0.0.1:8080".to_string(),
+            title: "Polar".to_string(),
+            description: String::new(),
+        }
+    }
+}
+
+/* ---------------------------------------- Feed Config ---------------------------------------- */
+
+/// What feed entries carry of their post
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedContent {
+    /// The whole rendered body
+    Full,
+    /// A plain text opening of the body
+    Summary,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FeedConfig {
+    pub content: FeedContent,
+    /// Number of latest posts a feed lists, from 1 to 100
+    pub entries: i64,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        FeedConfig {
+            content: FeedContent::Full,
+            entries: 20,
+        }
+    }
+}
+
+/* --------------------------------------- Robots Config --------------------------------------- */
+
+/// Group of `robots.txt` rules applying to the given user agent
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RobotsRule {
+    pub user_agent: String,
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub disallow: Vec<String>,
+    /// Seconds crawlers should wait between requests, not every one honors it
+    pub crawl_delay: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RobotsConfig {
+    pub rules: Vec<RobotsRule>,
+}
+
+impl Default for RobotsConfig {
+    fn default() -> Self {
+        RobotsConfig {
+            rules: vec![RobotsRule {
+                user_agent: "*".to_string(),
+                allow: Vec::new(),
+                disallow: vec!["/api/".to_string()],
+                crawl_delay: None,
+            }],
+        }
+    }
+}
+
+/// Rules in the `robots.txt` format, the sitemap being left to the caller
+impl Display for RobotsConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for rule in &self.rules {
+            writeln!(f, "User-agent: {}", rule.user_agent)?;
+            for path in &rule.allow {
+                writeln!(f, "Allow: {}", path)?;
+            }
+            for path in &rule.disallow {
+                writeln!(f, "Disallow: {}", path)?;
+            }
+            if let Some(delay) = rule.crawl_delay {
+                writeln!(f, "Crawl-delay: {}", delay)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/* -------------------------------------- Comments Config -------------------------------------- */
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct CommentsConfig {
+    /// Whether readers may comment at all, posts having their own toggle too
+    pub enabled: bool,
+    /// Whether readers may comment without being logged in
+    pub anonymous: bool,
+    /// Whether new comments wait for an approval before being shown, those of
+    /// editors and administrators never do
+    pub moderated: bool,
+    /// Spam probability above which new comments are set aside as spam rather
+    /// than queued, 1 disabling the filter
+    pub spam_threshold: f32,
+}
+
+impl Default for CommentsConfig {
+    fn default() -> Self {
+        CommentsConfig {
+            enabled: true,
+            anonymous: true,
+            moderated: true,
+            spam_threshold: 0.9,
+        }
+    }
+}
+
+/* --------------------------------------- Search Config --------------------------------------- */
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SearchConfig {
+    /// Text search configuration posts are indexed with unless they pick their
+    /// own, as listed by `\dF` in psql
+    pub language: String,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            language: "english".to_string(),
+        }
+    }
+}
+
+/* ---------------------------------------- Media Config --------------------------------------- */
+
+/// Where uploaded files are kept
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum StorageConfig {
+    /// Files of a local directory, created as needed
+    Local { directory: String },
+    /// Bucket of an S3-compatible object storage such as MinIO, addressed
+    /// path-style
+    S3 {
+        /// Base URL of the storage, such as `http://127.0.0.1:9000` or
+        /// `https://s3.eu-west-3.amazonaws.com`
+        endpoint: String,
+        region: String,
+        bucket: String,
+        access_key: String,
+        secret_key: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MediaConfig {
+    /// MIME types of the files authors may upload, their size being bounded by
+    /// the `file/<extension>` limits
+    pub types: Vec<String>,
+    /// Widths and heights in pixels image renditions may be requested in,
+    /// responsive images being offered in each of them
+    pub sizes: Vec<u32>,
+    /// EXIF tags kept in uploaded images, all other metadata being stripped
+    pub keep_metadata: Vec<String>,
+    pub storage: StorageConfig,
+}
+
+impl Default for MediaConfig {
+    fn default() -> Self {
+        MediaConfig {
+            types: ["image/jpeg", "image/png", "image/gif", "image/webp"]
+                .map(String::from)
+                .to_vec(),
+            sizes: vec![320, 640, 960, 1280, 1920],
+            keep_metadata: vec!["Orientation".to_string(), "Copyright".to_string()],
+            storage: StorageConfig::Local {
+                directory: "media".to_string(),
+            },
+        }
+    }
+}
+
+/* ---------------------------------------- Theme Config --------------------------------------- */
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ThemeConfig {
+    /// Directory of templates overriding the built-in ones, files of its
+    /// `static` subdirectory being served under `/static`
+    pub directory: Option<String>,
+    /// Code highlighting theme, as listed by `polar theme list`
+    pub highlight: String,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        ThemeConfig {
+            directory: None,
+            highlight: "InspiredGitHub".to_string(),
+        }
+    }
+}
+
 /* -------------------------------------- General Config --------------------------------------- */
 
 /// General Polar startup and runtime configuration store, attached to rocket as
@@ -147,13 +367,20 @@ impl Default for SecurityConfig {
 /// ```
 ///
 /// Said endpoint would return the number 600 to any consumer.
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Config {
     pub address: String,
     pub port: u16,
 
     pub security: SecurityConfig,
     pub database: DatabaseConfig,
+    pub site: SiteConfig,
+    pub feed: FeedConfig,
+    pub robots: RobotsConfig,
+    pub comments: CommentsConfig,
+    pub search: SearchConfig,
+    pub media: MediaConfig,
+    pub theme: ThemeConfig,
 }
 
 impl Default for Config {
@@ -164,6 +391,13 @@ impl Default for Config {
 
             security: SecurityConfig::default(),
             database: DatabaseConfig::default(),
+            site: SiteConfig::default(),
+            feed: FeedConfig::default(),
+            robots: RobotsConfig::default(),
+            comments: CommentsConfig::default(),
+            search: SearchConfig::default(),
+            media: MediaConfig::default(),
+            theme: ThemeConfig::default(),
         }
     }
 }
@@ -204,6 +438,14 @@ impl Config {
 
         Ok(with_db_pool(config)?.select(profile.as_str()))
     }
+
+    /// Reject the values which deserialize but the blog cannot work with
+    pub fn validate<'a>(&self) -> Result<(), Error<'a>> {
+        if !(1..=100).contains(&self.feed.entries) {
+            return Err(ConfigurationError::misconfigured("feed.entries").into());
+        }
+        Ok(())
+    }
 }
 
 /* ------------------------------------------- Tests ------------------------------------------- */
@@ -211,10 +453,11 @@ impl Config {
 #[cfg(test)]
 mod tests {
     use super::{
-        super::cli::{Cli, Command, Serve},
-        Config,
+        super::cli::{Cli, Command, DumpFormat, Serve},
+        Config, StorageConfig,
     };
     use crate::lib::config::from_file;
+    use crate::lib::result::{ConfigurationError, Error};
     use figment::{Error as FigmentError, Figment, Jail, Profile};
 
     fn cli(
@@ -436,6 +679,72 @@ mod tests {
         })
     }
 
+    #[test]
+    fn file_robots_rules() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "polar.toml",
+                r#"
+                [[default.robots.rules]]
+                user_agent = "*"
+                disallow = ["/api/", "/drafts/"]
+
+                [[default.robots.rules]]
+                user_agent = "SlowBot"
+                allow = ["/"]
+                crawl_delay = 10
+                "#,
+            )?;
+
+            let config: Config = Figment::from(Config::default())
+                .merge(from_file(Some("polar.toml")).unwrap())
+                .extract()?;
+
+            assert_eq!(
+                config.robots.to_string(),
+                "User-agent: *\nDisallow: /api/\nDisallow: /drafts/\n\n\
+                 User-agent: SlowBot\nAllow: /\nCrawl-delay: 10\n\n"
+            );
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn file_s3_storage() {
+        Jail::expect_with(|jail| {
+            jail.create_file(
+                "polar.toml",
+                r#"
+                [default.media.storage]
+                backend = "s3"
+                endpoint = "http://127.0.0.1:9000"
+                region = "us-east-1"
+                bucket = "polar"
+                access_key = "minioadmin"
+                secret_key = "minioadmin"
+                "#,
+            )?;
+
+            let config: Config = Figment::from(Config::default())
+                .merge(from_file(Some("polar.toml")).unwrap())
+                .extract()?;
+
+            assert_eq!(
+                config.media.storage,
+                StorageConfig::S3 {
+                    endpoint: "http://127.0.0.1:9000".to_string(),
+                    region: "us-east-1".to_string(),
+                    bucket: "polar".to_string(),
+                    access_key: "minioadmin".to_string(),
+                    secret_key: "minioadmin".to_string(),
+                }
+            );
+
+            Ok(())
+        })
+    }
+
     // Args and file
 
     #[test]
@@ -677,4 +986,48 @@ mod tests {
             Ok(())
         })
     }
+
+    #[test]
+    fn feed_entries_bounds() {
+        let mut config = Config::default();
+        assert!(config.validate().is_ok());
+
+        for entries in [0, 101] {
+            config.feed.entries = entries;
+            assert!(matches!(
+                config.validate(),
+                Err(Error::ConfigurationError(
+                    ConfigurationError::MisconfiguredEntry("feed.entries")
+                ))
+            ));
+        }
+    }
+
+    #[test]
+    fn xml_dump() {
+        assert_eq!(
+            DumpFormat::Xml.to_string(&Config::default()).unwrap(),
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?><Config>"#,
+                "<address>127.0.0.1</address><port>8080</port>",
+                "<security><jwt_secret>secret</jwt_secret><jwt_lifetime>900</jwt_lifetime></security>",
+                "<database><host>127.0.0.1</host><port>5432</port><user>polar</user>",
+                "<password>polar</password><schema>polar</schema></database>",
+                "<site><url>http://127.0.0.1:8080</url><title>Polar</title><description/></site>",
+                "<feed><content>full</content><entries>20</entries></feed>",
+                "<robots><rules><user_agent>*</user_agent><disallow>/api/</disallow>",
+                "<crawl_delay/></rules></robots>",
+                "<comments><enabled>true</enabled><anonymous>true</anonymous>",
+                "<moderated>true</moderated><spam_threshold>0.9</spam_threshold></comments>",
+                "<search><language>english</language></search>",
+                "<media><types>image/jpeg</types><types>image/png</types><types>image/gif</types>",
+                "<types>image/webp</types><sizes>320</sizes><sizes>640</sizes><sizes>960</sizes>",
+                "<sizes>1280</sizes><sizes>1920</sizes><keep_metadata>Orientation</keep_metadata>",
+                "<keep_metadata>Copyright</keep_metadata>",
+                "<storage><backend>local</backend><directory>media</directory></storage></media>",
+                "<theme><directory/><highlight>InspiredGitHub</highlight></theme>",
+                "</Config>"
+            )
+        );
+    }
 }
@@ -3,9 +3,37 @@ use crate::result::DatabaseError;
 use diesel::pg::PgConnection;
 use diesel::Connection;
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
-use rocket_db_pools::diesel::PgPool;
+use rocket_db_pools::diesel::{AsyncConnection, AsyncPgConnection, PgPool};
 use rocket_db_pools::Database;
 
+/// Implement the Diesel `Text` conversions of an enum stored as a string,
+/// relying on its `as_str` method and its `FromStr` implementation.
+macro_rules! text_enum_sql {
+    ($ty:ty) => {
+        impl diesel::serialize::ToSql<diesel::sql_types::Text, diesel::pg::Pg> for $ty {
+            fn to_sql<'b>(
+                &'b self,
+                out: &mut diesel::serialize::Output<'b, '_, diesel::pg::Pg>,
+            ) -> diesel::serialize::Result {
+                std::io::Write::write_all(out, self.as_str().as_bytes())?;
+                Ok(diesel::serialize::IsNull::No)
+            }
+        }
+
+        impl diesel::deserialize::FromSql<diesel::sql_types::Text, diesel::pg::Pg> for $ty {
+            fn from_sql(bytes: diesel::pg::PgValue<'_>) -> diesel::deserialize::Result<Self> {
+                let value = <String as diesel::deserialize::FromSql<
+                    diesel::sql_types::Text,
+                    diesel::pg::Pg,
+                >>::from_sql(bytes)?;
+                Ok(value.parse()?)
+            }
+        }
+    };
+}
+
+pub(crate) use text_enum_sql;
+
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("./resources/migrations/postgres");
 
 #[derive(Database)]
@@ -18,6 +46,14 @@ pub fn establish_connection(db_config: &DatabaseConfig) -> Result<PgConnection,
     Ok(conn)
 }
 
+pub async fn establish_async_connection(
+    db_config: &DatabaseConfig,
+) -> Result<AsyncPgConnection, DatabaseError> {
+    let url = db_config.to_string();
+    let conn = AsyncPgConnection::establish(&url).await?;
+    Ok(conn)
+}
+
 pub fn migrate(db_config: &DatabaseConfig) -> Result<(), DatabaseError> {
     let mut conn = establish_connection(db_config)?;
     conn.run_pending_migrations(MIGRATIONS)?;
@@ -1,16 +1,24 @@
 use crate::lib::result::ConfigurationError::{MisconfiguredEntry, MissingEntry};
+use argon2::password_hash::Error as HashError;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
 use diesel::ConnectionError;
+use image::ImageError;
+use jsonwebtoken::errors::Error as JwtError;
 use std::error::Error as StdError;
 use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
 use std::io::Error as IOError;
 use std::option::Option;
 use std::result::Result as StdResult;
 
+use quick_xml::SeError as SerdeXmlError;
+use reqwest::Error as HttpError;
 use rocket::figment::Error as FigmentError;
 use rocket::Error as RocketError;
+use serde::Serialize;
 use serde_json::Error as SerdeJsonError;
-use serde_xml_rs::Error as SerdeXmlError;
 use serde_yaml::Error as SerdeYamlError;
+use syntect::Error as HighlightError;
+use tera::Error as TemplateError;
 use toml::ser::Error as SerdeTomlError;
 
 use tokio_postgres::Error as TokioPgError;
@@ -120,6 +128,7 @@ pub enum DatabaseError {
     ConnectionError(ConnectionError),
     MigrationError(Box<dyn StdError + Send + Sync>),
     TokioPgError(TokioPgError),
+    QueryError(DieselError),
 }
 
 impl Display for DatabaseError {
@@ -128,6 +137,7 @@ impl Display for DatabaseError {
             DatabaseError::ConnectionError(ce) => Display::fmt(ce, f),
             DatabaseError::MigrationError(rme) => Display::fmt(rme, f),
             DatabaseError::TokioPgError(tpge) => Display::fmt(tpge, f),
+            DatabaseError::QueryError(qe) => Display::fmt(qe, f),
         }
     }
 }
@@ -138,6 +148,7 @@ impl StdError for DatabaseError {
             DatabaseError::ConnectionError(ce) => ce.source(),
             DatabaseError::MigrationError(rme) => rme.source(),
             DatabaseError::TokioPgError(tpge) => tpge.source(),
+            DatabaseError::QueryError(qe) => qe.source(),
         }
     }
 }
@@ -160,17 +171,278 @@ impl From<TokioPgError> for DatabaseError {
     }
 }
 
+impl From<DieselError> for DatabaseError {
+    fn from(qe: DieselError) -> Self {
+        DatabaseError::QueryError(qe)
+    }
+}
+
+// --------------------------------------------------------------------------------- Security Error
+
+#[derive(Debug)]
+pub enum SecurityError {
+    TokenError(JwtError),
+    HashError(HashError),
+}
+
+impl Display for SecurityError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            SecurityError::TokenError(te) => Display::fmt(te, f),
+            SecurityError::HashError(he) => Display::fmt(he, f),
+        }
+    }
+}
+
+impl StdError for SecurityError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            SecurityError::TokenError(te) => te.source(),
+            SecurityError::HashError(he) => he.source(),
+        }
+    }
+}
+
+impl From<JwtError> for SecurityError {
+    fn from(te: JwtError) -> Self {
+        SecurityError::TokenError(te)
+    }
+}
+
+impl From<HashError> for SecurityError {
+    fn from(he: HashError) -> Self {
+        SecurityError::HashError(he)
+    }
+}
+
+// ----------------------------------------------------------------------------------- Render Error
+
+#[derive(Debug)]
+pub enum RenderError {
+    UnknownTheme(String),
+    HighlightError(HighlightError),
+    TemplateError(TemplateError),
+    ImageError(ImageError),
+}
+
+impl Display for RenderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            RenderError::UnknownTheme(name) => write!(f, "Unknown highlighting theme {}", name),
+            RenderError::HighlightError(he) => Display::fmt(he, f),
+            RenderError::TemplateError(te) => Display::fmt(te, f),
+            RenderError::ImageError(ie) => Display::fmt(ie, f),
+        }
+    }
+}
+
+impl StdError for RenderError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            RenderError::UnknownTheme(_) => None,
+            RenderError::HighlightError(he) => he.source(),
+            RenderError::TemplateError(te) => te.source(),
+            RenderError::ImageError(ie) => ie.source(),
+        }
+    }
+}
+
+impl From<HighlightError> for RenderError {
+    fn from(he: HighlightError) -> Self {
+        RenderError::HighlightError(he)
+    }
+}
+
+impl From<TemplateError> for RenderError {
+    fn from(te: TemplateError) -> Self {
+        RenderError::TemplateError(te)
+    }
+}
+
+impl From<ImageError> for RenderError {
+    fn from(ie: ImageError) -> Self {
+        RenderError::ImageError(ie)
+    }
+}
+
+// ---------------------------------------------------------------------------------- Storage Error
+
+#[derive(Debug)]
+pub enum StorageError {
+    InvalidKey(String),
+    InvalidEndpoint(String),
+    IoError(IOError),
+    HttpError(HttpError),
+    /// Unexpected response of a remote storage, along with its status code
+    BackendError(u16, String),
+}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            StorageError::InvalidKey(key) => write!(f, "Invalid storage key {}", key),
+            StorageError::InvalidEndpoint(url) => write!(f, "Invalid storage endpoint {}", url),
+            StorageError::IoError(ioe) => Display::fmt(ioe, f),
+            StorageError::HttpError(he) => Display::fmt(he, f),
+            StorageError::BackendError(status, message) => {
+                write!(f, "Storage answered {}: {}", status, message)
+            }
+        }
+    }
+}
+
+impl StdError for StorageError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            StorageError::IoError(ioe) => ioe.source(),
+            StorageError::HttpError(he) => he.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<IOError> for StorageError {
+    fn from(ioe: IOError) -> Self {
+        StorageError::IoError(ioe)
+    }
+}
+
+impl From<HttpError> for StorageError {
+    fn from(he: HttpError) -> Self {
+        StorageError::HttpError(he)
+    }
+}
+
+// ----------------------------------------------------------------------------------- Export Error
+
+#[derive(Debug)]
+pub enum ExportError {
+    IoError(IOError),
+    /// Unexpected status answered for the given path
+    UnexpectedStatus(String, u16),
+}
+
+impl Display for ExportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            ExportError::IoError(ioe) => Display::fmt(ioe, f),
+            ExportError::UnexpectedStatus(path, status) => {
+                write!(f, "Exporting {} failed with status {}", path, status)
+            }
+        }
+    }
+}
+
+impl StdError for ExportError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            ExportError::IoError(ioe) => ioe.source(),
+            ExportError::UnexpectedStatus(_, _) => None,
+        }
+    }
+}
+
+impl From<IOError> for ExportError {
+    fn from(ioe: IOError) -> Self {
+        ExportError::IoError(ioe)
+    }
+}
+
+// ----------------------------------------------------------------------------------- Import Error
+
+#[derive(Debug)]
+pub enum ImportError {
+    IoError(IOError),
+    /// Export which cannot be read, for the given reason
+    InvalidExport(String),
+    /// Problems the import plan lists, nothing being imported
+    Problems(usize),
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            ImportError::IoError(ioe) => Display::fmt(ioe, f),
+            ImportError::InvalidExport(reason) => write!(f, "Invalid export: {}", reason),
+            ImportError::Problems(count) => {
+                write!(f, "Nothing imported, {} problems to solve first", count)
+            }
+        }
+    }
+}
+
+impl StdError for ImportError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            ImportError::IoError(ioe) => ioe.source(),
+            ImportError::InvalidExport(_) | ImportError::Problems(_) => None,
+        }
+    }
+}
+
+impl From<IOError> for ImportError {
+    fn from(ioe: IOError) -> Self {
+        ImportError::IoError(ioe)
+    }
+}
+
+// ------------------------------------------------------------------------------- Validation Error
+
+/// Reason why the value of a given input field was rejected
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError<'a> {
+    pub field: &'a str,
+    pub message: String,
+}
+
+impl<'a> FieldError<'a> {
+    pub fn new<S: Into<String>>(field: &'a str, message: S) -> Self {
+        FieldError {
+            field,
+            message: message.into(),
+        }
+    }
+}
+
+impl<'a> Display for FieldError<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} {}", self.field, self.message)
+    }
+}
+
+/// Fail with [Error::Validation] if any field error was collected
+pub fn ensure_valid<'a>(errors: Vec<FieldError<'a>>) -> Result<'a, ()> {
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(Error::Validation(errors)),
+    }
+}
+
 // -------------------------------------------------------------------------------- Root Error type
 
 #[derive(Debug)]
 pub enum Error<'a> {
     // Add error types here
     NotFound,
+    /// Request which cannot be parsed, for the given reason
+    Malformed(&'a str),
+    Validation(Vec<FieldError<'a>>),
+    Conflict(String),
+    Unauthorized(&'a str),
+    Forbidden,
+    NotAcceptable,
+    PayloadTooLarge,
+    RateLimited,
     ConfigurationError(ConfigurationError<'a>),
     FigmentError(FigmentError),
     SerdeError(SerdeError),
     RocketError(RocketError),
     DatabaseError(DatabaseError),
+    SecurityError(SecurityError),
+    RenderError(RenderError),
+    StorageError(StorageError),
+    ExportError(ExportError),
+    ImportError(ImportError),
 }
 
 impl<'a> Display for Error<'a> {
@@ -178,11 +450,27 @@ impl<'a> Display for Error<'a> {
         match self {
             // Add error types here
             Error::NotFound => write!(f, "Not found"),
+            Error::Malformed(reason) => write!(f, "Malformed request: {}", reason),
+            Error::Validation(errors) => {
+                let fields: Vec<String> = errors.iter().map(FieldError::to_string).collect();
+                write!(f, "Invalid input: {}", fields.join(", "))
+            }
+            Error::Conflict(reason) => write!(f, "Conflict: {}", reason),
+            Error::Unauthorized(reason) => write!(f, "Unauthorized: {}", reason),
+            Error::Forbidden => write!(f, "Forbidden"),
+            Error::NotAcceptable => write!(f, "No acceptable representation available"),
+            Error::PayloadTooLarge => write!(f, "Payload too large"),
+            Error::RateLimited => write!(f, "Too many requests"),
             Error::ConfigurationError(ce) => Display::fmt(&ce, f),
             Error::FigmentError(fe) => Display::fmt(&fe, f),
             Error::SerdeError(se) => Display::fmt(se, f),
             Error::RocketError(re) => Display::fmt(re, f),
             Error::DatabaseError(de) => Display::fmt(de, f),
+            Error::SecurityError(se) => Display::fmt(se, f),
+            Error::RenderError(re) => Display::fmt(re, f),
+            Error::StorageError(se) => Display::fmt(se, f),
+            Error::ExportError(ee) => Display::fmt(ee, f),
+            Error::ImportError(ie) => Display::fmt(ie, f),
         }
     }
 }
@@ -196,6 +484,11 @@ impl<'a> StdError for Error<'a> {
             Error::SerdeError(e) => e.source(),
             Error::RocketError(e) => e.source(),
             Error::DatabaseError(e) => e.source(),
+            Error::SecurityError(e) => e.source(),
+            Error::RenderError(e) => e.source(),
+            Error::StorageError(e) => e.source(),
+            Error::ExportError(e) => e.source(),
+            Error::ImportError(e) => e.source(),
             _ => None,
         }
     }
@@ -269,3 +562,62 @@ impl<'a> From<DatabaseError> for Error<'a> {
         Error::DatabaseError(ce)
     }
 }
+
+impl<'a> From<DieselError> for Error<'a> {
+    fn from(qe: DieselError) -> Self {
+        match qe {
+            DieselError::NotFound => Error::NotFound,
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, info) => {
+                let constraint = info.constraint_name().unwrap_or("unique constraint");
+                Error::Conflict(format!("{} violated", constraint))
+            }
+            DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, info) => {
+                let constraint = info.constraint_name().unwrap_or("foreign key constraint");
+                Error::Conflict(format!("{} violated", constraint))
+            }
+            _ => Error::DatabaseError(qe.into()),
+        }
+    }
+}
+
+impl<'a> From<SecurityError> for Error<'a> {
+    fn from(se: SecurityError) -> Self {
+        Error::SecurityError(se)
+    }
+}
+
+impl<'a> From<JwtError> for Error<'a> {
+    fn from(te: JwtError) -> Self {
+        Error::SecurityError(SecurityError::TokenError(te))
+    }
+}
+
+impl<'a> From<RenderError> for Error<'a> {
+    fn from(re: RenderError) -> Self {
+        Error::RenderError(re)
+    }
+}
+
+impl<'a> From<TemplateError> for Error<'a> {
+    fn from(te: TemplateError) -> Self {
+        Error::RenderError(RenderError::TemplateError(te))
+    }
+}
+
+impl<'a> From<StorageError> for Error<'a> {
+    fn from(se: StorageError) -> Self {
+        Error::StorageError(se)
+    }
+}
+
+impl<'a> From<ExportError> for Error<'a> {
+    fn from(ee: ExportError) -> Self {
+        Error::ExportError(ee)
+    }
+}
+
+impl<'a> From<ImportError> for Error<'a> {
+    fn from(ie: ImportError) -> Self {
+        Error::ImportError(ie)
+    }
+}
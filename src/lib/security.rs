@@ -0,0 +1,426 @@
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use chrono::{Duration, Utc};
+use clap::ValueEnum;
+use diesel::deserialize::FromSqlRow;
+use diesel::expression::AsExpression;
+use diesel::sql_types::Text;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use serde::{Deserialize, Serialize};
+
+use super::config::Config;
+use super::database::text_enum_sql;
+use super::result::{ConfigurationError, Error, SecurityError};
+
+/* -------------------------------------------- Roles ------------------------------------------ */
+
+/// Role of a user, each role being granted the rights of the ones it
+/// compares greater to
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    ValueEnum,
+    AsExpression,
+    FromSqlRow,
+    Serialize,
+    Deserialize,
+)]
+#[diesel(sql_type = Text)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// May only read and comment
+    Reader,
+    /// May write and publish their own posts
+    Author,
+    /// May edit and publish anyone's posts
+    Editor,
+    /// May do anything, including managing users
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Reader => "reader",
+            Role::Author => "author",
+            Role::Editor => "editor",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.pad(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reader" => Ok(Role::Reader),
+            "author" => Ok(Role::Author),
+            "editor" => Ok(Role::Editor),
+            "admin" => Ok(Role::Admin),
+            other => Err(format!("Unknown role {}", other)),
+        }
+    }
+}
+
+text_enum_sql!(Role);
+
+/* ----------------------------------------- Passwords ----------------------------------------- */
+
+/// Hash a password with Argon2id and a random salt, in the PHC string format
+pub fn hash_password(password: &str) -> Result<String, SecurityError> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default().hash_password(password.as_bytes(), &salt)?;
+    Ok(hash.to_string())
+}
+
+/// Hash no password is known to match, checked against when there is no
+/// actual one so that unknown and locked accounts take as long to refuse
+const DUMMY_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$DyZWPo5/46PcWLWDN8JLYw$e+hiGsa0YS6Y7ORfxuUEo7wicPEPppdfSOhfzBru2TM";
+
+/// Check a password against a PHC string, any malformed hash never matches
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => {
+            reject_password(password);
+            false
+        }
+    }
+}
+
+/// Spend the time of a password check on an account which does not exist
+pub fn reject_password(password: &str) {
+    if let Ok(parsed) = PasswordHash::new(DUMMY_HASH) {
+        let _ = Argon2::default().verify_password(password.as_bytes(), &parsed);
+    }
+}
+
+/* ------------------------------------------- Tokens ------------------------------------------ */
+
+/// Payload of the JSON web tokens emitted by Polar
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Claims {
+    /// Username of the token bearer
+    pub sub: String,
+    /// Role of the token bearer at issuance
+    pub role: Role,
+    /// Issuance timestamp, in seconds since epoch
+    pub iat: i64,
+    /// Expiration timestamp, in seconds since epoch
+    pub exp: i64,
+}
+
+impl Claims {
+    pub fn new(subject: &str, role: Role, lifetime: u16) -> Self {
+        let now = Utc::now();
+        Claims {
+            sub: subject.to_string(),
+            role,
+            iat: now.timestamp(),
+            exp: (now + Duration::seconds(lifetime.into())).timestamp(),
+        }
+    }
+}
+
+pub fn issue_token(claims: &Claims, secret: &str) -> Result<String, SecurityError> {
+    let key = EncodingKey::from_secret(secret.as_bytes());
+    Ok(encode(&Header::default(), claims, &key)?)
+}
+
+pub fn verify_token<'a>(token: &str, secret: &str) -> Result<Claims, Error<'a>> {
+    let key = DecodingKey::from_secret(secret.as_bytes());
+    decode::<Claims>(token, &key, &Validation::default())
+        .map(|data| data.claims)
+        .map_err(|e| match e.kind() {
+            ErrorKind::ExpiredSignature => Error::Unauthorized("Expired authentication token"),
+            _ => Error::Unauthorized("Invalid authentication token"),
+        })
+}
+
+/* ------------------------------------------- Guards ------------------------------------------ */
+
+/// Accounts tokens are issued to, managed as a `Box<dyn Accounts>` so that
+/// the guards learn their current state from whoever stores them.
+#[rocket::async_trait]
+pub trait Accounts: Send + Sync {
+    /// Role the account of `username` holds now, [None] if it is unknown or
+    /// has been disabled
+    async fn role(
+        &self,
+        req: &Request<'_>,
+        username: &str,
+    ) -> Outcome<Option<Role>, Error<'static>>;
+}
+
+/// Reason of the authentication failure of a request, if any, kept in the
+/// request local cache so that catchers may report it.
+#[derive(Debug, Default)]
+pub struct AuthFailure(pub Option<&'static str>);
+
+/// Request guard succeeding only for requests bearing a valid token in their
+/// `Authorization` header, issued to an account which is still enabled. The
+/// role of the user is the one the account holds now, not the one it held
+/// when the token was issued.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// use polar::security::AuthenticatedUser;
+///
+/// #[get("/whoami")]
+/// fn whoami(user: AuthenticatedUser) -> String {
+///     user.username().to_string()
+/// }
+/// ```
+#[derive(Debug)]
+pub struct AuthenticatedUser {
+    pub claims: Claims,
+}
+
+impl AuthenticatedUser {
+    pub fn username(&self) -> &str {
+        &self.claims.sub
+    }
+
+    pub fn role(&self) -> Role {
+        self.claims.role
+    }
+
+    /// Whether the user owns a resource or holds a role overriding ownership
+    pub fn owns_or_is(&self, owner: &str, role: Role) -> bool {
+        self.username() == owner || self.role() >= role
+    }
+
+    /// Fail with [Error::Forbidden] unless the user holds at least `role`
+    pub fn require<'a>(&self, role: Role) -> Result<(), Error<'a>> {
+        match self.role() >= role {
+            true => Ok(()),
+            false => Err(Error::Forbidden),
+        }
+    }
+}
+
+fn reject<T>(req: &Request<'_>, reason: &'static str) -> Outcome<T, Error<'static>> {
+    req.local_cache(|| AuthFailure(Some(reason)));
+    Outcome::Error((Status::Unauthorized, Error::Unauthorized(reason)))
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthenticatedUser {
+    type Error = Error<'static>;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let config = match req.rocket().state::<Config>() {
+            Some(config) => config,
+            None => {
+                let error = ConfigurationError::missing("security");
+                return Outcome::Error((Status::InternalServerError, error.into()));
+            }
+        };
+
+        let token = match req.headers().get_one("Authorization") {
+            Some(header) => match header.strip_prefix("Bearer ") {
+                Some(token) => token.trim(),
+                None => return reject(req, "Unsupported authorization scheme"),
+            },
+            None => return reject(req, "Missing authentication token"),
+        };
+
+        let mut claims = match verify_token(token, &config.security.jwt_secret) {
+            Ok(claims) => claims,
+            Err(Error::Unauthorized(reason)) => return reject(req, reason),
+            Err(e) => return Outcome::Error((Status::InternalServerError, e)),
+        };
+
+        let accounts = match req.rocket().state::<Box<dyn Accounts>>() {
+            Some(accounts) => accounts,
+            None => {
+                let error = ConfigurationError::missing("accounts");
+                return Outcome::Error((Status::InternalServerError, error.into()));
+            }
+        };
+
+        // Accounts may have been disabled or their role changed since the token was issued
+        match accounts.role(req, &claims.sub).await {
+            Outcome::Success(Some(role)) => {
+                claims.role = role;
+                Outcome::Success(AuthenticatedUser { claims })
+            }
+            Outcome::Success(None) => reject(req, "Disabled account"),
+            Outcome::Error(e) => Outcome::Error(e),
+            Outcome::Forward(status) => Outcome::Forward(status),
+        }
+    }
+}
+
+/// Minimal [Role] required by an [Authorized] guard
+pub trait RoleBound: Send + Sync + 'static {
+    const ROLE: Role;
+}
+
+/// Markers to use as [Authorized] parameters
+pub mod roles {
+    use super::{Role, RoleBound};
+
+    pub struct Author;
+    pub struct Editor;
+    pub struct Admin;
+
+    impl RoleBound for Author {
+        const ROLE: Role = Role::Author;
+    }
+
+    impl RoleBound for Editor {
+        const ROLE: Role = Role::Editor;
+    }
+
+    impl RoleBound for Admin {
+        const ROLE: Role = Role::Admin;
+    }
+}
+
+/// Request guard succeeding only for authenticated users holding at least the
+/// role `R`, failing with a `403 Forbidden` otherwise.
+///
+/// # Example
+///
+/// ```rust,no_run,compile_fail
+/// use polar::security::{roles::Admin, Authorized};
+///
+/// #[delete("/cache")]
+/// fn purge(admin: Authorized<Admin>) -> String {
+///     format!("Cache purged by {}", admin.user.username())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Authorized<R: RoleBound> {
+    pub user: AuthenticatedUser,
+    role: PhantomData<R>,
+}
+
+#[rocket::async_trait]
+impl<'r, R: RoleBound> FromRequest<'r> for Authorized<R> {
+    type Error = Error<'static>;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let user = match AuthenticatedUser::from_request(req).await {
+            Outcome::Success(user) => user,
+            Outcome::Error(e) => return Outcome::Error(e),
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+
+        match user.require(R::ROLE) {
+            Ok(()) => Outcome::Success(Authorized {
+                user,
+                role: PhantomData,
+            }),
+            Err(e) => Outcome::Error((Status::Forbidden, e)),
+        }
+    }
+}
+
+/* ------------------------------------------- Tests ------------------------------------------- */
+
+#[cfg(test)]
+mod tests {
+    use argon2::password_hash::PasswordHash;
+
+    use super::{
+        hash_password, issue_token, verify_password, verify_token, Claims, Role, DUMMY_HASH,
+    };
+    use crate::lib::result::Error;
+
+    #[test]
+    fn password_roundtrip() {
+        let hash = hash_password("hunter2").unwrap();
+
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("hunter2", &hash));
+        assert!(!verify_password("hunter3", &hash));
+    }
+
+    #[test]
+    fn password_unusable_hash() {
+        assert!(!verify_password("", "!"));
+        assert!(!verify_password("!", "!"));
+        // Unknown accounts are refused only once the dummy hash is checked
+        assert!(PasswordHash::new(DUMMY_HASH).is_ok());
+        assert!(!verify_password("", DUMMY_HASH));
+    }
+
+    #[test]
+    fn token_roundtrip() {
+        let claims = Claims::new("polar", Role::Author, 60);
+        let token = issue_token(&claims, "secret").unwrap();
+
+        assert_eq!(verify_token(&token, "secret").unwrap(), claims);
+    }
+
+    #[test]
+    fn token_wrong_secret() {
+        let token = issue_token(&Claims::new("polar", Role::Author, 60), "secret").unwrap();
+
+        match verify_token(&token, "other") {
+            Err(Error::Unauthorized(reason)) => assert_eq!(reason, "Invalid authentication token"),
+            other => assert!(false, "{:?}", other),
+        }
+    }
+
+    #[test]
+    fn token_tampered() {
+        let token = issue_token(&Claims::new("polar", Role::Author, 60), "secret").unwrap();
+        let forged = issue_token(&Claims::new("admin", Role::Admin, 60), "secret").unwrap();
+
+        // Graft the payload of a token onto the signature of another
+        let mut parts: Vec<&str> = token.split('.').collect();
+        parts[1] = forged.split('.').nth(1).unwrap();
+
+        assert!(verify_token(&parts.join("."), "secret").is_err());
+    }
+
+    #[test]
+    fn token_expired() {
+        let mut claims = Claims::new("polar", Role::Author, 60);
+        claims.iat -= 3600;
+        claims.exp -= 3600;
+        let token = issue_token(&claims, "secret").unwrap();
+
+        match verify_token(&token, "secret") {
+            Err(Error::Unauthorized(reason)) => assert_eq!(reason, "Expired authentication token"),
+            other => assert!(false, "{:?}", other),
+        }
+    }
+
+    #[test]
+    fn role_hierarchy() {
+        assert!(Role::Admin > Role::Editor);
+        assert!(Role::Editor > Role::Author);
+        assert!(Role::Author > Role::Reader);
+        assert_eq!("editor".parse::<Role>(), Ok(Role::Editor));
+        assert_eq!(format!("{:<8}|", Role::Admin), "admin   |");
+    }
+}
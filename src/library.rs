@@ -11,3 +11,4 @@ pub use lib::cli;
 pub use lib::config;
 pub use lib::database;
 pub use lib::result;
+pub use lib::security;